@@ -0,0 +1,173 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+struct Levels {
+    // Input black, input white, gamma and output black
+    curve: vec4<f32>,
+    output_white: f32,
+};
+
+struct EffectParams {
+    // 0 copies the input, 1 blurs it along `direction`, 2 casts a shadow of it,
+    // 3 draws the original over it, then 4 to 7 are the fill, tint, tritone
+    // and levels color corrections
+    kind: u32,
+    repeat_edge: u32,
+    sigma: f32,
+    direction: vec2<f32>,
+    colors: array<vec4<f32>, 3>,
+    offset: vec2<f32>,
+    shadow_only: u32,
+    amount: f32,
+    // RGB, red, green, blue and alpha
+    levels: array<Levels, 5>,
+};
+
+@group(1) @binding(0)
+var input: texture_2d<f32>;
+
+@group(1) @binding(1)
+var input_sampler: sampler;
+
+@group(1) @binding(2)
+var<uniform> params: EffectParams;
+
+@group(1) @binding(3)
+var original: texture_2d<f32>;
+
+@group(1) @binding(4)
+var original_sampler: sampler;
+
+// Layer textures are drawn over a transparent target, so the area around the
+// layer is transparent unless edge pixels are repeated
+fn sample_input(uv: vec2<f32>) -> vec4<f32> {
+    let outside = any(uv < vec2(0.0)) || any(uv > vec2(1.0));
+    if outside && params.repeat_edge == 0u {
+        return vec4(0.0);
+    }
+    return textureSampleLevel(input, input_sampler, clamp(uv, vec2(0.0), vec2(1.0)), 0.0);
+}
+
+// One dimension of a gaussian blur, see `GaussianBlur::apply` in lottie-core
+// for the reference implementation
+fn gaussian_blur(uv: vec2<f32>) -> vec4<f32> {
+    let sigma = params.sigma;
+    if sigma <= 0.0 {
+        return sample_input(uv);
+    }
+    let texel = params.direction / vec2<f32>(textureDimensions(input));
+    let radius = ceil(sigma * 3.0);
+    // Wide kernels are sampled sparsely, relying on linear filtering
+    let step = max(1.0, radius / 64.0);
+    var sum = vec4(0.0);
+    var weights = 0.0;
+    for (var x = -radius; x <= radius; x += step) {
+        let weight = exp(-(x * x) / (2.0 * sigma * sigma));
+        sum += sample_input(uv + texel * x) * weight;
+        weights += weight;
+    }
+    return sum / weights;
+}
+
+// Alpha of the input moved by the shadow offset, tinted with the shadow color
+fn shadow(uv: vec2<f32>) -> vec4<f32> {
+    let offset = params.offset / vec2<f32>(textureDimensions(input));
+    let color = params.colors[0];
+    let alpha = sample_input(uv - offset).a * color.a;
+    return vec4(color.rgb * alpha, alpha);
+}
+
+// The original drawn over the blurred shadow, both premultiplied
+fn over_original(uv: vec2<f32>) -> vec4<f32> {
+    let below = sample_input(uv);
+    if params.shadow_only != 0u {
+        return below;
+    }
+    let above = textureSampleLevel(original, original_sampler, uv, 0.0);
+    return above + below * (1.0 - above.a);
+}
+
+const GAMMA = 2.2;
+
+// Luminance used by color correction effects
+fn luminance(c: vec3<f32>) -> f32 {
+    return dot(c, vec3(0.3, 0.59, 0.11));
+}
+
+// See `LevelsCurve::map` in lottie-core
+fn levels(info: Levels, value: f32) -> f32 {
+    let input_black = info.curve.x;
+    let input_white = info.curve.y;
+    let gamma = max(info.curve.z, 0.01);
+    let output_black = info.curve.w;
+    let input_delta = input_white - input_black;
+    var below = output_black;
+    var above = info.output_white;
+    if input_delta < 0.0 {
+        below = info.output_white;
+        above = output_black;
+    }
+    if value <= min(input_black, input_white) {
+        return below;
+    }
+    if value >= max(input_black, input_white) {
+        return above;
+    }
+    let t = pow((value - input_black) / input_delta, 1.0 / gamma);
+    return output_black + (info.output_white - output_black) * t;
+}
+
+// Fill, tint, tritone and levels on straight sRGB colors, see the effects of
+// the same names in lottie-core
+fn correct_color(color: vec4<f32>) -> vec4<f32> {
+    var out = color;
+    switch params.kind {
+        case 4u: { out = vec4(params.colors[0].rgb, color.a * params.amount); }
+        case 5u: {
+            let tinted = mix(params.colors[0].rgb, params.colors[1].rgb, luminance(color.rgb));
+            out = vec4(mix(color.rgb, tinted, params.amount), color.a);
+        }
+        case 6u: {
+            let l = luminance(color.rgb);
+            var toned = mix(params.colors[1].rgb, params.colors[0].rgb, l * 2.0 - 1.0);
+            if l < 0.5 {
+                toned = mix(params.colors[2].rgb, params.colors[1].rgb, l * 2.0);
+            }
+            out = vec4(mix(toned, color.rgb, params.amount), color.a);
+        }
+        case 7u: {
+            out = vec4(
+                levels(params.levels[1], levels(params.levels[0], color.r)),
+                levels(params.levels[2], levels(params.levels[0], color.g)),
+                levels(params.levels[3], levels(params.levels[0], color.b)),
+                levels(params.levels[4], color.a),
+            );
+        }
+        default: {}
+    }
+    return clamp(out, vec4(0.0), vec4(1.0));
+}
+
+// Color corrections work on straight sRGB colors, while textures hold
+// premultiplied linear ones
+fn color_correction(uv: vec2<f32>) -> vec4<f32> {
+    let pixel = sample_input(uv);
+    var color = vec4(0.0, 0.0, 0.0, pixel.a);
+    if pixel.a > 0.0 {
+        color = vec4(pow(pixel.rgb / pixel.a, vec3(1.0 / GAMMA)), pixel.a);
+    }
+    let out = correct_color(color);
+    return vec4(pow(out.rgb, vec3(GAMMA)) * out.a, out.a);
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    var out: vec4<f32>;
+    switch params.kind {
+        case 1u: { out = gaussian_blur(in.uv); }
+        case 2u: { out = shadow(in.uv); }
+        case 3u: { out = over_original(in.uv); }
+        case 4u, 5u, 6u, 7u: { out = color_correction(in.uv); }
+        default: { out = sample_input(in.uv); }
+    }
+    return out;
+}
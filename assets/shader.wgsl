@@ -2,16 +2,16 @@
 #import bevy_sprite::mesh2d_types Mesh2d
 #import bevy_sprite::mesh2d_view_bindings
 
-struct GradientStop {
-    offset: f32,
-    color: vec4<f32>,
-};
+const GAMMA = 2.200000048;
+// Luminance coefficients used by luma mattes, same as SVG's `luminanceToAlpha`
+const LUMA = vec3<f32>(0.2125, 0.7154, 0.0721);
 
 struct GradientInfo {
     start_pos: vec2<f32>,
     end_pos: vec2<f32>,
+    // 0 without gradient, 1 for linear and 2 for radial gradients
     use_gradient: u32,
-    stops: array<GradientStop, 2>
+    focal_pos: vec2<f32>,
 };
 
 struct MaskInfo {
@@ -35,6 +35,34 @@ var<uniform> mask_info: MaskInfo;
 @group(1) @binding(4)
 var<uniform> gradient: GradientInfo;
 
+@group(1) @binding(5)
+var gradient_ramp: texture_2d<f32>;
+
+@group(1) @binding(6)
+var gradient_ramp_sampler: sampler;
+
+@group(1) @binding(7)
+var<uniform> stroke_gradient: GradientInfo;
+
+@group(1) @binding(8)
+var stroke_gradient_ramp: texture_2d<f32>;
+
+@group(1) @binding(9)
+var stroke_gradient_ramp_sampler: sampler;
+
+@group(1) @binding(10)
+var<uniform> blend_mode: u32;
+
+@group(1) @binding(11)
+var backdrop: texture_2d<f32>;
+
+@group(1) @binding(12)
+var backdrop_sampler: sampler;
+
+// Perspective of the camera for 3D layers, identity otherwise
+@group(1) @binding(13)
+var<uniform> camera: mat4x4<f32>;
+
 @group(2) @binding(0)
 var<uniform> mesh: Mesh2d;
 
@@ -54,6 +82,10 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     // We pass the vertex color to the fragment shader in location 0
     @location(0) color: vec4<f32>,
+    // Position in the shape's own coordinates, where gradients are defined
+    @location(1) local_position: vec2<f32>,
+    // 0.0 for fill vertices and 1.0 for stroke vertices
+    @location(2) style: f32,
 };
 
 
@@ -63,15 +95,16 @@ fn vertex(vertex: Vertex) -> VertexOutput {
     var out: VertexOutput;
     // Project the world position of the mesh into screen position
     // out.clip_position = view.view_proj * mesh.model * vec4<f32>(vertex.position, 0.0, 1.0);
-    out.clip_position = mesh2d_position_local_to_clip(mesh.model, vec4<f32>(vertex.position.xy, 0.0, 1.0));
+    // The camera projects world positions, its w coordinate giving the perspective
+    out.clip_position = mesh2d_position_local_to_clip(camera * mesh.model, vec4<f32>(vertex.position.xy, 0.0, 1.0));
     // Unpack the `u32` from the vertex buffer into the `vec4<f32>` used by the fragment shader
     out.color = vec4<f32>((vec4<u32>(vertex.color) >> vec4<u32>(0u, 8u, 16u, 24u)) & vec4<u32>(255u)) / 255.0;
+    out.local_position = vertex.position;
+    out.style = vertex.uv.x;
     return out;
 }
 
 // https://dawn.googlesource.com/tint/+/refs/heads/chromium/4846/test/benchmark/skinned-shadowed-pbr-fragment.wgsl.expected.wgsl
-const GAMMA = 2.200000048;
-
 fn linearTosRGB(color: vec3<f32>) -> vec3<f32> {
     let INV_GAMMA = (1.0 / GAMMA);
     return pow(color, vec3(INV_GAMMA));
@@ -81,25 +114,133 @@ fn sRGBToLinear(color: vec3<f32>) -> vec3<f32> {
     return pow(color, vec3(GAMMA));
 }
 
-// https://docs.rs/lyon_geom/latest/src/lyon_geom/line.rs.html#650-655
-fn point_projection(pt: vec2<f32>, start: vec2<f32>, end: vec2<f32>) -> vec2<f32> {
+// Position of `pt` projected on the line from `start` to `end`, 0.0 at `start`
+// and 1.0 at `end`
+fn linear_gradient_offset(pt: vec2<f32>, start: vec2<f32>, end: vec2<f32>) -> f32 {
     let v = end - start;
-    var a = -v.y;
-    var b = v.x;
-    var c = -(a * start.x + b * start.y);
-    let div = 1.0 / length(vec2(a, b));
-    a = a * div;
-    b = b * div;
-    c = c * div;
-    let x = b * (b * pt.x - a * pt.y) - a * c;
-    let y = a * (a * pt.y - b * pt.x) - b * c;
-    return vec2(x, y);
+    let len = dot(v, v);
+    if len == 0.0 {
+        return 0.0;
+    }
+    return dot(pt - start, v) / len;
+}
+
+// Offset of `pt` in a radial gradient of circle (`center`, |`end` - `center`|),
+// 0.0 at `focal` and 1.0 on the circle. Each offset is reached on the circle
+// interpolated between the focal point and the gradient circle, so we solve
+// |pt - focal - t * (center - focal)| = t * radius for the positive `t`
+fn radial_gradient_offset(pt: vec2<f32>, center: vec2<f32>, end: vec2<f32>, focal: vec2<f32>) -> f32 {
+    let radius = distance(center, end);
+    if radius == 0.0 {
+        return 1.0;
+    }
+    let d = pt - focal;
+    let e = center - focal;
+    // Negative as the focal point is kept inside the circle
+    let a = dot(e, e) - radius * radius;
+    let b = dot(d, e);
+    let c = dot(d, d);
+    return (b - sqrt(max(b * b - a * c, 0.0))) / a;
+}
+
+// Offset of `pt` in the gradient described by `info`
+fn gradient_offset(info: GradientInfo, pt: vec2<f32>) -> f32 {
+    if info.use_gradient == 2u {
+        return radial_gradient_offset(pt, info.start_pos, info.end_pos, info.focal_pos);
+    }
+    return linear_gradient_offset(pt, info.start_pos, info.end_pos);
+}
+
+// Sample a gradient lookup texture, clamping `t` to the first and last texel
+// centers so the outermost stops are not blended with the border
+fn gradient_color(ramp: texture_2d<f32>, ramp_sampler: sampler, t: f32) -> vec4<f32> {
+    let width = f32(textureDimensions(ramp).x);
+    let u = (clamp(t, 0.0, 1.0) * (width - 1.0) + 0.5) / width;
+    return textureSampleLevel(ramp, ramp_sampler, vec2(u, 0.5), 0.0);
+}
+
+// Blend modes as defined by the W3C compositing spec, see `BlendMode::blend`
+// in lottie-core for the reference implementation
+fn lum(c: vec3<f32>) -> f32 {
+    return dot(c, vec3(0.3, 0.59, 0.11));
+}
+
+fn clip_color(color: vec3<f32>) -> vec3<f32> {
+    var c = color;
+    let l = lum(c);
+    let n = min(min(c.r, c.g), c.b);
+    let x = max(max(c.r, c.g), c.b);
+    if n < 0.0 {
+        c = l + (c - l) * l / (l - n);
+    }
+    if x > 1.0 {
+        c = l + (c - l) * (1.0 - l) / (x - l);
+    }
+    return c;
+}
+
+fn set_lum(c: vec3<f32>, l: f32) -> vec3<f32> {
+    return clip_color(c + (l - lum(c)));
+}
+
+fn sat(c: vec3<f32>) -> f32 {
+    return max(max(c.r, c.g), c.b) - min(min(c.r, c.g), c.b);
+}
+
+fn set_sat(c: vec3<f32>, s: f32) -> vec3<f32> {
+    let n = min(min(c.r, c.g), c.b);
+    let x = max(max(c.r, c.g), c.b);
+    if x <= n {
+        return vec3(0.0);
+    }
+    return (c - n) * s / (x - n);
+}
+
+fn hard_light(cb: vec3<f32>, cs: vec3<f32>) -> vec3<f32> {
+    let s = 2.0 * cs - 1.0;
+    return select(cb + s - cb * s, cb * 2.0 * cs, cs <= vec3(0.5));
+}
+
+fn soft_light(cb: vec3<f32>, cs: vec3<f32>) -> vec3<f32> {
+    let d = select(sqrt(cb), ((16.0 * cb - 12.0) * cb + 4.0) * cb, cb <= vec3(0.25));
+    let light = cb + (2.0 * cs - 1.0) * (d - cb);
+    let dark = cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb);
+    return select(light, dark, cs <= vec3(0.5));
+}
+
+// Mix the straight sRGB colors of a source and its backdrop
+fn blend(mode: u32, cb: vec3<f32>, cs: vec3<f32>) -> vec3<f32> {
+    var result = cs;
+    switch mode {
+        case 1u: { result = cb * cs; }
+        case 2u: { result = cb + cs - cb * cs; }
+        case 3u: { result = hard_light(cs, cb); }
+        case 4u: { result = min(cb, cs); }
+        case 5u: { result = max(cb, cs); }
+        // Dodge and burn reach their limits through the division
+        case 6u: { result = min(vec3(1.0), cb / max(1.0 - cs, vec3(1e-6))); }
+        case 7u: { result = 1.0 - min(vec3(1.0), (1.0 - cb) / max(cs, vec3(1e-6))); }
+        case 8u: { result = hard_light(cb, cs); }
+        case 9u: { result = soft_light(cb, cs); }
+        case 10u: { result = abs(cb - cs); }
+        case 11u: { result = cb + cs - 2.0 * cb * cs; }
+        case 12u: { result = set_lum(set_sat(cs, sat(cb)), lum(cb)); }
+        case 13u: { result = set_lum(set_sat(cb, sat(cs)), lum(cb)); }
+        case 14u: { result = set_lum(cs, lum(cb)); }
+        case 15u: { result = set_lum(cb, lum(cs)); }
+        case 16u: { result = min(vec3(1.0), cb + cs); }
+        case 17u: { result = select(vec3(0.0), vec3(1.0), cb + cs >= vec3(1.0)); }
+        default: {}
+    }
+    return result;
 }
 
 // The input of the fragment shader must correspond to the output of the vertex shader for all `location`s
 struct FragmentInput {
     // The color is interpolated between vertices by default
     @location(0) color: vec4<f32>,
+    @location(1) local_position: vec2<f32>,
+    @location(2) style: f32,
 };
 
 /// Entry point for the fragment shader
@@ -108,20 +249,28 @@ fn fragment(@builtin(position) position: vec4<f32>, in: FragmentInput) -> @locat
     var out: vec4<f32>;
     let scale = scene_size.z;
     let pos = position.xy / scale;
-    if gradient.use_gradient == 1u {
-        var start = (mesh.model * vec4<f32>(gradient.start_pos, 0.0, 1.0)).xy;
-        let end = (mesh.model * vec4<f32>(gradient.end_pos, 0.0, 1.0)).xy;
-        let proj = point_projection(pos.xy, start, end);
-        let inv = smoothstep(start, end, proj);
-        var t = inv.x;
-        if end.x == start.x {
-            t = inv.y;
-        }
-        let color = mix(gradient.stops[0].color.xyz, gradient.stops[1].color.xyz, t);
-        out = vec4(color, 1.0);
+    // Vertices of gradient shapes are white, carrying only the opacity
+    if in.style > 0.5 && stroke_gradient.use_gradient != 0u {
+        let t = gradient_offset(stroke_gradient, in.local_position);
+        out = gradient_color(stroke_gradient_ramp, stroke_gradient_ramp_sampler, t) * in.color;
+    } else if in.style <= 0.5 && gradient.use_gradient != 0u {
+        let t = gradient_offset(gradient, in.local_position);
+        out = gradient_color(gradient_ramp, gradient_ramp_sampler, t) * in.color;
     } else {
         out = in.color;
     }
+    if blend_mode != 0u {
+        // Like masks, the backdrop is drawn over a transparent target so its
+        // color is already multiplied by its alpha. Blending happens in sRGB
+        // space as browsers do
+        let backdrop_pixel = textureSample(backdrop, backdrop_sampler, pos / vec2<f32>(textureDimensions(backdrop)));
+        if backdrop_pixel.a > 0.0 {
+            let cb = linearTosRGB(backdrop_pixel.rgb / backdrop_pixel.a);
+            let cs = linearTosRGB(out.rgb);
+            let mixed = mix(cs, clamp(blend(blend_mode, cb, cs), vec3(0.0), vec3(1.0)), backdrop_pixel.a);
+            out = vec4(sRGBToLinear(mixed), out.a);
+        }
+    }
     let mask_size = vec2<f32>(textureDimensions(mask));
     let count = mask_info.mask_count;
     for (var i: u32 = 0u; i < count; i++) {
@@ -131,7 +280,14 @@ fn fragment(@builtin(position) position: vec4<f32>, in: FragmentInput) -> @locat
         let stride = vec2(mask_size.x / mask_count, 0.0);
         let sample_pos = (pos.xy + stride * mask_index) / mask_size;
         var mask_pixel = textureSample(mask, mask_sampler, sample_pos);
-        if info.y == 2u {
+        // Mask layers are drawn over a transparent target, so the color stored
+        // in the texture is already multiplied by its alpha
+        let luma = dot(mask_pixel.rgb, LUMA);
+        if info.y == 4u {
+            out.a *= (1.0 - luma);
+        } else if info.y == 3u {
+            out.a *= luma;
+        } else if info.y == 2u {
             out.a *= (1.0 - mask_pixel.a);
         } else if info.y == 1u {
             out.a *= mask_pixel.a;
@@ -27,3 +27,50 @@ pub enum Error {
     #[error(transparent)]
     Base64Decode(#[from] base64::DecodeError),
 }
+
+/// A problem found in a Lottie file which doesn't prevent it from being
+/// rendered, but makes the result differ from After Effects
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Diagnostic {
+    #[error("Layer {layer:?} uses effect {name:?} (type {ty}) which is not supported")]
+    UnsupportedEffect {
+        layer: Option<String>,
+        name: Option<String>,
+        ty: u8,
+    },
+    #[error("Value {index} of effect {name:?} on layer {layer:?} is malformed and ignored")]
+    InvalidEffectValue {
+        layer: Option<String>,
+        name: Option<String>,
+        index: usize,
+    },
+    #[error("Precomposition layer {layer:?} uses blend mode {mode:?}, which is not applied to its layers")]
+    PrecompositionBlendMode {
+        layer: Option<String>,
+        mode: crate::model::BlendMode,
+    },
+    #[error("Font {name:?} of family {family:?} is not available, its text is not drawn")]
+    MissingFont { name: String, family: String },
+    #[error("Font {name:?} of family {family:?} is not available, {fallback:?} is used instead")]
+    FontFallback {
+        name: String,
+        family: String,
+        fallback: String,
+    },
+    #[error("Font {name:?} could not be loaded: {reason}")]
+    FontNotLoaded { name: String, reason: String },
+    #[error("Font {font:?} of text layer {layer:?} has no glyph for {character:?}, which is drawn with {fallback:?} or else left out")]
+    MissingGlyph {
+        layer: Option<String>,
+        font: String,
+        character: char,
+        /// Family of the installed font drawing the character instead
+        fallback: Option<String>,
+    },
+    #[error("Font {family:?} resolved for font {font:?} of text layer {layer:?} is not found in its font collection, its text is drawn with the next fonts or else left out")]
+    FontFaceNotFound {
+        layer: Option<String>,
+        font: String,
+        family: String,
+    },
+}
@@ -1,17 +1,214 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::io::Read;
 use std::ops::Deref;
+use std::path::PathBuf;
 
+use crate::layer::shaping::script_runs;
 use crate::model::{Font as LottieFont, FontPathOrigin, Model};
 use fontkit::{Font, FontKey, FontKit};
+use unicode_script::Script;
 
-use crate::Error;
+use crate::{Diagnostic, Error};
 
 const BUFFER_LIMIT: usize = 10 * 1_024 * 1_024;
 
+/// Families tried, in order, when the font of a text is not installed
+#[derive(Debug, Clone)]
+pub struct FontFallbacks {
+    /// Families for text in a script, by ISO 15924 code like `Arab`
+    pub scripts: HashMap<String, Vec<String>>,
+    /// Families for generic font classes like `serif` or `monospace`
+    pub classes: HashMap<String, Vec<String>>,
+    /// Families tried last
+    pub default: Vec<String>,
+}
+
+impl Default for FontFallbacks {
+    fn default() -> Self {
+        let families = |entries: &[(&str, &[&str])]| {
+            entries
+                .iter()
+                .map(|(key, families)| {
+                    let families = families.iter().map(|family| family.to_string()).collect();
+                    (key.to_string(), families)
+                })
+                .collect()
+        };
+        FontFallbacks {
+            scripts: families(&[
+                ("Arab", &["Noto Sans Arabic", "Geeza Pro", "Arial"]),
+                ("Hebr", &["Noto Sans Hebrew", "Arial Hebrew", "Arial"]),
+                ("Deva", &["Noto Sans Devanagari", "Kohinoor Devanagari"]),
+                ("Thai", &["Noto Sans Thai", "Thonburi"]),
+                (
+                    "Hani",
+                    &["Noto Sans CJK SC", "PingFang SC", "Microsoft YaHei"],
+                ),
+                ("Hira", &["Noto Sans CJK JP", "Hiragino Sans"]),
+                ("Kana", &["Noto Sans CJK JP", "Hiragino Sans"]),
+                ("Hang", &["Noto Sans CJK KR", "Apple SD Gothic Neo"]),
+            ]),
+            classes: families(&[
+                ("serif", &["Times New Roman", "Times", "DejaVu Serif"]),
+                ("sans-serif", &["Arial", "Helvetica", "DejaVu Sans"]),
+                ("monospace", &["Courier New", "Menlo", "DejaVu Sans Mono"]),
+            ]),
+            default: vec!["Fira Mono".to_string()],
+        }
+    }
+}
+
+/// How the font of an animation was resolved
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontMatch {
+    /// Font supplied by the font provider, e.g. downloaded from its url
+    Provided,
+    /// Installed font named like the font of the animation
+    Name,
+    /// Installed font of the same family, weight and style
+    Family,
+    /// Installed font of the same family, but another weight or style
+    FamilyOnly,
+    /// Fallback for text in this script
+    Script(String),
+    /// Fallback for this generic class of fonts
+    Class(String),
+    /// Last resort fallback
+    Default,
+    /// Glyphs embedded in the animation are used instead
+    Embedded,
+    /// No font was found
+    Missing,
+}
+
+/// Installed font used for a font of an animation
+#[derive(Debug, Clone)]
+pub struct FontResolution {
+    /// Name of the font in the animation
+    pub name: String,
+    pub family: String,
+    pub weight: u32,
+    pub italic: bool,
+    /// Key of the installed font
+    pub resolved: Option<FontKey>,
+    pub matched: FontMatch,
+}
+
+impl fmt::Display for FontResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let style = |weight: u32, italic: bool| {
+            format!("{}{}", weight, if italic { " italic" } else { "" })
+        };
+        write!(
+            f,
+            "{} ({} {})",
+            self.name,
+            self.family,
+            style(self.weight, self.italic)
+        )?;
+        match &self.resolved {
+            Some(key) => write!(
+                f,
+                " -> {} {} [{:?}]",
+                key.family,
+                style(key.weight.unwrap_or(400), key.italic.unwrap_or(false)),
+                self.matched
+            ),
+            None if self.matched == FontMatch::Embedded => write!(f, " -> embedded glyphs"),
+            None => write!(f, " -> not found"),
+        }
+    }
+}
+
+/// Source of the font files of an animation which are not installed, so
+/// that renderers without network access or system fonts can supply them.
+/// Providers are kept by the `FontDB` of a `Lottie`, which renderers share
+/// between threads
+pub trait FontProvider: Send + Sync {
+    /// Font file at `url`, for fonts of the animation with a font url
+    fn font_from_url(&self, url: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Font file of `font` when no installed font matches it
+    fn font_by_name(&self, font: &LottieFont) -> Result<Option<Vec<u8>>, Error> {
+        let _ = font;
+        Ok(None)
+    }
+}
+
+/// Downloads fonts from their url, blocking until they are loaded
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NetworkFontProvider;
+
+impl FontProvider for NetworkFontProvider {
+    fn font_from_url(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
+        let response = ureq::get(url).call()?;
+        let mut bytes = vec![];
+        response
+            .into_reader()
+            .take((BUFFER_LIMIT + 1) as u64)
+            .read_to_end(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+}
+
+/// Never loads any font, leaving installed fonts only
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OfflineFontProvider;
+
+impl FontProvider for OfflineFontProvider {
+    fn font_from_url(&self, _url: &str) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+}
+
+/// Where the fonts of an animation are looked for
+pub struct FontOptions {
+    /// Directories of installed fonts
+    pub directories: Vec<PathBuf>,
+    /// Font files installed besides the directories, like the bundled Fira
+    /// Mono fallback
+    pub files: Vec<PathBuf>,
+    /// Source of the fonts which are not installed
+    pub provider: Box<dyn FontProvider>,
+    pub fallbacks: FontFallbacks,
+}
+
+impl FontOptions {
+    /// Fonts of the system and the bundled Fira Mono, and fonts downloaded
+    /// from their url
+    #[cfg(not(all(target_os = "unknown", target_arch = "wasm32")))]
+    pub fn system() -> Self {
+        let mut directories = dirs::font_dir().into_iter().collect::<Vec<_>>();
+        if cfg!(target_os = "macos") {
+            directories.push(PathBuf::from("/System/Library/Fonts"));
+        }
+        FontOptions {
+            directories,
+            files: bundled_font().into_iter().collect(),
+            provider: Box::new(NetworkFontProvider),
+            fallbacks: FontFallbacks::default(),
+        }
+    }
+
+    /// Fonts of `directories` only, without any network access. The bundled
+    /// Fira Mono is not loaded unless added to `files`
+    pub fn offline(directories: Vec<PathBuf>) -> Self {
+        FontOptions {
+            directories,
+            files: vec![],
+            provider: Box::new(OfflineFontProvider),
+            fallbacks: FontFallbacks::default(),
+        }
+    }
+}
+
 pub struct FontDB {
     fontkit: FontKit,
     font_map: HashMap<String, Vec<FontKey>>,
+    fallbacks: FontFallbacks,
+    provider: Box<dyn FontProvider>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl FontDB {
@@ -19,70 +216,286 @@ impl FontDB {
         FontDB {
             fontkit,
             font_map: HashMap::new(),
+            fallbacks: FontFallbacks::default(),
+            provider: Box::new(NetworkFontProvider),
+            diagnostics: vec![],
         }
     }
 
-    pub fn load_fonts_from_model(&mut self, model: &Model) -> Result<(), Error> {
-        // load default font
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let current_exe = std::env::current_exe()?;
-            let mut path = current_exe.clone();
-            path.push("assets/FiraMono-Regular.ttf");
-
-            while !path.exists() && path.parent().is_some() {
-                path.pop();
-                path.pop();
-                path.pop();
-                path.push("assets/FiraMono-Regular.ttf");
+    /// A `FontDB` of `fontkit` with the bundled Fira Mono added to it as the
+    /// last fallback, if it is found
+    pub fn with_bundled_font(fontkit: FontKit) -> Result<Self, Error> {
+        #[cfg(not(all(target_os = "unknown", target_arch = "wasm32")))]
+        let fontkit = {
+            let mut fontkit = fontkit;
+            if let Some(path) = bundled_font() {
+                fontkit.search_fonts_from_path(path)?;
+            }
+            fontkit
+        };
+        Ok(FontDB::new(fontkit))
+    }
+
+    /// A `FontDB` of the fonts in the directories and files of `options`.
+    /// Paths which don't exist are skipped
+    pub fn with_options(options: FontOptions) -> Result<Self, Error> {
+        let mut fontkit = FontKit::new();
+        for directory in &options.directories {
+            if directory.exists() {
+                fontkit.search_fonts_from_path(directory.clone())?;
+            } else {
+                log::warn!("Font directory {} does not exist", directory.display());
             }
-            if path.exists() {
-                self.fontkit.search_fonts_from_path(
-                    &path.into_os_string().into_string().unwrap_or_default(),
-                )?;
+        }
+        for file in &options.files {
+            if file.exists() {
+                fontkit.search_fonts_from_path(file.clone())?;
+            } else {
+                log::warn!("Font file {} does not exist", file.display());
             }
         }
-        // load remote fonts
+        let mut fontdb = FontDB::new(fontkit);
+        fontdb.fallbacks = options.fallbacks;
+        fontdb.provider = options.provider;
+        Ok(fontdb)
+    }
+
+    pub fn set_fallbacks(&mut self, fallbacks: FontFallbacks) {
+        self.fallbacks = fallbacks;
+    }
+
+    pub fn set_provider(&mut self, provider: impl FontProvider + 'static) {
+        self.provider = Box::new(provider);
+    }
+
+    /// Problems found while loading the fonts of the animation
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Load the fonts of `model` which are not installed from the font
+    /// provider. Fonts which can't be loaded are reported as diagnostics
+    pub fn load_fonts_from_model(&mut self, model: &Model) -> Result<(), Error> {
         for font in &model.fonts.list {
-            if let Some(path) = font.path.as_ref() {
-                if font.origin == FontPathOrigin::FontUrl {
-                    let response = ureq::get(path).call()?;
-                    let mut bytes = vec![];
-                    response
-                        .into_reader()
-                        .take((BUFFER_LIMIT + 1) as u64)
-                        .read_to_end(&mut bytes)?;
-                    let keys = self.fontkit.add_font_from_buffer(bytes)?;
+            // Embedded glyphs don't need the font
+            if !model.font_chars(font).is_empty() {
+                continue;
+            }
+            let bytes = match font.path.as_ref() {
+                Some(url) if font.origin == FontPathOrigin::FontUrl => {
+                    self.provider.font_from_url(url)
+                }
+                _ => {
+                    let installed = self.resolve(font, None).is_some_and(|(_, matched)| {
+                        matches!(
+                            matched,
+                            FontMatch::Name | FontMatch::Family | FontMatch::FamilyOnly
+                        )
+                    });
+                    if installed {
+                        continue;
+                    }
+                    self.provider.font_by_name(font)
+                }
+            };
+            let keys = match bytes {
+                Ok(Some(bytes)) => self
+                    .fontkit
+                    .add_font_from_buffer(bytes)
+                    .map_err(Error::from),
+                Ok(None) => continue,
+                Err(error) => Err(error),
+            };
+            match keys {
+                Ok(keys) => {
                     self.font_map.insert(font.name.clone(), keys);
                 }
+                Err(error) => self.diagnostics.push(Diagnostic::FontNotLoaded {
+                    name: font.name.clone(),
+                    reason: error.to_string(),
+                }),
             }
         }
+        for resolution in self.report(model) {
+            let diagnostic = match resolution.matched {
+                FontMatch::Missing => Diagnostic::MissingFont {
+                    name: resolution.name,
+                    family: resolution.family,
+                },
+                FontMatch::Script(_) | FontMatch::Class(_) | FontMatch::Default => {
+                    Diagnostic::FontFallback {
+                        name: resolution.name,
+                        family: resolution.family,
+                        fallback: resolution
+                            .resolved
+                            .map(|key| key.family)
+                            .unwrap_or_default(),
+                    }
+                }
+                _ => continue,
+            };
+            self.diagnostics.push(diagnostic);
+        }
         Ok(())
     }
 
-    pub fn font(&self, font: &LottieFont) -> Option<impl Deref<Target = Font> + '_> {
-        match font.origin {
-            // This is not an html player. So we treat script/css urls as local obtained fonts
-            // TODO: could this be a thing in WASM target?
-            FontPathOrigin::Local | FontPathOrigin::ScriptUrl | FontPathOrigin::CssUrl => self
-                .fontkit
-                .query(&FontKey::new_with_family(font.name.clone()))
-                .or_else(|| {
-                    self.fontkit
-                        .query(&FontKey::new_with_family(font.family.clone()))
-                })
-                .or_else(|| {
-                    // default font
-                    self.fontkit
-                        .query(&FontKey::new_with_family("Fira Mono".to_string()))
-                }),
-            // TODO: What if font from url is *.ttc and font.name points to one font in the
-            // collection? Could this be possible?
-            FontPathOrigin::FontUrl => self.fontkit.query(self.font_map.get(&font.name)?.first()?),
+    /// Installed font to draw `text` with `font`, matching its family, weight
+    /// and style or else falling back to fonts for the script of the text,
+    /// for its class, and finally to the default fonts
+    pub fn font(&self, font: &LottieFont, text: &str) -> Option<impl Deref<Target = Font> + '_> {
+        let script = script_runs(text)
+            .into_iter()
+            .map(|(_, script)| script)
+            .find(|script| !matches!(script, Script::Common | Script::Inherited));
+        self.resolve(font, script).map(|(font, _)| font)
+    }
+
+    /// Installed fonts to draw `text` with `font`, the first one being the
+    /// font it resolves to regardless of the script of the text, and for
+    /// each script of the text the indices of the fonts to try in order
+    pub fn fonts(
+        &self,
+        font: &LottieFont,
+        text: &str,
+    ) -> (
+        Vec<impl Deref<Target = Font> + '_>,
+        HashMap<Script, Vec<usize>>,
+    ) {
+        let mut fonts = vec![];
+        let mut chains = HashMap::new();
+        let scripts = script_runs(text).into_iter().map(|(_, script)| script);
+        for script in [Script::Common].into_iter().chain(scripts) {
+            if chains.contains_key(&script) {
+                continue;
+            }
+            let fallback_script = match script {
+                Script::Common | Script::Inherited => None,
+                script => Some(script),
+            };
+            let mut chain = vec![];
+            for (found, _) in self.candidates(font, fallback_script) {
+                // Candidates often resolve to the same installed font
+                let index = match fonts.iter().position(|known| same_font(known, &found)) {
+                    Some(index) => index,
+                    None => {
+                        fonts.push(found);
+                        fonts.len() - 1
+                    }
+                };
+                if !chain.contains(&index) {
+                    chain.push(index);
+                }
+            }
+            chains.insert(script, chain);
         }
+        (fonts, chains)
+    }
+
+    /// How each font of `model` resolves, regardless of the script of its
+    /// text
+    pub fn report(&self, model: &Model) -> Vec<FontResolution> {
+        model
+            .fonts
+            .list
+            .iter()
+            .map(|font| {
+                let (resolved, matched) = match self.resolve(font, None) {
+                    _ if !model.font_chars(font).is_empty() => (None, FontMatch::Embedded),
+                    Some((found, matched)) => (Some(found.key()), matched),
+                    None => (None, FontMatch::Missing),
+                };
+                FontResolution {
+                    name: font.name.clone(),
+                    family: font.family.clone(),
+                    weight: font.weight_value(),
+                    italic: font.is_italic(),
+                    resolved,
+                    matched,
+                }
+            })
+            .collect()
+    }
+
+    fn resolve(
+        &self,
+        font: &LottieFont,
+        script: Option<Script>,
+    ) -> Option<(impl Deref<Target = Font> + '_, FontMatch)> {
+        self.candidates(font, script).next()
+    }
+
+    /// Installed fonts matching `font`, from the best match to the last
+    /// fallback
+    fn candidates(
+        &self,
+        font: &LottieFont,
+        script: Option<Script>,
+    ) -> impl Iterator<Item = (impl Deref<Target = Font> + '_, FontMatch)> + '_ {
+        // TODO: What if font from url is *.ttc and font.name points to one font in the
+        // collection? Could this be possible?
+        let provided = self
+            .font_map
+            .get(&font.name)
+            .and_then(|keys| keys.first())
+            .map(|key| (key.clone(), FontMatch::Provided));
+        // This is not an html player. So we treat script/css urls as local obtained fonts
+        // TODO: could this be a thing in WASM target?
+        let weight = font.weight_value();
+        let italic = font.is_italic();
+        let styled = |family: &str| {
+            let mut key = FontKey::new_with_family(family.to_string());
+            key.weight = Some(weight);
+            key.italic = Some(italic);
+            key
+        };
+        let family = |family: &str| FontKey::new_with_family(family.to_string());
+        let mut candidates = provided.into_iter().collect::<Vec<_>>();
+        candidates.extend([
+            (family(font.name.as_str()), FontMatch::Name),
+            (styled(font.family.as_str()), FontMatch::Family),
+            (family(font.family.as_str()), FontMatch::FamilyOnly),
+        ]);
+        let chain = |families: Option<&Vec<String>>, matched: FontMatch| {
+            families
+                .into_iter()
+                .flatten()
+                .map(move |family| (styled(family.as_str()), matched.clone()))
+        };
+        if let Some(script) = script {
+            let name = script.short_name();
+            let families = self.fallbacks.scripts.get(name);
+            candidates.extend(chain(families, FontMatch::Script(name.to_string())));
+        }
+        if let Some(class) = font.class.as_ref() {
+            let families = self.fallbacks.classes.get(&class.to_lowercase());
+            candidates.extend(chain(families, FontMatch::Class(class.clone())));
+        }
+        candidates.extend(chain(Some(&self.fallbacks.default), FontMatch::Default));
+        candidates
+            .into_iter()
+            .filter_map(move |(key, matched)| Some((self.fontkit.query(&key)?, matched)))
     }
 
     pub fn fontkit(&self) -> &FontKit {
         &self.fontkit
     }
 }
+
+/// Fira Mono of the `assets` directory next to the executable or one of its
+/// ancestors
+#[cfg(not(all(target_os = "unknown", target_arch = "wasm32")))]
+fn bundled_font() -> Option<PathBuf> {
+    let mut path = std::env::current_exe().ok()?;
+    path.push("assets/FiraMono-Regular.ttf");
+    while !path.exists() && path.parent().is_some() {
+        path.pop();
+        path.pop();
+        path.pop();
+        path.push("assets/FiraMono-Regular.ttf");
+    }
+    path.exists().then_some(path)
+}
+
+fn same_font<F: Deref<Target = Font>>(a: &F, b: &F) -> bool {
+    std::ptr::eq::<Font>(&**a, &**b)
+}
@@ -1,6 +1,12 @@
+pub mod animator;
+pub mod camera;
+pub mod effect;
 pub mod frame;
 pub mod hierarchy;
 pub mod media;
+pub mod selector;
 pub mod shape;
+pub mod shaping;
 pub mod staged;
 pub mod text;
+pub mod text_path;
@@ -0,0 +1,279 @@
+use glam::{Mat4, Vec3};
+
+use crate::model::{
+    rotation_mat4, Animated, Effect, EffectGroup, EffectValue, KeyFrame, Rgb, TextRangeInfo,
+    Vector3D,
+};
+
+/// Transform and colors of a glyph once the text animators are applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphStyle {
+    /// Transform of the glyph, whose origin is its start on the baseline
+    pub transform: Mat4,
+    /// Opacity of the whole glyph, from 0 to 1
+    pub opacity: f32,
+    /// sRGB channels from 0 to 1
+    pub fill_color: [f32; 3],
+    pub fill_opacity: f32,
+    /// sRGB channels from 0 to 1
+    pub stroke_color: [f32; 3],
+    pub stroke_opacity: f32,
+    pub stroke_width: f32,
+    /// Blurriness of the glyph, drawn with a Gaussian blur effect
+    pub blur: f32,
+}
+
+impl Default for GlyphStyle {
+    fn default() -> Self {
+        GlyphStyle {
+            transform: Mat4::IDENTITY,
+            opacity: 1.0,
+            fill_color: [0.0; 3],
+            fill_opacity: 1.0,
+            stroke_color: [0.0; 3],
+            stroke_opacity: 1.0,
+            stroke_width: 0.0,
+            blur: 0.0,
+        }
+    }
+}
+
+impl TextRangeInfo {
+    /// Apply the animators of the text to the `style` of this glyph at
+    /// `frame`, each property being weighted by the influence of its range.
+    /// Like lottie-web, transforms add up across animators while colors and
+    /// opacities move towards the animated values in order
+    pub fn apply(&self, style: &mut GlyphStyle, frame: f32) {
+        let value = |animated: &Option<Animated<f32>>| {
+            animated.as_ref().map(|animated| animated.value(frame))
+        };
+        let mut anchor = Vec3::ZERO;
+        let mut position = Vec3::ZERO;
+        let mut scale = Vec3::ONE;
+        let mut rotation = Mat4::IDENTITY;
+        let mut line_spacing = 0.0;
+        for (range, influence) in self.influences(frame) {
+            let Some(animator) = range.style.as_ref() else {
+                continue;
+            };
+            if influence == 0.0 {
+                continue;
+            }
+            if let Some(a) = animator.anchor.as_ref() {
+                anchor += vec3(a.value(frame)) * influence;
+            }
+            if let Some(p) = animator.position.as_ref() {
+                position += vec3(p.value(frame)) * influence;
+            }
+            if let Some(s) = animator.scale.as_ref() {
+                let s = s.value(frame) / 100.0;
+                scale *= Vec3::new(
+                    1.0 + (s.x - 1.0) * influence,
+                    1.0 + (s.y - 1.0) * influence,
+                    1.0,
+                );
+            }
+            if let Some(skew) = value(&animator.skew) {
+                let axis = value(&animator.skew_axis).unwrap_or(0.0);
+                rotation = skew_mat4(skew * influence, axis * influence) * rotation;
+            }
+            let degrees = Vector3D::new(
+                value(&animator.rotation_x).unwrap_or(0.0),
+                value(&animator.rotation_y).unwrap_or(0.0),
+                value(&animator.rotation).unwrap_or(0.0),
+            );
+            rotation = rotation_mat4(degrees * influence) * rotation;
+            if let Some(ls) = value(&animator.line_spacing) {
+                line_spacing += ls * influence;
+            }
+
+            let towards = |current: &mut f32, target: f32| {
+                *current += (target - *current) * influence;
+            };
+            if let Some(o) = value(&animator.opacity) {
+                towards(&mut style.opacity, o / 100.0);
+            }
+            if let Some(color) = animator.fill_color.as_ref() {
+                let color = color.value(frame).to_f32();
+                for (current, target) in style.fill_color.iter_mut().zip(color) {
+                    towards(current, target);
+                }
+            }
+            style.fill_color = adjust_hsv(
+                style.fill_color,
+                value(&animator.fill_hue).unwrap_or(0.0) * influence,
+                value(&animator.fill_saturation).unwrap_or(0.0) / 100.0 * influence,
+                value(&animator.fill_brightness).unwrap_or(0.0) / 100.0 * influence,
+            );
+            if let Some(o) = value(&animator.fill_opacity) {
+                towards(&mut style.fill_opacity, o / 100.0);
+            }
+            if let Some(color) = animator.stroke_color.as_ref() {
+                let color = color.value(frame).to_f32();
+                for (current, target) in style.stroke_color.iter_mut().zip(color) {
+                    towards(current, target);
+                }
+            }
+            style.stroke_color = adjust_hsv(
+                style.stroke_color,
+                value(&animator.stroke_hue).unwrap_or(0.0) * influence,
+                value(&animator.stroke_saturation).unwrap_or(0.0) / 100.0 * influence,
+                value(&animator.stroke_brightness).unwrap_or(0.0) / 100.0 * influence,
+            );
+            if let Some(o) = value(&animator.stroke_opacity) {
+                towards(&mut style.stroke_opacity, o / 100.0);
+            }
+            if let Some(width) = value(&animator.stroke_width) {
+                towards(&mut style.stroke_width, width);
+            }
+            if let Some(blur) = value(&animator.blur) {
+                style.blur += blur * influence;
+            }
+        }
+        style.opacity = style.opacity.clamp(0.0, 1.0);
+        style.fill_opacity = style.fill_opacity.clamp(0.0, 1.0);
+        style.stroke_opacity = style.stroke_opacity.clamp(0.0, 1.0);
+        style.stroke_width = style.stroke_width.max(0.0);
+        for channel in style.fill_color.iter_mut().chain(&mut style.stroke_color) {
+            *channel = channel.clamp(0.0, 1.0);
+        }
+
+        // Tracking moves every glyph after the first one of the line
+        let glyph = self.glyph();
+        let tracking = (glyph - self.index.1 + 1..=glyph)
+            .flat_map(|glyph| self.influences_at(glyph, frame))
+            .filter_map(|(range, influence)| {
+                let t = range.style.as_ref()?.letter_spacing.as_ref()?;
+                Some(t.value(frame) * influence)
+            })
+            .sum::<f32>();
+        let offset = Vec3::new(tracking, self.index.0 as f32 * line_spacing, 0.0);
+        // Glyphs are scaled and rotated around the center of their anchor
+        // group on the baseline, moved by the grouping alignment
+        let alignment = self
+            .alignment
+            .as_ref()
+            .map(|alignment| alignment.value(frame) / 100.0)
+            .unwrap_or_default();
+        let center = Vec3::new(
+            self.center + alignment.x * self.group_size.x / 2.0,
+            alignment.y * self.group_size.y,
+            0.0,
+        );
+        style.transform = Mat4::from_translation(offset + center + position)
+            * rotation
+            * Mat4::from_scale(scale)
+            * Mat4::from_translation(-anchor - center)
+            * style.transform;
+    }
+}
+
+impl TextRangeInfo {
+    /// Gaussian blur drawing this glyph with the blur of the animators, or
+    /// `None` if they never blur it. As selectors can change the blur in any
+    /// way, it is sampled at every frame from 0 to `frames`
+    pub fn blur_effect(&self, frames: f32) -> Option<Effect> {
+        let blurred = self.ranges.iter().any(|range| {
+            range
+                .style
+                .as_ref()
+                .is_some_and(|style| style.blur.is_some())
+        });
+        if !blurred {
+            return None;
+        }
+        let samples = (0..=frames.max(0.0).ceil() as u32)
+            .map(|frame| {
+                let frame = frame as f32;
+                let mut style = GlyphStyle::default();
+                self.apply(&mut style, frame);
+                (frame, style.blur.max(0.0))
+            })
+            .collect::<Vec<_>>();
+        if samples.iter().all(|(_, blur)| *blur == 0.0) {
+            return None;
+        }
+        let keyframe = |(start_frame, start_value), (end_frame, end_value)| KeyFrame {
+            start_value,
+            end_value,
+            start_frame,
+            end_frame,
+            easing_out: None,
+            easing_in: None,
+        };
+        let mut keyframes = samples
+            .windows(2)
+            .map(|pair| keyframe(pair[0], pair[1]))
+            .collect::<Vec<_>>();
+        // The last blur is held, a single sample being a static value
+        let (frame, blur) = samples[samples.len() - 1];
+        keyframes.push(keyframe((frame, blur), (frame + 1.0, blur)));
+        let blurriness = Animated {
+            animated: keyframes.len() > 1,
+            keyframes,
+        };
+        // Blurred in both dimensions, without repeating the edge pixels
+        Some(Effect::GaussianBlur(EffectGroup {
+            ty: 29,
+            name: Some("Gaussian Blur".to_string()),
+            match_name: None,
+            index: None,
+            enabled: true,
+            values: vec![
+                EffectValue::Slider(blurriness),
+                EffectValue::Dropdown(Animated::from_value(1.0)),
+                EffectValue::Checkbox(Animated::from_value(0.0)),
+            ],
+        }))
+    }
+}
+
+fn vec3(v: Vector3D) -> Vec3 {
+    Vec3::new(v.x, v.y, v.z)
+}
+
+/// Skew by `degrees` along the x axis rotated by `axis` degrees. Positive
+/// skews lean the top of the glyph to the right
+fn skew_mat4(degrees: f32, axis: f32) -> Mat4 {
+    let rotation = Mat4::from_rotation_z(axis.to_radians());
+    let mut skew = Mat4::IDENTITY;
+    skew.y_axis.x = -degrees.to_radians().tan();
+    rotation * skew * rotation.inverse()
+}
+
+/// Rotate the hue of `color` by `hue` degrees, then add `saturation` and
+/// `brightness`, from -1 to 1
+fn adjust_hsv(color: [f32; 3], hue: f32, saturation: f32, brightness: f32) -> [f32; 3] {
+    if hue == 0.0 && saturation == 0.0 && brightness == 0.0 {
+        return color;
+    }
+    let [r, g, b] = color;
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let h = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+
+    let h = (h + hue / 60.0).rem_euclid(6.0);
+    let s = (s + saturation).clamp(0.0, 1.0);
+    let v = (max + brightness).clamp(0.0, 1.0);
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    [r + m, g + m, b + m]
+}
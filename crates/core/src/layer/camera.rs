@@ -0,0 +1,146 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::model::{rotation_mat4, Animated, Layer, LayerContent, Transform, Vector2D, Vector3D};
+
+/// A camera layer of the root composition, projecting 3D layers with a
+/// perspective
+#[derive(Debug, Clone)]
+pub struct LayerCamera {
+    pub name: Option<String>,
+    pub start_frame: f32,
+    pub end_frame: f32,
+    start_time: f32,
+    perspective: Animated<f32>,
+    transform: Transform,
+    /// Transforms of the parents of the camera with their start time, closest
+    /// parent first
+    parents: Vec<(Transform, f32)>,
+    width: f32,
+    height: f32,
+}
+
+impl LayerCamera {
+    /// The camera of `layer`, parented to the other `layers` of a `width` by
+    /// `height` composition. Returns `None` if `layer` is not a camera
+    pub fn new(layer: &Layer, layers: &[Layer], width: f32, height: f32) -> Option<Self> {
+        let LayerContent::Camera(camera) = &layer.content else {
+            return None;
+        };
+        let mut parents = vec![];
+        let mut parent_index = layer.parent_index;
+        while let Some(parent) =
+            parent_index.and_then(|index| layers.iter().find(|l| l.index == Some(index)))
+        {
+            // Parenting cycles are invalid, stop once every layer is visited
+            if parents.len() >= layers.len() {
+                break;
+            }
+            parents.push((
+                parent.transform.clone().unwrap_or_default(),
+                parent.start_time,
+            ));
+            parent_index = parent.parent_index;
+        }
+        Some(LayerCamera {
+            name: layer.name.clone(),
+            start_frame: layer.start_frame,
+            end_frame: layer.end_frame,
+            start_time: layer.start_time,
+            perspective: camera.perspective.clone(),
+            transform: layer.transform.clone().unwrap_or_default(),
+            parents,
+            width,
+            height,
+        })
+    }
+
+    pub fn is_active(&self, frame: f32) -> bool {
+        frame >= self.start_frame && frame < self.end_frame
+    }
+
+    /// Transform from the space of the camera, which looks along its z axis,
+    /// to the composition
+    pub fn transform(&self, frame: f32) -> Mat4 {
+        let parents = self
+            .parents
+            .iter()
+            .rev()
+            .fold(Mat4::IDENTITY, |matrix, (transform, start_time)| {
+                matrix * transform.value(frame - *start_time)
+            });
+        let frame = frame - self.start_time;
+        let position = self
+            .transform
+            .position
+            .as_ref()
+            .map(|p| vec3(p.value(frame)))
+            .unwrap_or_default();
+        let mut matrix = Mat4::from_translation(position);
+        // Two-node cameras turn toward their point of interest
+        if let Some(target) = self.transform.anchor.as_ref() {
+            let direction = vec3(target.value(frame)) - position;
+            if direction.length_squared() > 0.0 {
+                let direction = direction.normalize();
+                let pitch = (-direction.y).atan2(direction.x.hypot(direction.z));
+                let yaw = direction.x.atan2(direction.z);
+                matrix *= Mat4::from_rotation_y(yaw) * Mat4::from_rotation_x(pitch);
+            }
+        }
+        let orientation = self
+            .transform
+            .orientation
+            .as_ref()
+            .map(|o| o.value(frame))
+            .unwrap_or_default();
+        let rotation = Vector3D::new(
+            self.transform.rotation_x.value(frame),
+            self.transform.rotation_y.value(frame),
+            self.transform.rotation.value(frame),
+        );
+        parents * matrix * rotation_mat4(orientation) * rotation_mat4(rotation)
+    }
+
+    /// Project points of the composition to homogeneous 2D points of the
+    /// composition, to be divided by their w coordinate. The z coordinate is
+    /// dropped, see [`LayerCamera::depth`] to order layers. Points at the
+    /// distance of the zoom from the camera keep their size
+    pub fn view_projection(&self, frame: f32) -> Mat4 {
+        let zoom = self
+            .perspective
+            .value(frame - self.start_time)
+            .max(f32::EPSILON);
+        let projection = Mat4::from_cols(
+            Vec4::X,
+            Vec4::Y,
+            Vec4::new(
+                self.width / 2.0 / zoom,
+                self.height / 2.0 / zoom,
+                0.0,
+                1.0 / zoom,
+            ),
+            Vec4::ZERO,
+        );
+        projection * self.transform(frame).inverse()
+    }
+
+    /// Position of `point` on the composition once projected, `None` when it
+    /// is behind the camera
+    pub fn project(&self, point: Vector3D, frame: f32) -> Option<Vector2D> {
+        let projected = self.view_projection(frame) * vec3(point).extend(1.0);
+        (projected.w > 0.0)
+            .then(|| Vector2D::new(projected.x / projected.w, projected.y / projected.w))
+    }
+
+    /// Distance from the plane of the camera to `point`, farther points
+    /// being drawn first
+    pub fn depth(&self, point: Vector3D, frame: f32) -> f32 {
+        self.transform(frame)
+            .inverse()
+            .transform_point3(vec3(point))
+            .z
+    }
+}
+
+fn vec3(v: Vector3D) -> Vec3 {
+    Vec3::new(v.x, v.y, v.z)
+}
@@ -0,0 +1,747 @@
+use lyon_path::iterator::PathIterator;
+use lyon_path::PathEvent;
+
+use crate::layer::shape::PathFactory;
+use crate::model::{
+    Animated, Effect, EffectGroup, KeyFrame, LineCap, LineJoin, Mask, Rgb, Rgba, Shape, ShapeLayer,
+    Stroke, Transform, Trim, TrimMultipleShape, Vector2D,
+};
+
+/// After Effects' blurriness is not a standard deviation, lottie-web converts
+/// it with this factor as well
+const BLURRINESS_TO_SIGMA: f32 = 0.3;
+
+/// Drop shadow softness is converted to a standard deviation like lottie-web
+const SOFTNESS_TO_SIGMA: f32 = 0.25;
+
+/// Number of strokes of decreasing width used to draw a soft brush
+const SOFT_BRUSH_STEPS: usize = 4;
+
+/// Tolerance used to measure the length of mask paths
+const PATH_LENGTH_TOLERANCE: f32 = 0.1;
+
+/// An effect resolved at a given frame, ready to be applied on the rendered
+/// contents of a layer
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerEffect {
+    GaussianBlur(GaussianBlur),
+    DropShadow(DropShadow),
+    Fill(Fill),
+    Tint(Tint),
+    Tritone(Tritone),
+    Levels(Levels),
+}
+
+impl LayerEffect {
+    /// Resolve `effect` at `frame`. Returns `None` for disabled effects and
+    /// effects without a visual output or renderer support
+    pub fn resolve(effect: &Effect, frame: f32) -> Option<Self> {
+        if !effect.enabled() {
+            return None;
+        }
+        match effect {
+            Effect::GaussianBlur(group) => Some(LayerEffect::GaussianBlur(GaussianBlur::resolve(
+                group, frame,
+            ))),
+            Effect::DropShadow(group) => {
+                Some(LayerEffect::DropShadow(DropShadow::resolve(group, frame)))
+            }
+            Effect::Fill(group) => Some(LayerEffect::Fill(Fill::resolve(group, frame))),
+            Effect::Tint(group) => Some(LayerEffect::Tint(Tint::resolve(group, frame))),
+            Effect::Tritone(group) => Some(LayerEffect::Tritone(Tritone::resolve(group, frame))),
+            Effect::Levels(group) => Some(LayerEffect::Levels(Levels::resolve(group, frame))),
+            // Strokes are drawn as shapes, see `MaskStroke`
+            _ => None,
+        }
+    }
+
+    /// Apply this effect to the premultiplied RGBA8 `pixels` of a layer
+    pub fn apply(&self, pixels: &mut [u8], width: usize, height: usize) {
+        match self {
+            LayerEffect::GaussianBlur(blur) => blur.apply(pixels, width, height),
+            LayerEffect::DropShadow(shadow) => shadow.apply(pixels, width, height),
+            LayerEffect::Fill(fill) => map_colors(pixels, |c| fill.transform(c)),
+            LayerEffect::Tint(tint) => map_colors(pixels, |c| tint.transform(c)),
+            LayerEffect::Tritone(tritone) => map_colors(pixels, |c| tritone.transform(c)),
+            LayerEffect::Levels(levels) => map_colors(pixels, |c| levels.transform(c)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaussianBlur {
+    /// Standard deviation of the horizontal blur, in pixels
+    pub sigma_x: f32,
+    /// Standard deviation of the vertical blur, in pixels
+    pub sigma_y: f32,
+    /// Extend the edge pixels of the layer instead of blurring them with
+    /// transparency
+    pub repeat_edge: bool,
+}
+
+impl GaussianBlur {
+    /// Parameters are blurriness, blur dimensions (1 for both, 2 for
+    /// horizontal and 3 for vertical) and repeat edge pixels
+    pub fn resolve(group: &EffectGroup, frame: f32) -> Self {
+        let value = |index: usize, default: f32| {
+            group
+                .number(index)
+                .map(|v| v.value(frame))
+                .unwrap_or(default)
+        };
+        let sigma = value(0, 0.0).max(0.0) * BLURRINESS_TO_SIGMA;
+        let dimensions = value(1, 1.0).round() as u32;
+        GaussianBlur {
+            sigma_x: if dimensions == 3 { 0.0 } else { sigma },
+            sigma_y: if dimensions == 2 { 0.0 } else { sigma },
+            repeat_edge: value(2, 0.0) != 0.0,
+        }
+    }
+
+    /// Blur premultiplied RGBA8 `pixels` in place, one dimension at a time
+    pub fn apply(&self, pixels: &mut [u8], width: usize, height: usize) {
+        if self.sigma_x <= 0.0 && self.sigma_y <= 0.0 {
+            return;
+        }
+        let mut buffer = pixels.iter().map(|v| *v as f32).collect::<Vec<_>>();
+        if self.sigma_x > 0.0 {
+            buffer = convolve(
+                &buffer,
+                width,
+                height,
+                &gaussian_kernel(self.sigma_x),
+                true,
+                self.repeat_edge,
+            );
+        }
+        if self.sigma_y > 0.0 {
+            buffer = convolve(
+                &buffer,
+                width,
+                height,
+                &gaussian_kernel(self.sigma_y),
+                false,
+                self.repeat_edge,
+            );
+        }
+        for (pixel, value) in pixels.iter_mut().zip(buffer) {
+            *pixel = value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DropShadow {
+    /// Color of the shadow, its alpha being the opacity of the shadow
+    pub color: Rgba,
+    /// Offset of the shadow from the layer, in pixels
+    pub offset: Vector2D,
+    /// Standard deviation of the shadow blur, in pixels
+    pub sigma: f32,
+    /// Render the shadow without the layer itself
+    pub shadow_only: bool,
+}
+
+impl DropShadow {
+    /// Parameters are color, opacity (`0.0..=255.0`), direction (degrees
+    /// clockwise from the top), distance, softness and shadow only
+    pub fn resolve(group: &EffectGroup, frame: f32) -> Self {
+        let value = |index: usize, default: f32| {
+            group
+                .number(index)
+                .map(|v| v.value(frame))
+                .unwrap_or(default)
+        };
+        let mut color = group.color(0).map(|v| v.value(frame)).unwrap_or_default();
+        let opacity = value(1, 255.0).clamp(0.0, 255.0) / 255.0;
+        color.a = (color.a as f32 * opacity).round() as u8;
+        let direction = (value(2, 135.0) - 90.0).to_radians();
+        let distance = value(3, 5.0);
+        DropShadow {
+            color,
+            offset: Vector2D::new(direction.cos(), direction.sin()) * distance,
+            sigma: value(4, 0.0).max(0.0) * SOFTNESS_TO_SIGMA,
+            shadow_only: value(5, 0.0) != 0.0,
+        }
+    }
+
+    /// Draw the shadow of premultiplied RGBA8 `pixels` behind them
+    pub fn apply(&self, pixels: &mut [u8], width: usize, height: usize) {
+        let dx = self.offset.x.round() as isize;
+        let dy = self.offset.y.round() as isize;
+        let color = [self.color.r, self.color.g, self.color.b, 255].map(|c| c as f32);
+        let opacity = self.color.a as f32 / 255.0;
+        // The alpha of the layer is moved by the offset and tinted
+        let mut shadow = vec![0_u8; pixels.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x as isize - dx, y as isize - dy);
+                if sx < 0 || sy < 0 || sx >= width as isize || sy >= height as isize {
+                    continue;
+                }
+                let alpha = pixels[(sy as usize * width + sx as usize) * 4 + 3] as f32 / 255.0;
+                let start = (y * width + x) * 4;
+                for (value, channel) in shadow[start..start + 4].iter_mut().zip(color) {
+                    *value = (channel * alpha * opacity).round() as u8;
+                }
+            }
+        }
+        GaussianBlur {
+            sigma_x: self.sigma,
+            sigma_y: self.sigma,
+            repeat_edge: false,
+        }
+        .apply(&mut shadow, width, height);
+        for (pixel, shadow) in pixels.chunks_exact_mut(4).zip(shadow.chunks_exact(4)) {
+            if self.shadow_only {
+                pixel.copy_from_slice(shadow);
+                continue;
+            }
+            // Source over, the layer being the source
+            let coverage = 1.0 - pixel[3] as f32 / 255.0;
+            for (value, shadow) in pixel.iter_mut().zip(shadow) {
+                *value = (*value as f32 + *shadow as f32 * coverage)
+                    .round()
+                    .min(255.0) as u8;
+            }
+        }
+    }
+}
+
+/// Luminance used by color correction effects
+fn luminance([r, g, b, _]: [f32; 4]) -> f32 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn mix(from: [f32; 3], to: [f32; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| from[i] + (to[i] - from[i]) * t)
+}
+
+/// Straight RGB of `color` in `0.0..=1.0`
+fn rgb(color: Rgba) -> [f32; 3] {
+    [color.r, color.g, color.b].map(|c| c as f32 / 255.0)
+}
+
+/// Apply a color transform to the straight colors of premultiplied RGBA8
+/// `pixels`
+pub fn map_colors(pixels: &mut [u8], transform: impl Fn([f32; 4]) -> [f32; 4]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3] as f32 / 255.0;
+        let straight = |c: u8| {
+            if alpha > 0.0 {
+                c as f32 / 255.0 / alpha
+            } else {
+                0.0
+            }
+        };
+        let color = [
+            straight(pixel[0]),
+            straight(pixel[1]),
+            straight(pixel[2]),
+            alpha,
+        ];
+        let [r, g, b, a] = transform(color).map(|c| c.clamp(0.0, 1.0));
+        for (value, channel) in pixel.iter_mut().zip([r * a, g * a, b * a, a]) {
+            *value = (channel * 255.0).round() as u8;
+        }
+    }
+}
+
+/// Replaces the colors of a layer, keeping its alpha
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fill {
+    pub color: Rgba,
+    /// Opacity of the filled layer, in `0.0..=1.0`
+    pub opacity: f32,
+}
+
+impl Fill {
+    /// Color is the 3rd parameter and opacity the 7th, the others only target
+    /// masks
+    pub fn resolve(group: &EffectGroup, frame: f32) -> Self {
+        Fill {
+            color: group.color(2).map(|v| v.value(frame)).unwrap_or_default(),
+            opacity: group
+                .number(6)
+                .map(|v| v.value(frame))
+                .unwrap_or(1.0)
+                .clamp(0.0, 1.0),
+        }
+    }
+
+    /// Transform a straight RGBA color in `0.0..=1.0`
+    pub fn transform(&self, color: [f32; 4]) -> [f32; 4] {
+        let [r, g, b] = rgb(self.color);
+        [r, g, b, color[3] * self.opacity]
+    }
+}
+
+/// Maps the luminance of a layer between two colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tint {
+    /// Color of black pixels
+    pub black: Rgba,
+    /// Color of white pixels
+    pub white: Rgba,
+    /// Mix of the tinted and original colors, in `0.0..=1.0`
+    pub amount: f32,
+}
+
+impl Tint {
+    /// Parameters are map black to, map white to and amount to tint (`0.0..=100.0`)
+    pub fn resolve(group: &EffectGroup, frame: f32) -> Self {
+        let color = |index: usize, default: Rgba| {
+            group
+                .color(index)
+                .map(|v| v.value(frame))
+                .unwrap_or(default)
+        };
+        Tint {
+            black: color(0, Rgba::new_u8(0, 0, 0, 255)),
+            white: color(1, Rgba::new_u8(255, 255, 255, 255)),
+            amount: group
+                .number(2)
+                .map(|v| v.value(frame))
+                .unwrap_or(100.0)
+                .clamp(0.0, 100.0)
+                / 100.0,
+        }
+    }
+
+    /// Transform a straight RGBA color in `0.0..=1.0`
+    pub fn transform(&self, color: [f32; 4]) -> [f32; 4] {
+        let tinted = mix(rgb(self.black), rgb(self.white), luminance(color));
+        let [r, g, b] = mix([color[0], color[1], color[2]], tinted, self.amount);
+        [r, g, b, color[3]]
+    }
+}
+
+/// Maps the luminance of a layer to shadow, midtone and highlight colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tritone {
+    pub highlights: Rgba,
+    pub midtones: Rgba,
+    pub shadows: Rgba,
+    /// Mix of the original and mapped colors, in `0.0..=1.0`
+    pub blend: f32,
+}
+
+impl Tritone {
+    /// Parameters are highlights, midtones, shadows and blend with original
+    /// (`0.0..=100.0`)
+    pub fn resolve(group: &EffectGroup, frame: f32) -> Self {
+        let color = |index: usize, default: Rgba| {
+            group
+                .color(index)
+                .map(|v| v.value(frame))
+                .unwrap_or(default)
+        };
+        Tritone {
+            highlights: color(0, Rgba::new_u8(255, 255, 255, 255)),
+            midtones: color(1, Rgba::new_u8(128, 128, 128, 255)),
+            shadows: color(2, Rgba::new_u8(0, 0, 0, 255)),
+            blend: group
+                .number(3)
+                .map(|v| v.value(frame))
+                .unwrap_or(0.0)
+                .clamp(0.0, 100.0)
+                / 100.0,
+        }
+    }
+
+    /// Transform a straight RGBA color in `0.0..=1.0`
+    pub fn transform(&self, color: [f32; 4]) -> [f32; 4] {
+        let luminance = luminance(color);
+        let toned = if luminance < 0.5 {
+            mix(rgb(self.shadows), rgb(self.midtones), luminance * 2.0)
+        } else {
+            mix(
+                rgb(self.midtones),
+                rgb(self.highlights),
+                luminance * 2.0 - 1.0,
+            )
+        };
+        let [r, g, b] = mix(toned, [color[0], color[1], color[2]], self.blend);
+        [r, g, b, color[3]]
+    }
+}
+
+/// Levels of one channel, all values in `0.0..=1.0` except `gamma`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelsCurve {
+    pub input_black: f32,
+    pub input_white: f32,
+    pub gamma: f32,
+    pub output_black: f32,
+    pub output_white: f32,
+}
+
+impl Default for LevelsCurve {
+    fn default() -> Self {
+        LevelsCurve {
+            input_black: 0.0,
+            input_white: 1.0,
+            gamma: 1.0,
+            output_black: 0.0,
+            output_white: 1.0,
+        }
+    }
+}
+
+impl LevelsCurve {
+    /// Read the 5 parameters of a channel starting at `index`
+    fn resolve(group: &EffectGroup, index: usize, frame: f32) -> Self {
+        let default = LevelsCurve::default();
+        let value = |offset: usize, default: f32| {
+            group
+                .number(index + offset)
+                .map(|v| v.value(frame))
+                .unwrap_or(default)
+        };
+        LevelsCurve {
+            input_black: value(0, default.input_black),
+            input_white: value(1, default.input_white),
+            gamma: value(2, default.gamma),
+            output_black: value(3, default.output_black),
+            output_white: value(4, default.output_white),
+        }
+    }
+
+    /// Map a channel value, same as lottie-web's transfer tables
+    pub fn map(&self, value: f32) -> f32 {
+        let input_delta = self.input_white - self.input_black;
+        let min = self.input_black.min(self.input_white);
+        let max = self.input_black.max(self.input_white);
+        let (below, above) = if input_delta < 0.0 {
+            (self.output_white, self.output_black)
+        } else {
+            (self.output_black, self.output_white)
+        };
+        if value <= min {
+            below
+        } else if value >= max {
+            above
+        } else {
+            let t = ((value - self.input_black) / input_delta).powf(1.0 / self.gamma.max(0.01));
+            self.output_black + (self.output_white - self.output_black) * t
+        }
+    }
+}
+
+/// Levels (Individual Controls), applying the RGB curve then the curve of
+/// each channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+    pub rgb: LevelsCurve,
+    pub red: LevelsCurve,
+    pub green: LevelsCurve,
+    pub blue: LevelsCurve,
+    pub alpha: LevelsCurve,
+}
+
+impl Levels {
+    /// Each channel has input black, input white, gamma, output black and
+    /// output white parameters, after a channel selector and a histogram
+    pub fn resolve(group: &EffectGroup, frame: f32) -> Self {
+        Levels {
+            rgb: LevelsCurve::resolve(group, 3, frame),
+            red: LevelsCurve::resolve(group, 10, frame),
+            green: LevelsCurve::resolve(group, 17, frame),
+            blue: LevelsCurve::resolve(group, 24, frame),
+            alpha: LevelsCurve::resolve(group, 31, frame),
+        }
+    }
+
+    /// Transform a straight RGBA color in `0.0..=1.0`
+    pub fn transform(&self, [r, g, b, a]: [f32; 4]) -> [f32; 4] {
+        [
+            self.red.map(self.rgb.map(r)),
+            self.green.map(self.rgb.map(g)),
+            self.blue.map(self.rgb.map(b)),
+            self.alpha.map(a),
+        ]
+    }
+}
+
+/// How the Stroke effect is combined with the contents of its layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrokePaintStyle {
+    OnOriginal,
+    OnTransparent,
+    /// The stroke is used as an alpha mask of the layer
+    RevealOriginal,
+}
+
+/// The Stroke effect, drawn along the masks of a layer. Unlike the other
+/// effects it does not filter pixels, so it is turned into regular shapes
+/// drawn like the contents of the layer
+#[derive(Debug, Clone)]
+pub struct MaskStroke {
+    /// One group per mask and brush step, each holding the mask path, a trim
+    /// and a stroke
+    pub shapes: Vec<ShapeLayer>,
+    pub paint_style: StrokePaintStyle,
+}
+
+impl MaskStroke {
+    /// Parameters are path (1-based mask index), all masks, stroke
+    /// sequentially, color, brush size, brush hardness, opacity, start, end,
+    /// spacing and paint style. Animated ranges are sampled between
+    /// `start_frame` and `end_frame`. Returns `None` when no mask is stroked
+    pub fn new(
+        group: &EffectGroup,
+        masks: &[Mask],
+        start_frame: f32,
+        end_frame: f32,
+    ) -> Option<Self> {
+        let number = |index: usize, default: f32| {
+            group
+                .number(index)
+                .cloned()
+                .unwrap_or_else(|| Animated::from_value(default))
+        };
+        let initial = |index: usize, default: f32| number(index, default).initial_value();
+        let masks = if initial(1, 0.0) != 0.0 {
+            masks.iter().collect::<Vec<_>>()
+        } else {
+            let index = initial(0, 1.0).round().max(1.0) as usize;
+            masks.get(index - 1).into_iter().collect()
+        };
+        if masks.is_empty() {
+            return None;
+        }
+        let color = group
+            .color(3)
+            .map(|color| map_animated(color, |c| Rgb::new_u8(c.r, c.g, c.b)))
+            .unwrap_or_else(|| Animated::from_value(Rgb::new_u8(255, 255, 255)));
+        let size = number(4, 2.0);
+        let hardness = number(5, 100.0);
+        let hardness_at = |frame: f32| (hardness.value(frame) / 100.0).clamp(0.0, 1.0);
+        let opacity = number(6, 1.0);
+        let start = number(7, 0.0);
+        let end = number(8, 100.0);
+        let paint_style = match initial(10, 1.0).round() as u32 {
+            2 => StrokePaintStyle::OnTransparent,
+            3 => StrokePaintStyle::RevealOriginal,
+            _ => StrokePaintStyle::OnOriginal,
+        };
+
+        // Soft brushes are approximated by stacking narrower strokes, whose
+        // opacities add up to the opacity of the effect in the middle
+        let steps = if hardness.is_animated() || hardness_at(0.0) < 1.0 {
+            SOFT_BRUSH_STEPS
+        } else {
+            1
+        };
+        let strokes = (0..steps)
+            .map(|step| {
+                let scale = |hard: f32| hard + (1.0 - hard) * (step + 1) as f32 / steps as f32;
+                let width = if hardness.is_animated() {
+                    sample_frames(start_frame, end_frame, |frame| {
+                        size.value(frame) * scale(hardness_at(frame))
+                    })
+                } else {
+                    let scale = scale(hardness_at(0.0));
+                    map_animated(&size, |size| size * scale)
+                };
+                let opacity = map_animated(&opacity, |opacity| {
+                    let opacity = opacity.clamp(0.0, 1.0);
+                    (1.0 - (1.0 - opacity).powf(1.0 / steps as f32)) * 100.0
+                });
+                Stroke::new(
+                    color.clone(),
+                    width,
+                    opacity,
+                    LineCap::Round,
+                    LineJoin::Round,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Sequential strokes share the start to end range, each mask taking a
+        // part proportional to its length. Lengths of animated masks are
+        // measured on their first keyframe
+        let lengths = masks
+            .iter()
+            .map(|mask| mask_length(mask))
+            .collect::<Vec<_>>();
+        let total = lengths.iter().sum::<f32>();
+        let sequential = initial(2, 0.0) != 0.0 && total > 0.0;
+        let mut offset = 0.0;
+        let mut shapes = vec![];
+        for (mask, length) in masks.into_iter().zip(lengths) {
+            let (from, to) = if sequential {
+                (offset / total * 100.0, (offset + length) / total * 100.0)
+            } else {
+                (0.0, 100.0)
+            };
+            offset += length;
+            let trim = Trim {
+                start: remap_range(&start, from, to, start_frame, end_frame),
+                end: remap_range(&end, from, to, start_frame, end_frame),
+                offset: Animated::from_value(0.0),
+                multiple_shape: TrimMultipleShape::Individually,
+            };
+            for stroke in &strokes {
+                let shape = |shape: Shape| ShapeLayer {
+                    name: None,
+                    hidden: false,
+                    shape,
+                };
+                shapes.push(shape(Shape::Group {
+                    shapes: vec![
+                        shape(Shape::Path {
+                            d: mask.points.clone(),
+                            text_range: None,
+                        }),
+                        shape(Shape::Trim(trim.clone())),
+                        shape(Shape::Stroke(stroke.clone())),
+                        shape(Shape::Transform(Transform::default())),
+                    ],
+                }));
+            }
+        }
+        Some(MaskStroke {
+            shapes,
+            paint_style,
+        })
+    }
+}
+
+/// Convert every keyframe of `animated` with `f`, keeping their timing
+fn map_animated<T, U>(animated: &Animated<T>, f: impl Fn(&T) -> U) -> Animated<U> {
+    Animated {
+        animated: animated.animated,
+        keyframes: animated
+            .keyframes
+            .iter()
+            .map(|keyframe| keyframe.alter_value(f(&keyframe.start_value), f(&keyframe.end_value)))
+            .collect(),
+    }
+}
+
+/// Map percentages of `range` from `from..=to` to `0.0..=100.0`, clamping the
+/// values outside of it. Clamping does not preserve easing, so animated ranges
+/// are resampled linearly on every frame
+fn remap_range(
+    range: &Animated<f32>,
+    from: f32,
+    to: f32,
+    start_frame: f32,
+    end_frame: f32,
+) -> Animated<f32> {
+    if from == 0.0 && to == 100.0 {
+        return range.clone();
+    }
+    let remap = |value: f32| {
+        if to > from {
+            ((value - from) / (to - from) * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        }
+    };
+    if !range.is_animated() {
+        return Animated::from_value(remap(range.initial_value()));
+    }
+    sample_frames(start_frame, end_frame, |frame| remap(range.value(frame)))
+}
+
+/// Linear keyframes of `f` sampled on every frame between `start_frame` and
+/// `end_frame`
+fn sample_frames(start_frame: f32, end_frame: f32, f: impl Fn(f32) -> f32) -> Animated<f32> {
+    let start = start_frame.floor() as i32;
+    let end = (end_frame.ceil() as i32).max(start + 1);
+    Animated {
+        animated: true,
+        keyframes: (start..end)
+            .map(|frame| {
+                let frame = frame as f32;
+                KeyFrame {
+                    start_value: f(frame),
+                    end_value: f(frame + 1.0),
+                    start_frame: frame,
+                    end_frame: frame + 1.0,
+                    easing_out: None,
+                    easing_in: None,
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Length of the outline of `mask` on its first keyframe, once flattened
+fn mask_length(mask: &Mask) -> f32 {
+    mask.points
+        .initial_value()
+        .path(0.0)
+        .iter()
+        .flattened(PATH_LENGTH_TOLERANCE)
+        .map(|event| match event {
+            PathEvent::Line { from, to } => (to - from).length(),
+            PathEvent::End {
+                last,
+                first,
+                close: true,
+            } => (first - last).length(),
+            _ => 0.0,
+        })
+        .sum()
+}
+
+/// Normalized weights of a gaussian kernel, covering 3 standard deviations on
+/// each side
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil() as i32;
+    let weights = (-radius..=radius)
+        .map(|x| (-((x * x) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<_>>();
+    let sum = weights.iter().sum::<f32>();
+    weights.into_iter().map(|w| w / sum).collect()
+}
+
+/// Convolve every row, or every column if not `horizontal`, of RGBA `pixels`
+/// with `kernel`
+fn convolve(
+    pixels: &[f32],
+    width: usize,
+    height: usize,
+    kernel: &[f32],
+    horizontal: bool,
+    repeat_edge: bool,
+) -> Vec<f32> {
+    let radius = (kernel.len() / 2) as isize;
+    let (len, lines) = if horizontal {
+        (width, height)
+    } else {
+        (height, width)
+    };
+    let index = |line: usize, pos: usize| {
+        if horizontal {
+            line * width + pos
+        } else {
+            pos * width + line
+        }
+    };
+    let mut result = vec![0.0; pixels.len()];
+    for line in 0..lines {
+        for pos in 0..len {
+            let mut sum = [0.0_f32; 4];
+            for (offset, weight) in kernel.iter().enumerate() {
+                let mut sample = pos as isize + offset as isize - radius;
+                if sample < 0 || sample >= len as isize {
+                    if !repeat_edge {
+                        continue;
+                    }
+                    sample = sample.clamp(0, len as isize - 1);
+                }
+                let start = index(line, sample as usize) * 4;
+                for (channel, value) in sum.iter_mut().zip(&pixels[start..start + 4]) {
+                    *channel += value * weight;
+                }
+            }
+            let start = index(line, pos) * 4;
+            result[start..start + 4].copy_from_slice(&sum);
+        }
+    }
+    result
+}
@@ -74,4 +74,21 @@ impl MaskHierarchy {
     pub fn masks(&self) -> &[StagedLayerMask] {
         &self.stack
     }
+
+    /// Whether any of the mattes needs the color of its source, not only the
+    /// alpha channel
+    pub fn has_luma(&self) -> bool {
+        self.stack.iter().any(|mask| mask.mode.is_luma())
+    }
+
+    /// Combined coverage of all mattes at one pixel. `sample` should return the
+    /// straight-alpha RGBA color of the given matte source layer at that pixel.
+    pub fn coverage<F>(&self, mut sample: F) -> f32
+    where
+        F: FnMut(Id) -> [f32; 4],
+    {
+        self.stack.iter().fold(1.0, |current, mask| {
+            current * mask.mode.coverage(sample(mask.id))
+        })
+    }
 }
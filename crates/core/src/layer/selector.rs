@@ -0,0 +1,202 @@
+use std::f32::consts::PI;
+
+use crate::model::{
+    Animated, Easing, KeyFrame, TextBased, TextRange, TextRangeInfo, TextRangeSelector,
+    TextRangeUnits, TextShape,
+};
+
+/// Units of a text a range selector is based on, e.g. its words
+#[derive(Debug, Clone)]
+pub struct TextUnits {
+    /// Unit of each character of the text, line breaks excluded
+    pub indices: Vec<usize>,
+    /// Number of units of the text
+    pub count: usize,
+}
+
+impl TextUnits {
+    /// Split `lines` of characters into units the way After Effects counts
+    /// them: spaces belong to the word before them, and take the unit of the
+    /// next character when spaces are excluded. Randomized units are shuffled
+    /// in an order which only depends on their count
+    pub fn new(lines: &[Vec<char>], based_on: TextBased, randomize: bool) -> Self {
+        // Line breaks end words and lines without being rendered
+        let letters = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(index, line)| {
+                let line_break = (index + 1 < lines.len()).then_some(None);
+                line.iter().copied().map(Some).chain(line_break)
+            })
+            .collect::<Vec<_>>();
+        let mut indices = vec![];
+        let mut count = 0;
+        for (index, letter) in letters.iter().enumerate() {
+            if letter.is_some() {
+                indices.push(count);
+            }
+            let last = index + 1 == letters.len();
+            let ends_unit = match (based_on, letter) {
+                (TextBased::Characters, Some(_)) => true,
+                (TextBased::CharactersExcludingSpaces, Some(c)) => *c != ' ',
+                (TextBased::Words, Some(c)) => *c == ' ' || last,
+                (TextBased::Lines, Some(_)) => last,
+                (TextBased::Words | TextBased::Lines, None) => true,
+                _ => false,
+            };
+            if ends_unit {
+                count += 1;
+            }
+        }
+        if randomize {
+            let order = shuffled(count);
+            for index in indices.iter_mut() {
+                if let Some(random) = order.get(*index) {
+                    *index = *random;
+                }
+            }
+        }
+        TextUnits { indices, count }
+    }
+}
+
+/// A permutation of `0..count`, always the same for a given count as the
+/// random seed of selectors is not exported
+fn shuffled(count: usize) -> Vec<usize> {
+    let mut order = (0..count).collect::<Vec<_>>();
+    let mut state = 0x2545_f491_u32;
+    for index in (1..count).rev() {
+        // xorshift32
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        order.swap(index, state as usize % (index + 1));
+    }
+    order
+}
+
+impl TextRangeSelector {
+    /// Influence of this selector on the `index`-th of `count` units at
+    /// `frame`, from 0 to 1 then scaled by the amount
+    pub fn influence(&self, index: usize, count: usize, frame: f32) -> f32 {
+        let value = |animated: &Option<Animated<f32>>, default: f32| {
+            animated
+                .as_ref()
+                .map(|animated| animated.value(frame))
+                .unwrap_or(default)
+        };
+        let (divisor, default_end) = match self.range_units {
+            TextRangeUnits::Percent => (100.0 / count.max(1) as f32, 100.0),
+            TextRangeUnits::Index => (1.0, count as f32),
+        };
+        let offset = value(&self.offset, 0.0) / divisor;
+        let start = value(&self.start, 0.0) / divisor + offset;
+        let end = value(&self.end, default_end) / divisor + offset;
+        let (start, end) = if start > end {
+            (end, start)
+        } else {
+            (start, end)
+        };
+        let index = index as f32;
+        let length = end - start;
+        // Position of the center of the unit within the range
+        let ratio = ((index + 0.5 - start) / length).clamp(0.0, 1.0);
+        let influence = match self.shape {
+            // Square selectors cover the part of the unit inside the range
+            TextShape::Square => (end.min(index + 1.0) - start.max(index)).clamp(0.0, 1.0),
+            // Empty ramps are steps at the end of the range
+            TextShape::RampUp | TextShape::RampDown if length == 0.0 => {
+                if (index >= end) == (self.shape == TextShape::RampUp) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            _ if length == 0.0 => 0.0,
+            TextShape::RampUp => ratio,
+            TextShape::RampDown => 1.0 - ratio,
+            TextShape::Triangle => 1.0 - (2.0 * ratio - 1.0).abs(),
+            TextShape::Round => (1.0 - (2.0 * ratio - 1.0).powi(2)).sqrt(),
+            TextShape::Smooth => (1.0 + (PI + 2.0 * PI * ratio).cos()) / 2.0,
+        };
+        let mut influence = self.ease(influence, frame);
+        let smoothness = value(&self.selector_smoothness, 100.0) / 100.0;
+        if self.shape == TextShape::Square && smoothness < 1.0 {
+            // Smoothness narrows the transition around half of the influence
+            let smoothness = smoothness.max(f32::EPSILON);
+            let threshold = 0.5 - smoothness * 0.5;
+            influence = if influence < threshold {
+                0.0
+            } else {
+                ((influence - threshold) / smoothness).min(1.0)
+            };
+        }
+        influence * self.max_amount.value(frame) / 100.0
+    }
+
+    /// Apply the min and max ease of this selector to `influence`
+    fn ease(&self, influence: f32, frame: f32) -> f32 {
+        let min_ease = self.min_ease.value(frame) / 100.0;
+        let max_ease = self.max_ease.value(frame) / 100.0;
+        if min_ease == 0.0 && max_ease == 0.0 {
+            return influence;
+        }
+        let (x1, y1) = if min_ease > 0.0 {
+            (min_ease, 0.0)
+        } else {
+            (0.0, -min_ease)
+        };
+        let (x2, y2) = if max_ease > 0.0 {
+            (1.0 - max_ease, 1.0)
+        } else {
+            (1.0, 1.0 + max_ease)
+        };
+        let curve = KeyFrame {
+            start_value: 0.0,
+            end_value: 1.0,
+            start_frame: 0.0,
+            end_frame: 1.0,
+            easing_out: Some(Easing {
+                x: vec![x1],
+                y: vec![y1],
+            }),
+            easing_in: Some(Easing {
+                x: vec![x2],
+                y: vec![y2],
+            }),
+        };
+        curve.value(influence)
+    }
+}
+
+impl TextRangeInfo {
+    /// Index of the glyph in the whole text
+    pub fn glyph(&self) -> usize {
+        self.value[..self.index.0]
+            .iter()
+            .map(|line| line.len())
+            .sum::<usize>()
+            + self.index.1
+    }
+
+    /// Ranges of the text with their influence on this glyph at `frame`
+    pub fn influences(&self, frame: f32) -> impl Iterator<Item = (&TextRange, f32)> + '_ {
+        self.influences_at(self.glyph(), frame)
+    }
+
+    /// Ranges of the text with their influence on the `glyph`-th glyph of the
+    /// text at `frame`
+    pub fn influences_at(
+        &self,
+        glyph: usize,
+        frame: f32,
+    ) -> impl Iterator<Item = (&TextRange, f32)> + '_ {
+        self.ranges
+            .iter()
+            .zip(self.units.iter())
+            .map(move |(range, units)| {
+                let index = units.indices.get(glyph).copied().unwrap_or(units.count);
+                (range, range.selector.influence(index, units.count, frame))
+            })
+    }
+}
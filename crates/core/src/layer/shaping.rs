@@ -0,0 +1,331 @@
+use std::ops::Range;
+
+use rustybuzz::ttf_parser::{GlyphId, OutlineBuilder};
+use rustybuzz::{Direction, Face, Tag, UnicodeBuffer};
+use unicode_bidi::{BidiInfo, Level, ParagraphBidiInfo};
+use unicode_script::{Script, UnicodeScript};
+
+use crate::model::{Bezier, Vector2D};
+
+/// A glyph of a shaped cluster, in font units with y pointing up
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedGlyph {
+    pub id: u16,
+    pub advance: f32,
+    pub offset: Vector2D,
+}
+
+/// Characters of a paragraph shaped into glyphs which can't be split, like a
+/// base letter and its marks, a ligature or an emoji sequence. Clusters are
+/// drawn, wrapped and animated as a single glyph
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedCluster {
+    /// Bytes of the paragraph making up the cluster
+    pub range: Range<usize>,
+    /// Glyphs of the cluster, from left to right
+    pub glyphs: Vec<ShapedGlyph>,
+    /// Embedding level of the cluster, odd when it is right-to-left
+    pub level: u8,
+    /// Script of the run of the cluster
+    pub script: Script,
+    /// Index of the face the cluster is shaped with
+    pub face: usize,
+}
+
+impl ShapedCluster {
+    /// Advance of the cluster in font units
+    pub fn advance(&self) -> f32 {
+        self.glyphs.iter().map(|glyph| glyph.advance).sum()
+    }
+
+    pub fn is_rtl(&self) -> bool {
+        self.level % 2 == 1
+    }
+}
+
+/// Shape a paragraph of `text` with `faces`, returning its clusters in
+/// logical order. The paragraph is split into runs of a single direction and
+/// script, each of them shaped on its own with the first face of the `chain`
+/// of its script. Clusters a face has no glyph for are shaped again with the
+/// next faces of the chain
+pub fn shape_paragraph(
+    faces: &[Face],
+    text: &str,
+    chain: impl Fn(Script) -> Vec<usize>,
+) -> Vec<ShapedCluster> {
+    if text.is_empty() {
+        return vec![];
+    }
+    let bidi = ParagraphBidiInfo::new(text, None);
+    let mut clusters = vec![];
+    for (range, script) in script_runs(text) {
+        let mut start = range.start;
+        // Split the script run where the embedding level changes
+        while start < range.end {
+            let level = bidi.levels[start];
+            let end = text[start..range.end]
+                .char_indices()
+                .map(|(index, _)| start + index)
+                .find(|index| bidi.levels[*index] != level)
+                .unwrap_or(range.end);
+            let run = Run {
+                range: start..end,
+                script,
+                level,
+            };
+            clusters.extend(shape_with_fallbacks(faces, &chain(script), text, run));
+            start = end;
+        }
+    }
+    clusters
+}
+
+/// Split `text` into runs of a single script. Common and inherited
+/// characters, like spaces, digits and marks, belong to the run around them
+pub fn script_runs(text: &str) -> Vec<(Range<usize>, Script)> {
+    let mut runs: Vec<(Range<usize>, Script)> = vec![];
+    for (index, c) in text.char_indices() {
+        let script = c.script();
+        let end = index + c.len_utf8();
+        match runs.last_mut() {
+            Some((range, current))
+                if script == *current || matches!(script, Script::Common | Script::Inherited) =>
+            {
+                range.end = end;
+            }
+            // Leading common characters take the script of the first run
+            Some((range, current)) if matches!(*current, Script::Common | Script::Inherited) => {
+                range.end = end;
+                *current = script;
+            }
+            _ => runs.push((index..end, script)),
+        }
+    }
+    runs
+}
+
+/// Visual order of clusters with the given embedding levels, from left to
+/// right
+pub fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let levels = levels
+        .iter()
+        .map(|level| Level::new(*level).unwrap_or_else(|_| Level::ltr()))
+        .collect::<Vec<_>>();
+    BidiInfo::reorder_visual(&levels)
+}
+
+/// Text of a single direction and script
+#[derive(Clone)]
+struct Run {
+    range: Range<usize>,
+    script: Script,
+    level: Level,
+}
+
+/// Shape `run` with the first face of `chain`, shaping the clusters it has no
+/// glyph for with the rest of the chain. Clusters none of the faces have
+/// glyphs for are shaped with the first one
+fn shape_with_fallbacks(
+    faces: &[Face],
+    chain: &[usize],
+    text: &str,
+    run: Run,
+) -> Vec<ShapedCluster> {
+    let Some((first, fallbacks)) = chain.split_first() else {
+        return vec![];
+    };
+    let clusters = shape_run(&faces[*first], *first, text, run.clone());
+    if fallbacks.is_empty() {
+        return clusters;
+    }
+    let missing = |cluster: &ShapedCluster| {
+        cluster.glyphs.iter().any(|glyph| glyph.id == 0)
+            && !text[cluster.range.clone()].chars().all(char::is_whitespace)
+    };
+    let mut result = vec![];
+    let mut index = 0;
+    while index < clusters.len() {
+        if !missing(&clusters[index]) {
+            result.push(clusters[index].clone());
+            index += 1;
+            continue;
+        }
+        // Consecutive missing clusters are shaped together, so that the
+        // fallback can join them
+        let end = clusters[index..]
+            .iter()
+            .position(|cluster| !missing(cluster))
+            .map_or(clusters.len(), |count| index + count);
+        let range = clusters[index].range.start..clusters[end - 1].range.end;
+        let fallback = shape_with_fallbacks(
+            faces,
+            fallbacks,
+            text,
+            Run {
+                range,
+                ..run.clone()
+            },
+        );
+        if fallback.iter().all(missing) {
+            result.extend_from_slice(&clusters[index..end]);
+        } else {
+            result.extend(fallback);
+        }
+        index = end;
+    }
+    result
+}
+
+fn shape_run(face: &Face, index: usize, text: &str, run: Run) -> Vec<ShapedCluster> {
+    let Run {
+        range,
+        script,
+        level,
+    } = run;
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(&text[range.clone()]);
+    buffer.set_direction(if level.is_rtl() {
+        Direction::RightToLeft
+    } else {
+        Direction::LeftToRight
+    });
+    if let Some(script) =
+        rustybuzz::Script::from_iso15924_tag(Tag::from_bytes_lossy(script.short_name().as_bytes()))
+    {
+        buffer.set_script(script);
+    }
+    buffer.guess_segment_properties();
+    let output = rustybuzz::shape(face, &[], buffer);
+
+    // Glyphs come out from left to right, with decreasing clusters in
+    // right-to-left runs
+    let mut clusters: Vec<ShapedCluster> = vec![];
+    for (info, position) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+        let start = range.start + info.cluster as usize;
+        let glyph = ShapedGlyph {
+            id: info.glyph_id as u16,
+            advance: position.x_advance as f32,
+            offset: Vector2D::new(position.x_offset as f32, position.y_offset as f32),
+        };
+        match clusters.last_mut() {
+            Some(cluster) if cluster.range.start == start => cluster.glyphs.push(glyph),
+            _ => clusters.push(ShapedCluster {
+                range: start..start,
+                glyphs: vec![glyph],
+                level: level.number(),
+                script,
+                face: index,
+            }),
+        }
+    }
+    if level.is_rtl() {
+        clusters.reverse();
+    }
+    // Each cluster spans the text up to the next one
+    let ends = clusters
+        .iter()
+        .skip(1)
+        .map(|cluster| cluster.range.start)
+        .chain([range.end])
+        .collect::<Vec<_>>();
+    for (cluster, end) in clusters.iter_mut().zip(ends) {
+        cluster.range.end = end;
+    }
+    clusters
+}
+
+/// Outline of the glyph `id` of `face`, scaled by `scale` and flipped so that
+/// y points down, `offset` being the position of its origin
+pub fn glyph_beziers(face: &Face, id: u16, scale: f32, offset: Vector2D) -> Vec<Bezier> {
+    let mut builder = BezierBuilder {
+        beziers: vec![],
+        bezier: Bezier::default(),
+        last: Vector2D::zero(),
+        scale,
+        offset,
+    };
+    face.outline_glyph(GlyphId(id), &mut builder);
+    builder.finish()
+}
+
+struct BezierBuilder {
+    beziers: Vec<Bezier>,
+    bezier: Bezier,
+    last: Vector2D,
+    scale: f32,
+    offset: Vector2D,
+}
+
+impl BezierBuilder {
+    fn point(&self, x: f32, y: f32) -> Vector2D {
+        self.offset + Vector2D::new(x, -y) * self.scale
+    }
+
+    fn push(&mut self, out_tangent: Vector2D, in_tangent: Vector2D, point: Vector2D) {
+        self.bezier.out_tangent.push(out_tangent);
+        self.bezier.in_tangent.push(in_tangent);
+        self.bezier.verticies.push(point);
+        self.last = point;
+    }
+
+    fn end_contour(&mut self) {
+        if !self.bezier.verticies.is_empty() {
+            let mut bezier = std::mem::take(&mut self.bezier);
+            if bezier.out_tangent.len() < bezier.verticies.len() {
+                bezier.out_tangent.push(Vector2D::zero());
+            }
+            self.beziers.push(bezier);
+        }
+    }
+
+    fn finish(mut self) -> Vec<Bezier> {
+        self.end_contour();
+        self.beziers
+    }
+}
+
+impl OutlineBuilder for BezierBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.end_contour();
+        let point = self.point(x, y);
+        self.bezier.in_tangent.push(Vector2D::zero());
+        self.bezier.verticies.push(point);
+        self.last = point;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.push(Vector2D::zero(), Vector2D::zero(), point);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        // Quadratic curves are elevated to cubic ones
+        let control = self.point(x1, y1);
+        let point = self.point(x, y);
+        self.push(
+            (control - self.last) * (2.0 / 3.0),
+            (control - point) * (2.0 / 3.0),
+            point,
+        );
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let control1 = self.point(x1, y1);
+        let control2 = self.point(x2, y2);
+        let point = self.point(x, y);
+        self.push(control1 - self.last, control2 - point, point);
+    }
+
+    fn close(&mut self) {
+        // Contours usually end on their first point, which closing them
+        // already joins
+        let bezier = &mut self.bezier;
+        if bezier.verticies.len() > 1 && bezier.verticies.last() == bezier.verticies.first() {
+            bezier.verticies.pop();
+            if let Some(in_tangent) = bezier.in_tangent.pop() {
+                bezier.in_tangent[0] = in_tangent;
+            }
+        }
+        bezier.closed = true;
+    }
+}
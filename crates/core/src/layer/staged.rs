@@ -2,8 +2,9 @@ use crate::model::*;
 
 use crate::font::FontDB;
 use crate::prelude::{Id, MaskHierarchy};
-use crate::Error;
+use crate::{Diagnostic, Error};
 
+use super::effect::{MaskStroke, StrokePaintStyle};
 use super::frame::{FrameTransform, FrameTransformHierarchy};
 use super::hierarchy::TransformHierarchy;
 use super::media::Media;
@@ -26,6 +27,7 @@ impl RenderableContent {
             zindex: 0.0,
             target: TargetRef::Layer(0),
             parent: None,
+            composition: None,
             start_frame: layer.start_frame,
             end_frame: layer.end_frame,
             transform: transform.clone(),
@@ -34,9 +36,13 @@ impl RenderableContent {
             frame_transform: FrameTransform::new(0.0, layer.start_time),
             frame_transform_hierarchy: FrameTransformHierarchy::default(),
             is_mask: false,
+            mask_group: None,
             matte_mode: layer.matte_mode,
             mask_hierarchy: MaskHierarchy::default(),
             blend_mode: layer.blend_mode.unwrap_or(BlendMode::Normal),
+            effects: layer.effects.clone(),
+            is_3d: layer.is_3d,
+            motion_blur: layer.motion_blur,
         }
     }
 }
@@ -52,6 +58,8 @@ pub(crate) enum ContentInfo {
     ContentWithMasks {
         content: RenderableContent,
         masks: Vec<(RenderableContent, MatteMode)>,
+        /// Content drawn above `content` which the masks don't apply to
+        overlay: Option<RenderableContent>,
     },
     TextKeyframes(Vec<TextKeyframe>),
 }
@@ -75,6 +83,9 @@ pub struct StagedLayer {
     pub end_frame: f32,
     pub frame_rate: f32,
     pub parent: Option<Id>,
+    /// The precomposition or media layer this layer is part of. Unlike
+    /// `parent`, this is not changed by transform parenting
+    pub composition: Option<Id>,
     pub zindex: f32,
     pub transform: Transform,
     pub transform_hierarchy: TransformHierarchy,
@@ -82,9 +93,19 @@ pub struct StagedLayer {
     pub frame_transform_hierarchy: FrameTransformHierarchy,
     /// Mask info of this layer
     pub is_mask: bool,
+    /// The matte item this one is drawn into, when the matte source layer is
+    /// staged as several items
+    pub mask_group: Option<Id>,
     pub matte_mode: Option<MatteMode>,
     pub mask_hierarchy: MaskHierarchy,
     pub blend_mode: BlendMode,
+    /// Effects applied to the rendered content of this layer, in order
+    pub effects: Vec<Effect>,
+    /// Whether the layer is projected by the camera of the composition
+    pub is_3d: bool,
+    /// Whether the motion of the layer is blurred over the shutter of the
+    /// composition
+    pub motion_blur: bool,
 }
 
 impl ContentInfo {
@@ -93,10 +114,46 @@ impl ContentInfo {
         model: &Model,
         fontdb: &FontDB,
         root_path: &str,
+        diagnostics: &mut Vec<Diagnostic>,
     ) -> Result<ContentInfo, Error> {
         let content = match layer.content.clone() {
-            LayerContent::Shape(shape_group) => {
+            LayerContent::Shape(mut shape_group) => {
                 let mut result = vec![];
+                let mut overlay = vec![];
+                let start_frame = layer.start_frame - layer.start_time;
+                let end_frame = layer.end_frame - layer.start_time;
+                for effect in layer.effects.iter().filter(|effect| effect.enabled()) {
+                    let Effect::Stroke(group) = effect else {
+                        continue;
+                    };
+                    let Some(stroke) =
+                        MaskStroke::new(group, &layer.masks_properties, start_frame, end_frame)
+                    else {
+                        continue;
+                    };
+                    match stroke.paint_style {
+                        StrokePaintStyle::OnOriginal => overlay.extend(stroke.shapes),
+                        // Without the original content, the stroke is left out
+                        // of the masks like it is when drawn on the original
+                        StrokePaintStyle::OnTransparent if layer.has_mask => {
+                            shape_group.shapes.clear();
+                            overlay.extend(stroke.shapes);
+                        }
+                        StrokePaintStyle::OnTransparent => shape_group.shapes = stroke.shapes,
+                        StrokePaintStyle::RevealOriginal => result.push((
+                            RenderableContent::Shape(ShapeGroup {
+                                shapes: stroke.shapes,
+                            }),
+                            MatteMode::Alpha,
+                        )),
+                    }
+                }
+                let masked = layer.has_mask || !result.is_empty();
+                // Masks are applied before effects, so they don't clip the
+                // stroke drawn on the original content
+                if !masked {
+                    shape_group.shapes.splice(0..0, overlay.drain(..));
+                }
                 let content = RenderableContent::Shape(shape_group);
                 if layer.has_mask {
                     for mask in &layer.masks_properties {
@@ -144,9 +201,13 @@ impl ContentInfo {
                         };
                         result.push((content, matte_mode));
                     }
+                }
+                if masked {
                     ContentInfo::ContentWithMasks {
                         content,
                         masks: result,
+                        overlay: (!overlay.is_empty())
+                            .then(|| RenderableContent::Shape(ShapeGroup { shapes: overlay })),
                     }
                 } else {
                     ContentInfo::Simple(content)
@@ -154,23 +215,26 @@ impl ContentInfo {
             }
             LayerContent::PreCompositionRef(_)
             | LayerContent::Empty
+            | LayerContent::Camera(_)
             | LayerContent::MediaRef(_) => ContentInfo::Simple(RenderableContent::Group.into()),
-            LayerContent::Text(text) => match RenderableContent::from_text(&text, model, fontdb) {
-                Ok(t) => ContentInfo::TextKeyframes(
-                    t.keyframes
-                        .into_iter()
-                        .map(|keyframe| TextKeyframe {
-                            content: keyframe.start_value,
-                            start_frame: keyframe.start_frame,
-                            end_frame: keyframe.end_frame,
-                        })
-                        .collect(),
-                ),
-                Err(e) => {
-                    log::warn!("{:?}", e);
-                    ContentInfo::Simple(RenderableContent::Group)
+            LayerContent::Text(text) => {
+                match RenderableContent::from_text(&text, &layer, model, fontdb, diagnostics) {
+                    Ok(t) => ContentInfo::TextKeyframes(
+                        t.keyframes
+                            .into_iter()
+                            .map(|keyframe| TextKeyframe {
+                                content: keyframe.start_value,
+                                start_frame: keyframe.start_frame,
+                                end_frame: keyframe.end_frame,
+                            })
+                            .collect(),
+                    ),
+                    Err(e) => {
+                        log::warn!("{:?}", e);
+                        ContentInfo::Simple(RenderableContent::Group)
+                    }
                 }
-            },
+            }
             LayerContent::SolidColor {
                 color,
                 height,
@@ -1,28 +1,81 @@
 use crate::model::*;
-use fontkit::{Area, Line, PathSegment, Span};
+use fontkit::{Line, Span};
+use rustybuzz::ttf_parser;
+use unicode_script::Script;
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
 
 use crate::font::FontDB;
-use crate::prelude::RenderableContent;
-use crate::Error;
+use crate::layer::shaping::{glyph_beziers, shape_paragraph, visual_order};
+use crate::layer::text_path::PathLayout;
+use crate::prelude::{RenderableContent, TextUnits};
+use crate::{Diagnostic, Error};
 
-struct GlyphData {
-    c: char,
-    beziers: Vec<Bezier>,
-    offset_x: f32,
-}
+/// Ascent of fonts in percents of their size when the animation does not
+/// provide it
+const DEFAULT_ASCENT: f32 = 75.0;
+/// Size of small caps relative to capitals, as in After Effects
+const SMALL_CAPS_SCALE: f32 = 0.7;
+/// Line height of embedded fonts relative to their size when the document
+/// does not set it, like the auto leading of After Effects
+const AUTO_LEADING: f32 = 1.2;
 
 impl RenderableContent {
+    /// Glyphs of a text layer for each keyframe of its document. Characters
+    /// which can't be drawn with its font are reported to `diagnostics`
     pub fn from_text(
         text: &TextAnimationData,
+        layer: &Layer,
         model: &Model,
         fontdb: &FontDB,
+        diagnostics: &mut Vec<Diagnostic>,
     ) -> Result<Animated<RenderableContent>, Error> {
+        let path = PathLayout::new(
+            &text.follow_path,
+            &layer.masks_properties,
+            layer.start_frame - layer.start_time,
+            layer.end_frame - layer.start_time,
+        );
         let keyframes = text
             .document
             .keyframes
             .iter()
             .map(|keyframe| {
-                let parser = TextDocumentParser::new(keyframe, &text.ranges, &model, fontdb)?;
+                let mut missing = vec![];
+                let mut unlocated = vec![];
+                let parser = TextDocumentParser::new(
+                    keyframe,
+                    text,
+                    path.as_ref(),
+                    model,
+                    fontdb,
+                    &mut missing,
+                    &mut unlocated,
+                )?;
+                for family in unlocated {
+                    let diagnostic = Diagnostic::FontFaceNotFound {
+                        layer: layer.name.clone(),
+                        font: keyframe.start_value.font_name.clone(),
+                        family,
+                    };
+                    if !diagnostics.contains(&diagnostic) {
+                        diagnostics.push(diagnostic);
+                    }
+                }
+                for (character, fallback) in missing {
+                    let diagnostic = Diagnostic::MissingGlyph {
+                        layer: layer.name.clone(),
+                        font: keyframe.start_value.font_name.clone(),
+                        character,
+                        fallback,
+                    };
+                    // Keyframes often share characters
+                    if !diagnostics.contains(&diagnostic) {
+                        diagnostics.push(diagnostic);
+                    }
+                }
                 let shape = parser.shape_layer()?;
                 let content = RenderableContent::Shape(ShapeGroup {
                     shapes: vec![shape],
@@ -35,283 +88,529 @@ impl RenderableContent {
             keyframes,
         })
     }
+
+    /// Take the glyphs blurred by text animators out of the glyphs of a text
+    /// keyframe, each of them with the Gaussian blur drawing it from frame 0
+    /// to `frames`. Blurs are applied to whole layers, so these glyphs are
+    /// drawn on their own, with the transform of their line
+    pub fn take_blurred_glyphs(&mut self, frames: f32) -> Vec<(RenderableContent, Effect)> {
+        let RenderableContent::Shape(ShapeGroup { shapes }) = self else {
+            return vec![];
+        };
+        let mut result = vec![];
+        for root in shapes {
+            let Shape::Group { shapes: lines } = &mut root.shape else {
+                continue;
+            };
+            for line in lines {
+                let Shape::Group { shapes: glyphs } = &mut line.shape else {
+                    continue;
+                };
+                let Some(line_transform) = glyphs.last().cloned() else {
+                    continue;
+                };
+                let mut index = 0;
+                while index < glyphs.len() {
+                    let effect =
+                        glyph_range(&glyphs[index]).and_then(|info| info.blur_effect(frames));
+                    let Some(effect) = effect else {
+                        index += 1;
+                        continue;
+                    };
+                    let glyph = glyphs.remove(index);
+                    let line = ShapeLayer {
+                        name: line.name.clone(),
+                        hidden: false,
+                        shape: Shape::Group {
+                            shapes: vec![glyph, line_transform.clone()],
+                        },
+                    };
+                    let root = ShapeLayer {
+                        name: None,
+                        hidden: false,
+                        shape: Shape::Group { shapes: vec![line] },
+                    };
+                    let content = RenderableContent::Shape(ShapeGroup { shapes: vec![root] });
+                    result.push((content, effect));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Selector and animator data of the glyph drawn by `shape`
+fn glyph_range(shape: &ShapeLayer) -> Option<&TextRangeInfo> {
+    match &shape.shape {
+        Shape::Path { text_range, .. } => text_range.as_ref(),
+        Shape::Group { shapes } => shapes.iter().find_map(glyph_range),
+        _ => None,
+    }
 }
 
 #[derive(Clone)]
 struct Styles {
     fill: Rgb,
     fill_opacity: f32,
+    stroke: Rgb,
+    stroke_opacity: f32,
+    stroke_width: f32,
+}
+
+/// Horizontal layout of a line of text
+pub(crate) struct LineLayout {
+    /// Start of the line
+    pub start: f32,
+    /// Width of the line, tracking between glyphs included
+    pub width: f32,
+    /// Fraction of the free space before the line
+    pub align_factor: f32,
+    /// Start of each glyph
+    pub offsets: Vec<f32>,
+    pub advances: Vec<f32>,
+    /// Position of each glyph from the left of the line, which differs from
+    /// its order in the text within right-to-left runs
+    pub ranks: Vec<usize>,
+    /// Vertical position of the baseline
+    pub baseline: f32,
 }
 
 struct TextDocumentParser<'a> {
-    model: &'a Model,
-    fontdb: &'a FontDB,
-    area: Area<Styles>,
-    lottie_font: &'a Font,
+    lines: Vec<TextLine>,
+    styles: Styles,
     keyframe: &'a KeyFrame<TextDocument>,
+    text: &'a TextAnimationData,
     text_ranges: &'a Vec<TextRange>,
+    path: Option<&'a PathLayout<'a>>,
+    lottie_font: &'a Font,
+}
+
+/// A line of shaped text
+struct TextLine {
+    /// Glyphs of the line, in the order of the text
+    letters: Vec<Letter>,
+    /// Height of the line unless the document sets it
+    height: f32,
+    /// Whether the line was wrapped from the previous one of its paragraph
+    broke_from_prev: bool,
+}
+
+/// Installed fonts of a text, shaped with rustybuzz
+struct InstalledFonts<'a> {
+    /// Fonts of the `FontDB`, the first one being the font of the text
+    fonts: Vec<&'a fontkit::Font>,
+    faces: Vec<rustybuzz::Face<'a>>,
+    /// Fonts to try for each script of the text, in order
+    chains: HashMap<Script, Vec<usize>>,
+}
+
+/// Where the outlines of the glyphs of a text come from
+enum Glyphs<'a> {
+    /// Characters embedded in the animation, and installed fonts for the
+    /// ones which are missing
+    Embedded(Vec<&'a FontChar>, Option<InstalledFonts<'a>>),
+    Installed(InstalledFonts<'a>),
 }
 
 impl<'a> TextDocumentParser<'a> {
     fn new(
         keyframe: &'a KeyFrame<TextDocument>,
-        text_ranges: &'a Vec<TextRange>,
+        text: &'a TextAnimationData,
+        path: Option<&'a PathLayout<'a>>,
         model: &'a Model,
         fontdb: &'a FontDB,
+        missing: &mut Vec<(char, Option<String>)>,
+        unlocated: &mut Vec<String>,
     ) -> Result<Self, Error> {
         let doc = &keyframe.start_value;
         let lottie_font = model
             .font(&doc.font_name)
             .ok_or_else(|| Error::FontFamilyNotFound(doc.font_name.clone()))?;
-        let font = fontdb
-            .font(lottie_font)
-            .ok_or_else(|| Error::FontNotLoaded(doc.font_name.clone()))?;
-        font.load()?;
+        // Embedded characters are preferred, as they don't need the font to
+        // be installed
+        let chars = model.font_chars(lottie_font);
+        let (value, _) = apply_caps(&doc.value, doc.caps);
+        let embedded = !chars.is_empty()
+            && value.chars().all(|c| {
+                c.is_whitespace()
+                    || c == '\u{3}'
+                    || chars.iter().any(|data| data.character == c.to_string())
+            });
+        // Installed fonts are only loaded for characters which are not
+        // embedded
+        let installed;
+        let data;
+        let fonts = if embedded {
+            None
+        } else {
+            let (fonts, chains) = fontdb.fonts(lottie_font, &value);
+            installed = fonts;
+            data = installed
+                .iter()
+                .map(|font| {
+                    font.load()?;
+                    Ok(font.buffer())
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            // Fonts which can't be found in their collection are left out,
+            // and the chains point to the fonts which are kept
+            let mut fonts = vec![];
+            let mut faces = vec![];
+            let mut kept = vec![];
+            for (font, data) in installed.iter().zip(&data) {
+                let Some(index) = collection_index(font, data) else {
+                    unlocated.push(font.key().family);
+                    kept.push(None);
+                    continue;
+                };
+                let face = rustybuzz::Face::from_slice(data, index)
+                    .ok_or_else(|| Error::FontNotLoaded(doc.font_name.clone()))?;
+                kept.push(Some(faces.len()));
+                fonts.push(&**font);
+                faces.push(face);
+            }
+            let chains = chains
+                .into_iter()
+                .map(|(script, chain)| {
+                    let chain = chain.into_iter().filter_map(|index| kept[index]);
+                    (script, chain.collect())
+                })
+                .collect();
+            Some(InstalledFonts {
+                fonts,
+                faces,
+                chains,
+            })
+            .filter(|fonts| !fonts.fonts.is_empty())
+        };
+        let glyphs = if chars.is_empty() {
+            let fonts = fonts.ok_or_else(|| Error::FontNotLoaded(doc.font_name.clone()))?;
+            Glyphs::Installed(fonts)
+        } else {
+            Glyphs::Embedded(chars, fonts)
+        };
 
         // parse fill/opacity data
         let rgb = Rgb::new_u8(doc.fill_color.r, doc.fill_color.g, doc.fill_color.b);
 
         let opacity = doc.fill_color.a as f32 / 255.0 * 100.0;
+        let stroke = &doc.stroke_color;
         let styles = Styles {
             fill: rgb,
             fill_opacity: opacity,
+            stroke: Rgb::new_u8(stroke.r, stroke.g, stroke.b),
+            stroke_opacity: stroke.a as f32 / 255.0 * 100.0,
+            stroke_width: doc.stroke_width,
         };
-        // parse font data
-        let mut area = Area::new();
-        for line in doc.value.split('\r') {
-            let metrics = font.measure(&line)?;
-            let span = Span {
-                font_key: font.key(),
-                letter_spacing: 0.0,
-                line_height: None,
-                size: doc.size,
-                broke_from_prev: false,
-                metrics,
-                swallow_leading_space: false,
-                additional: styles.clone(),
+        let mut lines = vec![];
+        for paragraph in doc.value.split(['\r', '\u{3}']) {
+            let (text, small) = apply_caps(paragraph, doc.caps);
+            let letters = glyphs.letters(&text, &small, doc, missing);
+            let paragraph_lines = match doc.text_box() {
+                Some(text_box) => wrap(letters, text_box.width()),
+                None => vec![letters],
             };
-            let line = Line::new(span);
-            area.lines.push(line);
+            for (index, letters) in paragraph_lines.into_iter().enumerate() {
+                let height = glyphs.line_height(&letters, doc, &styles)?;
+                lines.push(TextLine {
+                    letters,
+                    height,
+                    broke_from_prev: index > 0,
+                });
+            }
         }
 
         Ok(TextDocumentParser {
-            model,
-            fontdb,
-            area,
-            lottie_font,
+            lines,
+            styles,
             keyframe,
-            text_ranges,
+            text,
+            text_ranges: &text.ranges,
+            path,
+            lottie_font,
         })
     }
 
     fn shape_layer(&self) -> Result<ShapeLayer, Error> {
-        let font = self
-            .fontdb
-            .font(self.lottie_font)
-            .ok_or_else(|| Error::FontNotLoaded(self.lottie_font.name.clone()))?;
-        font.load()?;
-        let units = font.units_per_em() as f32;
         let doc = &self.keyframe.start_value;
 
         let mut result = vec![];
-        let align_factor = match doc.justify {
-            TextJustify::Left => 0.0,
-            TextJustify::Center => -0.5,
-            TextJustify::Right => -1.0,
-            _ => 0.0, // TODO: support other TextJustify options
-        };
+        let tracking = doc.tracking / 1000.0 * doc.size;
+        let line_height = |line: &TextLine| doc.line_height.unwrap_or(line.height);
         let start_shift_y = -doc.baseline_shift;
         let mut line_y = 0.0;
-        let value = self
-            .area
-            .lines
+        let mut lines = &self.lines[..];
+        let text_box = doc.text_box();
+        if let Some(text_box) = text_box {
+            // Lines overflowing the bottom of the box are hidden
+            let mut height = 0.0;
+            let count = lines
+                .iter()
+                .take_while(|line| {
+                    height += line_height(line);
+                    height <= text_box.height()
+                })
+                .count();
+            lines = &lines[..count];
+            let height = lines.iter().map(line_height).sum::<f32>();
+            let valign_factor = match doc.vertical_justify {
+                TextVerticalJustify::Top => 0.0,
+                TextVerticalJustify::Center => 0.5,
+                TextVerticalJustify::Bottom => 1.0,
+            };
+            // The first baseline is one ascent below the top of the box
+            let ascent = self.lottie_font.ascent.unwrap_or(DEFAULT_ASCENT) / 100.0 * doc.size;
+            line_y = text_box.min_y() + ascent + (text_box.height() - height) * valign_factor;
+        }
+        let (box_x, box_width) = text_box
+            .map(|text_box| (text_box.min_x(), text_box.width()))
+            .unwrap_or_default();
+        // Selectors and animators work on shaped clusters in the order of the
+        // text, each of them standing for one glyph
+        let value = lines
             .iter()
-            .map(|l| {
-                l.spans
+            .map(|line| {
+                line.letters
                     .iter()
-                    .flat_map(|span| span.metrics.positions().iter().map(|p| p.metrics.c))
+                    .map(|letter| letter.c)
                     .collect::<Vec<_>>()
             })
-            .collect::<Vec<_>>();
-        for (line_index, line) in self.area.lines.iter().enumerate() {
-            let mut char_index = 0;
-            let mut adv = line.width() * align_factor;
-            for span in &line.spans {
-                let factor = span.size / units;
-                let mut all_beziers = vec![];
-
-                // styles
-                let fill = self
-                    .keyframe
-                    .alter_value(span.additional.fill, span.additional.fill);
-                let fill_opacity = self
-                    .keyframe
-                    .alter_value(span.additional.fill_opacity, span.additional.fill_opacity);
-                let fill_layer = ShapeLayer {
-                    name: None,
-                    hidden: false,
-                    shape: Shape::Fill(Fill {
-                        opacity: Animated {
-                            animated: false,
-                            keyframes: vec![fill_opacity],
-                        },
-                        color: Animated {
-                            animated: false,
-                            keyframes: vec![fill],
-                        },
-                        fill_rule: FillRule::NonZero,
-                    }),
-                };
-                for c in span.metrics.positions() {
-                    let (glyph, _) = font.outline(c.metrics.c).ok_or_else(|| {
-                        Error::FontGlyphNotFound(self.lottie_font.name.clone(), c.metrics.c)
-                    })?;
-                    let mut bezier = Bezier::default();
-                    let mut beziers = vec![];
-                    let mut last_pt = Vector2D::new(0.0, 0.0);
-                    let length = c.metrics.advanced_x as f32 * factor + c.kerning as f32 * factor;
-                    let segments = glyph.path.finish();
-                    let segments = segments
-                        .as_ref()
-                        .map(|p| p.segments())
-                        .into_iter()
-                        .flatten();
-                    for segment in segments {
-                        match segment {
-                            PathSegment::MoveTo(p) => {
-                                if !bezier.verticies.is_empty() {
-                                    let mut old = std::mem::replace(&mut bezier, Bezier::default());
-                                    old.out_tangent.push(Vector2D::new(0.0, 0.0));
-                                    beziers.push(old);
-                                }
-                                bezier.in_tangent.push(Vector2D::new(0.0, 0.0));
-                                last_pt = Vector2D::new(p.x, -p.y) * factor;
-                                bezier.verticies.push(last_pt);
-                            }
-                            PathSegment::LineTo(p) => {
-                                let pt = Vector2D::new(p.x, -p.y) * factor;
-                                bezier.out_tangent.push(Vector2D::new(0.0, 0.0));
-                                bezier.in_tangent.push(Vector2D::new(0.0, 0.0));
-                                bezier.verticies.push(pt);
-                                last_pt = pt;
-                            }
-                            PathSegment::CubicTo(p1, p2, p) => {
-                                let pt1 = Vector2D::new(p1.x, -p1.y) * factor;
-                                let pt2 = Vector2D::new(p2.x, -p2.y) * factor;
-                                let pt = Vector2D::new(p.x, -p.y) * factor;
-
-                                bezier.out_tangent.push(pt1 - last_pt);
-                                bezier.in_tangent.push(pt2 - pt);
-                                bezier.verticies.push(pt);
-                                last_pt = pt;
-                            }
-                            PathSegment::QuadTo(p1, p) => {
-                                let pt1 = Vector2D::new(p1.x, -p1.y) * factor;
-                                let pt = Vector2D::new(p.x, -p.y) * factor;
-
-                                bezier.out_tangent.push(pt1 - last_pt);
-                                bezier.in_tangent.push(pt1 - pt);
-                                bezier.verticies.push(pt);
-                                last_pt = pt;
-                            }
-                            PathSegment::Close => {
-                                bezier.closed = true;
-                            }
-                        }
-                    }
-                    if !bezier.verticies.is_empty() {
-                        bezier.out_tangent.push(Vector2D::new(0.0, 0.0));
-                        beziers.push(bezier);
-                    }
-                    all_beziers.push(GlyphData {
-                        c: c.metrics.c,
-                        beziers,
-                        offset_x: adv,
-                    });
-
-                    adv += length;
+            .collect::<Arc<[_]>>();
+        let ranges = Arc::<[TextRange]>::from(self.text_ranges.as_slice());
+        let range_units = self
+            .text_ranges
+            .iter()
+            .map(|range| TextUnits::new(&value, range.selector.based_on, range.selector.randomize))
+            .collect::<Arc<[_]>>();
+        // Lines are laid out first, as anchor groups and paths span several
+        // glyphs
+        let mut layouts = vec![];
+        for (line_index, line) in lines.iter().enumerate() {
+            let line_letters = &line.letters;
+            let advances = line_letters
+                .iter()
+                .map(|letter| letter.advance - tracking)
+                .collect::<Vec<_>>();
+            // Tracking only spaces glyphs, so that it doesn't shift aligned
+            // lines
+            let width = advances.iter().sum::<f32>()
+                + tracking * line_letters.len().saturating_sub(1) as f32;
+            // Wrapped lines continue the paragraph of the previous line
+            let last_of_paragraph = !self
+                .lines
+                .get(line_index + 1)
+                .is_some_and(|next| next.broke_from_prev);
+            // Fraction of the free space of the box before the line, and
+            // whether the line is stretched to the width of the box
+            let (align_factor, full) = match (doc.justify, last_of_paragraph) {
+                (TextJustify::Left, _) => (0.0, false),
+                (TextJustify::Right, _) => (1.0, false),
+                (TextJustify::Center, _) => (0.5, false),
+                (TextJustify::LastLineFull, _) | (_, false) => (0.0, true),
+                (TextJustify::LastLineLeft, true) => (0.0, false),
+                (TextJustify::LastLineRight, true) => (1.0, false),
+                (TextJustify::LastLineCenter, true) => (0.5, false),
+            };
+            // Right-to-left runs are reversed from left to right
+            let order = visual_order(
+                &line_letters
+                    .iter()
+                    .map(|letter| letter.level)
+                    .collect::<Vec<_>>(),
+            );
+            let chars = &value[line_index];
+            // Full justification spreads the free space between words
+            let spaces = order
+                .iter()
+                .rev()
+                .skip_while(|index| chars[**index] == ' ')
+                .filter(|index| chars[**index] == ' ')
+                .count();
+            let word_spacing = if full && text_box.is_some() && spaces > 0 {
+                ((box_width - width) / spaces as f32).max(0.0)
+            } else {
+                0.0
+            };
+            let last_word_end = order
+                .iter()
+                .rposition(|index| chars[*index] != ' ')
+                .unwrap_or(0);
+            let start = box_x + (box_width - width) * align_factor;
+            let mut adv = start;
+            let mut offsets = vec![0.0; order.len()];
+            let mut ranks = vec![0; order.len()];
+            for (rank, index) in order.iter().enumerate() {
+                offsets[*index] = adv;
+                ranks[*index] = rank;
+                adv += line_letters[*index].advance;
+                if chars[*index] == ' ' && rank < last_word_end {
+                    adv += word_spacing;
                 }
+            }
+            layouts.push(LineLayout {
+                start,
+                width,
+                align_factor,
+                offsets,
+                advances,
+                ranks,
+                baseline: start_shift_y + line_y,
+            });
+            line_y += line_height(line);
+        }
+        let groups = anchor_groups(&layouts, &value, self.text.options.grouping);
+
+        // styles
+        let Styles {
+            fill,
+            fill_opacity,
+            stroke,
+            stroke_opacity,
+            stroke_width,
+        } = self.styles;
+        let value_of = |value| Animated {
+            animated: false,
+            keyframes: vec![self.keyframe.alter_value(value, value)],
+        };
+        let fill_layer = ShapeLayer {
+            name: None,
+            hidden: false,
+            shape: Shape::Fill(Fill {
+                opacity: value_of(fill_opacity),
+                color: Animated {
+                    animated: false,
+                    keyframes: vec![self.keyframe.alter_value(fill, fill)],
+                },
+                fill_rule: FillRule::NonZero,
+            }),
+        };
+        // Animators can stroke text without a stroke of its own
+        let stroked = stroke_width > 0.0
+            || self.text_ranges.iter().any(|range| {
+                range
+                    .style
+                    .as_ref()
+                    .is_some_and(|style| style.stroke_width.is_some())
+            });
+        let stroke_layer = stroked.then(|| ShapeLayer {
+            name: None,
+            hidden: false,
+            shape: Shape::Stroke(Stroke::new(
+                Animated {
+                    animated: false,
+                    keyframes: vec![self.keyframe.alter_value(stroke, stroke)],
+                },
+                value_of(stroke_width),
+                value_of(stroke_opacity),
+                LineCap::Butt,
+                LineJoin::Miter,
+            )),
+        });
 
-                let mut glyphs = all_beziers
-                    .into_iter()
-                    .map(|data| {
-                        let GlyphData {
-                            c,
-                            beziers,
-                            offset_x,
-                        } = data;
-
-                        let mut transform = Transform::default();
-                        transform.position = Some(Animated {
-                            animated: false,
-                            keyframes: vec![KeyFrame::from_value(Vector2D::new(offset_x, 0.0))],
-                        });
-                        let text_range = if self.text_ranges.is_empty() {
-                            None
-                        } else {
-                            Some(TextRangeInfo {
-                                value: value.clone(),
-                                index: (line_index, char_index),
-                                ranges: self.text_ranges.clone(),
-                            })
-                        };
-                        char_index += 1;
-                        ShapeLayer {
-                            name: Some(format!("{}", c)),
-                            hidden: false,
-                            shape: Shape::Group {
-                                shapes: vec![
-                                    ShapeLayer {
-                                        name: None,
-                                        hidden: false,
-                                        shape: Shape::Path {
-                                            d: Animated {
-                                                animated: false,
-                                                keyframes: vec![self
-                                                    .keyframe
-                                                    .alter_value(beziers.clone(), beziers)],
-                                            },
-                                            text_range,
-                                        },
-                                    },
-                                    fill_layer.clone(),
-                                    ShapeLayer {
-                                        name: None,
-                                        hidden: false,
-                                        shape: Shape::Transform(transform),
-                                    },
-                                ],
+        for (line_index, layout) in layouts.iter().enumerate() {
+            let path_transforms = self.path.map(|path| path.transforms(layout));
+            let mut glyphs = lines[line_index]
+                .letters
+                .iter()
+                .enumerate()
+                .map(|(char_index, letter)| {
+                    let offset_x = layout.offsets[char_index];
+                    let transform = match path_transforms.as_ref() {
+                        Some(transforms) => transforms[char_index].clone(),
+                        None => {
+                            let mut transform = Transform::default();
+                            transform.position = Some(Animated {
+                                animated: false,
+                                keyframes: vec![KeyFrame::from_value(Vector3D::new(
+                                    offset_x, 0.0, 0.0,
+                                ))],
+                            });
+                            transform
+                        }
+                    };
+                    let text_range = if self.text_ranges.is_empty() {
+                        None
+                    } else {
+                        let (start, end) = groups[line_index][char_index];
+                        Some(TextRangeInfo {
+                            value: value.clone(),
+                            index: (line_index, char_index),
+                            ranges: ranges.clone(),
+                            units: range_units.clone(),
+                            center: (start + end) / 2.0 - offset_x,
+                            group_size: Vector2D::new(end - start, doc.size),
+                            alignment: self.text.options.alignment.clone(),
+                        })
+                    };
+                    let beziers = letter.beziers.clone();
+                    let path = ShapeLayer {
+                        name: None,
+                        hidden: false,
+                        shape: Shape::Path {
+                            d: Animated {
+                                animated: false,
+                                keyframes: vec![self
+                                    .keyframe
+                                    .alter_value(beziers.clone(), beziers)],
                             },
+                            text_range,
+                        },
+                    };
+                    let transform = ShapeLayer {
+                        name: None,
+                        hidden: false,
+                        shape: Shape::Transform(transform),
+                    };
+                    let shapes = match stroke_layer.as_ref() {
+                        None => vec![path, fill_layer.clone(), transform],
+                        // The fill and the stroke are separate groups so
+                        // that either of them can be drawn on top
+                        Some(stroke_layer) => {
+                            let fill =
+                                group(vec![path.clone(), fill_layer.clone(), transform.clone()]);
+                            let stroke = group(vec![path, stroke_layer.clone(), transform]);
+                            if doc.stroke_above_fill {
+                                vec![stroke, fill]
+                            } else {
+                                vec![fill, stroke]
+                            }
                         }
-                    })
-                    .collect::<Vec<_>>();
+                    };
+                    ShapeLayer {
+                        name: Some(letter.value.clone()),
+                        hidden: false,
+                        shape: Shape::Group { shapes },
+                    }
+                })
+                .collect::<Vec<_>>();
 
-                let shift = Vector2D::new(0.0, start_shift_y + line_y);
-                let transform_position = self.keyframe.alter_value(shift, shift);
-                let mut transform = Transform::default();
-                transform.position = Some(Animated {
-                    animated: false,
-                    keyframes: vec![transform_position],
-                });
+            // Glyphs along a path are moved to their line on their own
+            let shift = match self.path {
+                Some(_) => Vector3D::zero(),
+                None => Vector3D::new(0.0, layout.baseline, 0.0),
+            };
+            let transform_position = self.keyframe.alter_value(shift, shift);
+            let mut transform = Transform::default();
+            transform.position = Some(Animated {
+                animated: false,
+                keyframes: vec![transform_position],
+            });
 
-                glyphs.push(ShapeLayer {
-                    name: None,
-                    hidden: false,
-                    shape: Shape::Transform(transform),
-                });
-                let line_values = line
-                    .spans()
-                    .iter()
-                    .map(|span| span.metrics.value().to_string())
-                    .collect::<Vec<_>>();
-                let line_value = line_values.join("");
-                result.push(ShapeLayer {
-                    name: Some(line_value),
-                    hidden: false,
-                    shape: Shape::Group { shapes: glyphs },
-                });
-            }
-            line_y += line.height();
+            glyphs.push(ShapeLayer {
+                name: None,
+                hidden: false,
+                shape: Shape::Transform(transform),
+            });
+            let line_value = lines[line_index]
+                .letters
+                .iter()
+                .map(|letter| letter.value.as_str())
+                .collect::<String>();
+            result.push(ShapeLayer {
+                name: Some(line_value),
+                hidden: false,
+                shape: Shape::Group { shapes: glyphs },
+            });
         }
         Ok(ShapeLayer {
             name: None,
@@ -320,3 +619,332 @@ impl<'a> TextDocumentParser<'a> {
         })
     }
 }
+
+impl Glyphs<'_> {
+    /// Glyphs of a paragraph of `text`, `small` telling which of its
+    /// characters are small caps. Characters missing from the font are added
+    /// to `missing`, with the family of the font drawing them instead
+    fn letters(
+        &self,
+        text: &str,
+        small: &[bool],
+        doc: &TextDocument,
+        missing: &mut Vec<(char, Option<String>)>,
+    ) -> Vec<Letter> {
+        let (chars, fonts) = match self {
+            Glyphs::Embedded(chars, fonts) => (chars, fonts),
+            Glyphs::Installed(fonts) => return fonts.letters(text, small, doc, missing, false),
+        };
+        let tracking = doc.tracking / 1000.0 * doc.size;
+        // Embedded characters are laid out one by one like lottie-web
+        let mut letters = vec![];
+        for (c, small) in text.chars().zip(small) {
+            let value = c.to_string();
+            let data = chars.iter().find(|data| data.character == value);
+            // Missing characters are drawn with an installed font, or else
+            // left out like the empty character of lottie-web. Spaces may
+            // have no embedded character
+            if data.is_none() && !c.is_whitespace() {
+                if let Some(fonts) = fonts {
+                    letters.extend(fonts.letters(&value, &[*small], doc, missing, true));
+                    continue;
+                }
+                missing.push((c, None));
+            }
+            let scale = doc.size / 100.0 * caps_scale(*small);
+            letters.push(Letter {
+                c,
+                value,
+                small: *small,
+                advance: data.map(|data| data.width).unwrap_or(0.0) * scale + tracking,
+                level: 0,
+                beziers: data
+                    .map(|data| data.beziers())
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|bezier| scaled(bezier, scale))
+                    .collect(),
+            });
+        }
+        letters
+    }
+
+    /// Height of a line of `letters` when the document does not set it
+    fn line_height(
+        &self,
+        letters: &[Letter],
+        doc: &TextDocument,
+        styles: &Styles,
+    ) -> Result<f32, Error> {
+        let font = match self {
+            Glyphs::Installed(fonts) => fonts.fonts[0],
+            Glyphs::Embedded(..) => return Ok(doc.size * AUTO_LEADING),
+        };
+        let span = |run: &[Letter]| -> Result<_, Error> {
+            let text = run
+                .iter()
+                .map(|letter| letter.value.as_str())
+                .collect::<String>();
+            let small = run.first().is_some_and(|letter| letter.small);
+            Ok(Span {
+                font_key: font.key(),
+                letter_spacing: 0.0,
+                line_height: None,
+                size: if small {
+                    doc.size * SMALL_CAPS_SCALE
+                } else {
+                    doc.size
+                },
+                broke_from_prev: false,
+                metrics: font.measure(&text)?,
+                swallow_leading_space: false,
+                additional: styles.clone(),
+            })
+        };
+        // Small caps are smaller spans of the line
+        let mut runs = letters.chunk_by(|a, b| a.small == b.small);
+        let mut line = Line::new(span(runs.next().unwrap_or_default())?);
+        for run in runs {
+            line.spans.push(span(run)?);
+        }
+        Ok(line.height())
+    }
+}
+
+impl InstalledFonts<'_> {
+    /// Glyphs of a paragraph of `text` like `Glyphs::letters`. Each run of
+    /// the text is shaped with the fonts for its script, clusters none of
+    /// them have glyphs for being left out. Characters drawn with a fallback
+    /// of the chain are added to `missing`, or all of them when `instead` of
+    /// embedded characters
+    fn letters(
+        &self,
+        text: &str,
+        small: &[bool],
+        doc: &TextDocument,
+        missing: &mut Vec<(char, Option<String>)>,
+        instead: bool,
+    ) -> Vec<Letter> {
+        let tracking = doc.tracking / 1000.0 * doc.size;
+        let chain = |script: Script| {
+            self.chains
+                .get(&script)
+                .or_else(|| self.chains.get(&Script::Common))
+                .cloned()
+                .unwrap_or_default()
+        };
+        shape_paragraph(&self.faces, text, &chain)
+            .into_iter()
+            .map(|cluster| {
+                let face = &self.faces[cluster.face];
+                let value = &text[cluster.range.clone()];
+                let c = value.chars().next().unwrap_or(' ');
+                let small = small[text[..cluster.range.start].chars().count()];
+                // Shaping is done at the size of capitals, small caps being
+                // scaled down afterwards
+                let scale = doc.size / face.units_per_em() as f32 * caps_scale(small);
+                let found = c.is_whitespace() || cluster.glyphs.iter().all(|glyph| glyph.id != 0);
+                let fallback = chain(cluster.script).first() != Some(&cluster.face);
+                if !found {
+                    missing.push((c, None));
+                } else if (instead || fallback) && !c.is_whitespace() {
+                    missing.push((c, Some(self.family(cluster.face))));
+                }
+                let mut beziers = vec![];
+                let mut pen = 0.0;
+                for glyph in cluster.glyphs.iter().filter(|_| found) {
+                    let origin = Vector2D::new(pen + glyph.offset.x, -glyph.offset.y) * scale;
+                    beziers.extend(glyph_beziers(face, glyph.id, scale, origin));
+                    pen += glyph.advance;
+                }
+                Letter {
+                    c,
+                    value: value.to_string(),
+                    small,
+                    advance: pen * scale + tracking,
+                    level: cluster.level,
+                    beziers,
+                }
+            })
+            .collect()
+    }
+
+    /// Family of the font at `index`
+    fn family(&self, index: usize) -> String {
+        self.fonts[index].key().family
+    }
+}
+
+/// Index of the face of `font` in its file `data`, which is 0 unless the file
+/// is a font collection. Faces of a collection are told apart by their family,
+/// weight and style, `None` meaning none of them matches the font
+fn collection_index(font: &fontkit::Font, data: &[u8]) -> Option<u32> {
+    let count = ttf_parser::fonts_in_collection(data).unwrap_or(1);
+    if count <= 1 {
+        return Some(0);
+    }
+    let key = font.key();
+    (0..count).find(|&index| {
+        let Ok(face) = ttf_parser::Face::parse(data, index) else {
+            return false;
+        };
+        let family = face
+            .names()
+            .into_iter()
+            .filter(|name| {
+                name.name_id == ttf_parser::name_id::FAMILY
+                    || name.name_id == ttf_parser::name_id::TYPOGRAPHIC_FAMILY
+            })
+            .filter_map(|name| name.to_string())
+            .any(|family| family == key.family);
+        family
+            && key
+                .weight
+                .map_or(true, |weight| face.weight().to_number() as u32 == weight)
+            && key.italic.map_or(true, |italic| face.is_italic() == italic)
+    })
+}
+
+fn caps_scale(small: bool) -> f32 {
+    if small {
+        SMALL_CAPS_SCALE
+    } else {
+        1.0
+    }
+}
+
+/// Extent of the anchor group of each glyph of each line, from the leftmost
+/// to the rightmost of its visible glyphs
+fn anchor_groups(
+    layouts: &[LineLayout],
+    value: &[Vec<char>],
+    grouping: TextGrouping,
+) -> Vec<Vec<(f32, f32)>> {
+    // Groups only made of spaces span all of them
+    let extent = |layout: &LineLayout, chars: &[char], range: Range<usize>| {
+        let bounds = |visible: bool| {
+            range
+                .clone()
+                .filter(|index| !visible || chars[*index] != ' ')
+                .map(|index| {
+                    let start = layout.offsets[index];
+                    (start, start + layout.advances[index])
+                })
+                .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+        };
+        bounds(true)
+            .or_else(|| bounds(false))
+            .unwrap_or((layout.start, layout.start))
+    };
+    let all = layouts
+        .iter()
+        .zip(value)
+        .map(|(layout, chars)| extent(layout, chars, 0..chars.len()))
+        .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+        .unwrap_or_default();
+    layouts
+        .iter()
+        .zip(value)
+        .map(|(layout, chars)| {
+            let count = chars.len();
+            match grouping {
+                TextGrouping::Characters => (0..count)
+                    .map(|index| extent(layout, chars, index..index + 1))
+                    .collect(),
+                // Spaces belong to the word before them
+                TextGrouping::Words => {
+                    let mut groups = vec![];
+                    let mut start = 0;
+                    for index in 1..=count {
+                        if index == count || (chars[index] != ' ' && chars[index - 1] == ' ') {
+                            let group = extent(layout, chars, start..index);
+                            groups.extend(std::iter::repeat(group).take(index - start));
+                            start = index;
+                        }
+                    }
+                    groups
+                }
+                TextGrouping::Lines => vec![extent(layout, chars, 0..count); count],
+                TextGrouping::All => vec![all; count],
+            }
+        })
+        .collect()
+}
+
+/// `bezier` scaled by `scale` around the origin
+fn scaled(bezier: &Bezier, scale: f32) -> Bezier {
+    let scale_all = |points: &Vec<Vector2D>| points.iter().map(|p| *p * scale).collect();
+    Bezier {
+        closed: bezier.closed,
+        verticies: scale_all(&bezier.verticies),
+        in_tangent: scale_all(&bezier.in_tangent),
+        out_tangent: scale_all(&bezier.out_tangent),
+    }
+}
+
+fn group(shapes: Vec<ShapeLayer>) -> ShapeLayer {
+    ShapeLayer {
+        name: None,
+        hidden: false,
+        shape: Shape::Group { shapes },
+    }
+}
+
+/// A shaped cluster of a paragraph
+struct Letter {
+    /// First character of the cluster
+    c: char,
+    value: String,
+    small: bool,
+    /// Advance of the cluster, tracking included
+    advance: f32,
+    /// Bidi embedding level of the cluster
+    level: u8,
+    /// Outline of the cluster from its start on the baseline
+    beziers: Vec<Bezier>,
+}
+
+/// Apply the capitalization of a text document to `text`, also returning
+/// which characters are small caps
+fn apply_caps(text: &str, caps: TextCaps) -> (String, Vec<bool>) {
+    match caps {
+        TextCaps::Regular => (text.to_string(), vec![false; text.chars().count()]),
+        TextCaps::AllCaps => {
+            let text = text.to_uppercase();
+            let small = vec![false; text.chars().count()];
+            (text, small)
+        }
+        TextCaps::SmallCaps => text
+            .chars()
+            .flat_map(|c| c.to_uppercase().map(move |upper| (upper, c.is_lowercase())))
+            .unzip(),
+    }
+}
+
+/// Break the `letters` of a paragraph into lines no wider than `width` like
+/// lottie-web: lines break at their last space, which is dropped, or before
+/// the first overflowing character of a word wider than the box
+fn wrap(letters: Vec<Letter>, width: f32) -> Vec<Vec<Letter>> {
+    let mut lines = vec![];
+    let mut line: Vec<Letter> = vec![];
+    let mut line_width = 0.0;
+    for letter in letters {
+        if letter.c != ' ' && !line.is_empty() && line_width + letter.advance > width {
+            let rest = match line.iter().rposition(|letter| letter.c == ' ') {
+                Some(space) => {
+                    let rest = line.split_off(space + 1);
+                    line.pop();
+                    rest
+                }
+                None => vec![],
+            };
+            lines.push(line);
+            line_width = rest.iter().map(|letter| letter.advance).sum();
+            line = rest;
+        }
+        line_width += letter.advance;
+        line.push(letter);
+    }
+    lines.push(line);
+    lines
+}
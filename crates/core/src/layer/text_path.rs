@@ -0,0 +1,242 @@
+use lyon_path::iterator::PathIterator;
+use lyon_path::PathEvent;
+
+use crate::layer::shape::PathFactory;
+use crate::layer::text::LineLayout;
+use crate::model::{
+    Animated, Bezier, KeyFrame, Mask, TextFollowPath, Transform, Vector2D, Vector3D,
+};
+use crate::Lerp;
+
+const PATH_TOLERANCE: f32 = 0.1;
+
+/// A path flattened into a polyline and measured by length, to lay glyphs out
+/// along it
+#[derive(Debug, Clone)]
+pub struct PathMeasure {
+    points: Vec<Vector2D>,
+    /// Distance of each point from the start of the path
+    distances: Vec<f32>,
+    closed: bool,
+}
+
+impl PathMeasure {
+    /// Measure the first subpath of `beziers`, from its end if `reverse`
+    pub fn new(beziers: &Vec<Bezier>, reverse: bool) -> Self {
+        let mut points = vec![];
+        let mut closed = false;
+        for event in beziers.path(0.0).iter().flattened(PATH_TOLERANCE) {
+            match event {
+                PathEvent::Begin { at } => points.push(at.to_vector()),
+                PathEvent::Line { to, .. } => points.push(to.to_vector()),
+                PathEvent::End { first, close, .. } => {
+                    if close {
+                        points.push(first.to_vector());
+                        closed = true;
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+        if reverse {
+            points.reverse();
+        }
+        let mut distances = Vec::with_capacity(points.len());
+        let mut distance = 0.0;
+        for (index, point) in points.iter().enumerate() {
+            if index > 0 {
+                distance += (*point - points[index - 1]).length();
+            }
+            distances.push(distance);
+        }
+        PathMeasure {
+            points,
+            distances,
+            closed,
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// Point at `distance` along the path, and the angle of the path there in
+    /// degrees. Closed paths wrap around while open paths extend their ends in
+    /// straight lines
+    pub fn sample(&self, distance: f32) -> (Vector2D, f32) {
+        if self.points.len() < 2 {
+            return (self.points.first().copied().unwrap_or_default(), 0.0);
+        }
+        let length = self.length();
+        let distance = if self.closed && length > 0.0 {
+            distance.rem_euclid(length)
+        } else {
+            distance
+        };
+        let index = self
+            .distances
+            .partition_point(|d| *d <= distance)
+            .clamp(1, self.points.len() - 1);
+        let (from, to) = (self.points[index - 1], self.points[index]);
+        let start = self.distances[index - 1];
+        let segment = self.distances[index] - start;
+        let ratio = if segment > 0.0 {
+            (distance - start) / segment
+        } else {
+            0.0
+        };
+        let angle = (to - from).angle_from_x_axis().to_degrees();
+        (from + (to - from) * ratio, angle)
+    }
+}
+
+/// Text following a mask of its layer
+pub(crate) struct PathLayout<'a> {
+    follow_path: &'a TextFollowPath,
+    mask: &'a Mask,
+    start_frame: f32,
+    end_frame: f32,
+}
+
+impl<'a> PathLayout<'a> {
+    /// `None` when the text does not follow any of the `masks` of its layer
+    pub(crate) fn new(
+        follow_path: &'a TextFollowPath,
+        masks: &'a [Mask],
+        start_frame: f32,
+        end_frame: f32,
+    ) -> Option<Self> {
+        let mask = masks.get(follow_path.mask?)?;
+        Some(PathLayout {
+            follow_path,
+            mask,
+            start_frame,
+            end_frame,
+        })
+    }
+
+    fn is_animated(&self) -> bool {
+        let follow_path = self.follow_path;
+        self.mask.points.is_animated()
+            || [
+                &follow_path.first_margin,
+                &follow_path.last_margin,
+                &follow_path.perpendicular,
+                &follow_path.force_alignment,
+                &follow_path.reverse,
+            ]
+            .iter()
+            .any(|animated| animated.as_ref().is_some_and(|a| a.is_animated()))
+    }
+
+    /// Transforms of the glyphs of `line` centering them on the path, their
+    /// baseline along it. Like `MaskStroke`, animated paths and margins are
+    /// resampled linearly on every frame
+    pub(crate) fn transforms(&self, line: &LineLayout) -> Vec<Transform> {
+        let frames = if self.is_animated() {
+            let start = self.start_frame.floor() as i32;
+            let end = (self.end_frame.ceil() as i32).max(start + 1);
+            (start..=end).map(|frame| frame as f32).collect()
+        } else {
+            vec![0.0]
+        };
+        let placements = frames
+            .iter()
+            .map(|frame| self.place(line, *frame))
+            .collect::<Vec<_>>();
+        (0..line.offsets.len())
+            .map(|index| {
+                let mut previous = None;
+                let (positions, rotations): (Vec<_>, Vec<_>) = placements
+                    .iter()
+                    .map(|glyphs| {
+                        let (point, angle) = glyphs[index];
+                        // Unwrap angles so that they turn the short way
+                        let angle = match previous {
+                            Some(previous) => {
+                                previous + (angle - previous + 180.0).rem_euclid(360.0) - 180.0
+                            }
+                            None => angle,
+                        };
+                        previous = Some(angle);
+                        (Vector3D::new(point.x, point.y, 0.0), angle)
+                    })
+                    .unzip();
+                let mut transform = Transform::default();
+                transform.anchor = Some(Animated::from_value(Vector3D::new(
+                    line.advances[index] / 2.0,
+                    -line.baseline,
+                    0.0,
+                )));
+                transform.position = Some(sampled(&frames, positions));
+                transform.rotation = sampled(&frames, rotations);
+                transform
+            })
+            .collect()
+    }
+
+    /// Center of each glyph of `line` on the path at `frame`, with the angle
+    /// to rotate it by
+    fn place(&self, line: &LineLayout, frame: f32) -> Vec<(Vector2D, f32)> {
+        let value = |animated: &Option<Animated<f32>>, default: f32| {
+            animated
+                .as_ref()
+                .map(|animated| animated.value(frame))
+                .unwrap_or(default)
+        };
+        let follow_path = self.follow_path;
+        let reverse = value(&follow_path.reverse, 0.0) != 0.0;
+        let measure = PathMeasure::new(&self.mask.points.value(frame), reverse);
+        let first_margin = value(&follow_path.first_margin, 0.0);
+        let last_margin = value(&follow_path.last_margin, 0.0);
+        let free = measure.length() - first_margin - last_margin - line.width;
+        let perpendicular = value(&follow_path.perpendicular, 1.0) != 0.0;
+        // Forced alignment spreads the glyphs between the margins
+        let count = line.offsets.len();
+        let (start, spacing) = if value(&follow_path.force_alignment, 0.0) != 0.0 {
+            let spacing = if count > 1 {
+                free / (count - 1) as f32
+            } else {
+                0.0
+            };
+            (first_margin, spacing)
+        } else {
+            (first_margin + free * line.align_factor, 0.0)
+        };
+        line.offsets
+            .iter()
+            .zip(&line.advances)
+            .enumerate()
+            .map(|(index, (offset, advance))| {
+                let distance = start + offset - line.start
+                    + line.ranks[index] as f32 * spacing
+                    + advance / 2.0;
+                let (point, angle) = measure.sample(distance);
+                (point, if perpendicular { angle } else { 0.0 })
+            })
+            .collect()
+    }
+}
+
+/// Values sampled at `frames`, interpolated linearly in between
+fn sampled<T: Clone + Lerp<Target = T>>(frames: &[f32], values: Vec<T>) -> Animated<T> {
+    if values.len() < 2 {
+        return Animated::from_value(values[0].clone());
+    }
+    Animated {
+        animated: true,
+        keyframes: frames
+            .windows(2)
+            .zip(values.windows(2))
+            .map(|(frames, values)| KeyFrame {
+                start_value: values[0].clone(),
+                end_value: values[1].clone(),
+                start_frame: frames[0],
+                end_frame: frames[1],
+                easing_out: None,
+                easing_in: None,
+            })
+            .collect(),
+    }
+}
@@ -1,4 +1,4 @@
-use crate::model::{Bezier, GradientColor, Rgb, Rgba, Vector2D};
+use crate::model::{Bezier, GradientColor, Rgb, Rgba, Vector2D, Vector3D};
 
 pub trait Lerp {
     type Target;
@@ -13,6 +13,14 @@ impl Lerp for Vector2D {
     }
 }
 
+impl Lerp for Vector3D {
+    type Target = Vector3D;
+
+    fn lerp(&self, other: &Self, t: f32) -> Self::Target {
+        (*self - *other) * t + *other
+    }
+}
+
 impl Lerp for f32 {
     type Target = f32;
 
@@ -52,10 +60,60 @@ impl Lerp for Rgb {
     }
 }
 
+impl Lerp for Rgba {
+    type Target = Rgba;
+
+    fn lerp(&self, other: &Self, t: f32) -> Self::Target {
+        let channel = |a: u8, b: u8| (b as f32 + (a as f32 - b as f32) * t).round() as u8;
+        Rgba::new_u8(
+            channel(self.r, other.r),
+            channel(self.g, other.g),
+            channel(self.b, other.b),
+            channel(self.a, other.a),
+        )
+    }
+}
+
 impl Lerp for Vec<GradientColor> {
     type Target = Vec<GradientColor>;
 
     fn lerp(&self, other: &Self, t: f32) -> Self::Target {
+        if self.len() != other.len() {
+            // Resample both lists on the union of their offsets so every stop
+            // has a counterpart. Offsets are only merged with the ones of the
+            // other list, so both sides of a hard stop are kept
+            let near = |a: f32, b: f32| (a - b).abs() < 1e-4;
+            let mut offsets = self.iter().map(|stop| stop.offset).collect::<Vec<_>>();
+            for (index, stop) in other.iter().enumerate() {
+                let repeats = other[..index]
+                    .iter()
+                    .filter(|s| near(s.offset, stop.offset))
+                    .count();
+                if offsets.iter().filter(|o| near(**o, stop.offset)).count() <= repeats {
+                    offsets.push(stop.offset);
+                }
+            }
+            offsets.sort_by(|a, b| a.total_cmp(b));
+            let resample = |stops: &[GradientColor]| {
+                let mut result: Vec<GradientColor> = vec![];
+                for &offset in &offsets {
+                    // The n-th stop at a repeated offset is the n-th stop of
+                    // `stops` there, if it has any
+                    let repeats = result.iter().filter(|s| near(s.offset, offset)).count();
+                    let at = stops
+                        .iter()
+                        .filter(|s| near(s.offset, offset))
+                        .collect::<Vec<_>>();
+                    let color = match at.get(repeats).or(at.last()) {
+                        Some(stop) => stop.color,
+                        None => GradientColor::sample(stops, offset),
+                    };
+                    result.push(GradientColor { offset, color });
+                }
+                result
+            };
+            return resample(self).lerp(&resample(other), t);
+        }
         self.iter()
             .zip(other)
             .map(|(x, y)| {
@@ -1,8 +1,11 @@
 use std::io::Read;
 
 use crate::model::Model;
-pub use error::Error;
-use font::FontDB;
+pub use error::{Diagnostic, Error};
+pub use font::{
+    FontDB, FontFallbacks, FontMatch, FontOptions, FontProvider, FontResolution,
+    NetworkFontProvider, OfflineFontProvider,
+};
 pub use fontkit::tiny_skia_path;
 use fontkit::FontKit;
 pub use lerp::*;
@@ -18,12 +21,18 @@ mod renderer;
 mod timeline;
 
 pub mod prelude {
+    pub use crate::layer::animator::*;
+    pub use crate::layer::camera::*;
+    pub use crate::layer::effect::*;
     pub use crate::layer::frame::*;
     pub use crate::layer::hierarchy::*;
+    pub use crate::layer::selector::*;
     pub use crate::layer::shape::{
         AnyFill, AnyStroke, PathFactory, StyledShape, StyledShapeIterator, TrimInfo,
     };
+    pub use crate::layer::shaping::*;
     pub use crate::layer::staged::{RenderableContent, StagedLayer};
+    pub use crate::layer::text_path::PathMeasure;
     pub use crate::model::*;
     pub use crate::timeline::{Id, TimelineAction};
 }
@@ -38,10 +47,19 @@ pub struct Lottie {
 impl Lottie {
     /// Initiate a new `Lottie` by providing a raw `Model`, a `FontKit` for font
     /// management, and a root path.Root path will be used to resolve relative
-    /// paths of media files in this lottie model
+    /// paths of media files in this lottie model. The bundled Fira Mono is
+    /// added to `fontkit` as the last font fallback
     pub fn new(model: Model, fontkit: FontKit, root_path: &str) -> Result<Self, Error> {
-        let mut fontdb = FontDB::new(fontkit);
+        Lottie::with_fontdb(model, FontDB::with_bundled_font(fontkit)?, root_path)
+    }
+
+    /// Initiate a new `Lottie` with a `FontDB`, e.g. one with its own font
+    /// fallbacks
+    pub fn with_fontdb(model: Model, mut fontdb: FontDB, root_path: &str) -> Result<Self, Error> {
         fontdb.load_fonts_from_model(&model)?;
+        for resolution in fontdb.report(&model) {
+            log::debug!("Font {}", resolution);
+        }
 
         let timeline = Timeline::new(&model, &fontdb, root_path)?;
         Ok(Lottie {
@@ -52,15 +70,23 @@ impl Lottie {
         })
     }
 
+    /// Read a `Lottie` with the fonts of the system, downloading the fonts
+    /// of the animation from their url
     #[cfg(not(all(target_os = "unknown", target_arch = "wasm32")))]
     pub fn from_reader<R: Read>(r: R, root_path: &str) -> Result<Self, Error> {
-        let mut fontkit = FontKit::new();
-        let path = dirs::font_dir().unwrap();
-        fontkit.search_fonts_from_path(path)?;
-        #[cfg(target_os = "macos")]
-        fontkit.search_fonts_from_path(std::path::PathBuf::from("/System/Library/Fonts"))?;
+        Lottie::from_reader_with_fonts(r, root_path, FontOptions::system())
+    }
+
+    /// Read a `Lottie` with the fonts of `options`, e.g. with
+    /// `FontOptions::offline` for renderers without network access
+    pub fn from_reader_with_fonts<R: Read>(
+        r: R,
+        root_path: &str,
+        options: FontOptions,
+    ) -> Result<Self, Error> {
+        let fontdb = FontDB::with_options(options)?;
         let model = Model::from_reader(r)?;
-        Ok(Lottie::new(model, fontkit, root_path)?)
+        Lottie::with_fontdb(model, fontdb, root_path)
     }
 
     pub fn timeline(&self) -> &Timeline {
@@ -70,4 +96,15 @@ impl Lottie {
     pub fn fontdb(&self) -> &FontDB {
         &self.fontdb
     }
+
+    /// Installed font used for each font of the animation
+    pub fn font_report(&self) -> Vec<FontResolution> {
+        self.fontdb.report(&self.model)
+    }
+
+    /// Problems found while loading this animation, which make the rendering
+    /// differ from After Effects
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.timeline.diagnostics()
+    }
 }
@@ -3,14 +3,21 @@ pub use euclid::rect;
 use glam::{Mat4, Vec3};
 use serde::{Deserialize, Serialize};
 pub use serde_json::Error;
+use std::sync::Arc;
+
+use crate::layer::selector::TextUnits;
 pub type Vector2D = euclid::default::Vector2D<f32>;
+pub type Vector3D = euclid::default::Vector3D<f32>;
 
 mod animated;
+mod blend;
 mod color;
+mod effect;
 mod helpers;
 
 pub use animated::*;
 pub use color::*;
+pub use effect::*;
 use helpers::*;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -34,6 +41,13 @@ pub struct Model {
     pub assets: Vec<Asset>,
     #[serde(default)]
     pub fonts: FontList,
+    /// Glyphs of fonts embedded in the animation
+    #[serde(default)]
+    pub chars: Vec<FontChar>,
+    /// Motion blur settings of the composition, used by layers with motion
+    /// blur enabled
+    #[serde(rename = "mb", default)]
+    pub motion_blur: Option<MotionBlur>,
 }
 
 impl Model {
@@ -48,6 +62,68 @@ impl Model {
     pub fn font(&self, name: &str) -> Option<&Font> {
         self.fonts.list.iter().find(|f| f.name == name)
     }
+
+    /// Glyphs of `font` embedded in the animation
+    pub fn font_chars(&self, font: &Font) -> Vec<&FontChar> {
+        self.chars
+            .iter()
+            .filter(|c| c.family == font.family && c.style == font.style)
+            .collect()
+    }
+}
+
+/// Shutter of the composition, sampled multiple times per frame to blur the
+/// motion of layers
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MotionBlur {
+    /// Duration of the shutter opening in degrees, 360 being a whole frame
+    #[serde(rename = "sa", default = "default_shutter_angle")]
+    pub shutter_angle: f32,
+    /// Offset of the shutter opening from the frame in degrees
+    #[serde(rename = "sp", default = "default_shutter_phase")]
+    pub shutter_phase: f32,
+    #[serde(rename = "spf", default = "default_samples_per_frame")]
+    pub samples_per_frame: u32,
+    /// Maximum number of samples After Effects takes for 2D layers
+    #[serde(rename = "asl", default = "default_adaptive_sample_limit")]
+    pub adaptive_sample_limit: u32,
+}
+
+fn default_shutter_angle() -> f32 {
+    180.0
+}
+
+fn default_shutter_phase() -> f32 {
+    -90.0
+}
+
+fn default_samples_per_frame() -> u32 {
+    16
+}
+
+fn default_adaptive_sample_limit() -> u32 {
+    128
+}
+
+impl Default for MotionBlur {
+    fn default() -> Self {
+        MotionBlur {
+            shutter_angle: default_shutter_angle(),
+            shutter_phase: default_shutter_phase(),
+            samples_per_frame: default_samples_per_frame(),
+            adaptive_sample_limit: default_adaptive_sample_limit(),
+        }
+    }
+}
+
+impl MotionBlur {
+    /// Offset in frames of the `index`-th sample of a frame. Samples are
+    /// evenly spread over the shutter opening
+    pub fn sample_offset(&self, index: u32) -> f32 {
+        let samples = self.samples_per_frame.max(1) as f32;
+        let open = self.shutter_phase / 360.0;
+        open + self.shutter_angle / 360.0 * (index as f32 + 0.5) / samples
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -58,7 +134,7 @@ pub struct Layer {
         rename = "ddd",
         default
     )]
-    is_3d: bool,
+    pub is_3d: bool,
     #[serde(rename = "hd", default)]
     pub hidden: bool,
     #[serde(rename = "ind", default)]
@@ -88,12 +164,29 @@ pub struct Layer {
     pub content: LayerContent,
     #[serde(rename = "tt", default)]
     pub matte_mode: Option<MatteMode>,
+    /// Index of the layer used as track matte. When absent, the layer right
+    /// above this one is used
+    #[serde(rename = "tp", default)]
+    pub matte_parent: Option<u32>,
+    /// Whether this layer is used as a track matte by another layer
+    #[serde(
+        rename = "td",
+        deserialize_with = "bool_from_int",
+        serialize_with = "int_from_bool",
+        default
+    )]
+    pub is_matte_source: bool,
     #[serde(rename = "bm", default)]
     pub blend_mode: Option<BlendMode>,
     #[serde(default, rename = "hasMask")]
     pub has_mask: bool,
     #[serde(default, rename = "masksProperties")]
     pub masks_properties: Vec<Mask>,
+    #[serde(rename = "ef", default)]
+    pub effects: Vec<Effect>,
+    /// Whether the motion of this layer is blurred, see [`MotionBlur`]
+    #[serde(rename = "mb", default)]
+    pub motion_blur: bool,
 }
 
 impl Layer {
@@ -120,9 +213,13 @@ impl Layer {
             transform: None,
             content,
             matte_mode: None,
+            matte_parent: None,
+            is_matte_source: false,
             blend_mode: None,
             has_mask: false,
             masks_properties: vec![],
+            effects: vec![],
+            motion_blur: false,
         }
     }
 }
@@ -140,6 +237,7 @@ pub enum LayerContent {
     Shape(ShapeGroup),
     Text(TextAnimationData),
     Media(Media),
+    Camera(Camera),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -148,6 +246,16 @@ pub struct MediaRef {
     pub ref_id: String,
 }
 
+/// A camera layer. Its transform holds the position and the point of
+/// interest (`a`) of the camera
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Camera {
+    /// Distance from the camera to the plane where layers are seen at their
+    /// own size, which After Effects calls zoom
+    #[serde(rename = "pe")]
+    pub perspective: Animated<f32>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PreCompositionRef {
     #[serde(rename = "refId")]
@@ -162,14 +270,25 @@ pub struct PreCompositionRef {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Transform {
+    /// Anchor point, or the point of interest of a camera layer
     #[serde(rename = "a", default)]
-    pub anchor: Option<Animated<Vector2D>>,
+    pub anchor: Option<Animated<Vector3D>>,
+    /// Position, the z axis pointing away from the viewer
     #[serde(rename = "p", default)]
-    pub position: Option<Animated<Vector2D>>,
+    pub position: Option<Animated<Vector3D>>,
+    /// Scale of the x and y axes. Layers are flat, so the z scale is ignored
     #[serde(rename = "s", default = "default_vec2_100")]
     pub scale: Animated<Vector2D>,
-    #[serde(rename = "r", default)]
+    /// Rotation around the z axis, exported as `rz` by 3D layers
+    #[serde(rename = "r", alias = "rz", default)]
     pub rotation: Animated<f32>,
+    #[serde(rename = "rx", default)]
+    pub rotation_x: Animated<f32>,
+    #[serde(rename = "ry", default)]
+    pub rotation_y: Animated<f32>,
+    /// Orientation of a 3D layer, applied after its rotations
+    #[serde(rename = "or", default)]
+    pub orientation: Option<Animated<Vector3D>>,
     #[serde(skip)]
     pub auto_orient: bool,
     #[serde(rename = "o", default = "default_number_100")]
@@ -187,6 +306,9 @@ impl Default for Transform {
             position: Default::default(),
             scale: default_vec2_100(),
             rotation: Default::default(),
+            rotation_x: Default::default(),
+            rotation_y: Default::default(),
+            orientation: Default::default(),
             opacity: default_number_100(),
             skew: Default::default(),
             skew_axis: Default::default(),
@@ -213,11 +335,20 @@ impl Transform {
             .and_then(|a| Some(a.keyframes.last()?.end_frame))
             .unwrap_or(0.0);
         let scale_frames = self.scale.keyframes.last().unwrap().end_frame;
-        let rotation_frames = self.rotation.keyframes.last().unwrap().end_frame;
+        let rotation_frames = [&self.rotation, &self.rotation_x, &self.rotation_y]
+            .iter()
+            .map(|rotation| rotation.keyframes.last().unwrap().end_frame)
+            .fold(0.0, f32::max);
+        let orientation_frames = self
+            .orientation
+            .as_ref()
+            .and_then(|a| Some(a.keyframes.last()?.end_frame))
+            .unwrap_or(0.0);
         anchor_frames
             .max(pos_frames)
             .max(scale_frames)
             .max(rotation_frames)
+            .max(orientation_frames)
     }
 
     pub fn initial_value(&self) -> Mat4 {
@@ -237,6 +368,7 @@ impl Transform {
                     .find(|keyframe| frame >= keyframe.start_frame && frame < keyframe.end_frame)
                 {
                     angle = (keyframe.end_value - keyframe.start_value)
+                        .xy()
                         .angle_from_x_axis()
                         .to_degrees();
                 }
@@ -253,7 +385,16 @@ impl Transform {
             .map(|a| a.value(frame))
             .unwrap_or_default();
         let mut scale = self.scale.value(frame) / 100.0;
-        let rotation = self.rotation.value(frame) + angle;
+        let rotation = Vector3D::new(
+            self.rotation_x.value(frame),
+            self.rotation_y.value(frame),
+            self.rotation.value(frame) + angle,
+        );
+        let orientation = self
+            .orientation
+            .as_ref()
+            .map(|a| a.value(frame))
+            .unwrap_or_default();
         // Some lottie file has scale = 0, which is invalid
         if scale.x == 0.0 {
             scale.x = f32::EPSILON;
@@ -261,7 +402,7 @@ impl Transform {
         if scale.y == 0.0 {
             scale.y = f32::EPSILON;
         }
-        mat4(anchor, position, scale, rotation)
+        mat4(anchor, position, scale, rotation, orientation)
     }
 
     pub fn is_animated(&self) -> bool {
@@ -276,6 +417,13 @@ impl Transform {
                 .unwrap_or(false)
             || self.scale.is_animated()
             || self.rotation.is_animated()
+            || self.rotation_x.is_animated()
+            || self.rotation_y.is_animated()
+            || self
+                .orientation
+                .as_ref()
+                .map(|a| a.is_animated())
+                .unwrap_or(false)
     }
 }
 
@@ -306,22 +454,109 @@ pub struct FontList {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Font {
+    /// Ascent of the font in percents of its size
     #[serde(default)]
-    ascent: Option<f32>,
+    pub ascent: Option<f32>,
     #[serde(rename = "fFamily")]
     pub family: String,
     #[serde(rename = "fName")]
     pub name: String,
+    /// Style name of the font, e.g. `Bold Italic`
     #[serde(rename = "fStyle")]
-    style: String,
+    pub style: String,
     #[serde(rename = "fPath", default)]
     pub path: Option<String>,
     #[serde(rename = "fWeight")]
-    weight: Option<String>,
+    pub weight: Option<String>,
     #[serde(default)]
     pub origin: FontPathOrigin,
+    /// Generic family of the font, like `serif` or `monospace`
     #[serde(rename = "fClass", default)]
-    class: Option<String>,
+    pub class: Option<String>,
+}
+
+impl Font {
+    /// Weight of the font from 100 to 900 like in CSS, read from its weight
+    /// or else guessed from its style name
+    pub fn weight_value(&self) -> u32 {
+        let weight = self.weight.as_deref().unwrap_or_default().trim();
+        if let Ok(weight) = weight.parse::<f32>() {
+            return weight.clamp(1.0, 1000.0) as u32;
+        }
+        match weight.to_lowercase().as_str() {
+            "bold" => return 700,
+            "normal" => return 400,
+            _ => {}
+        }
+        let style = self.style.to_lowercase().replace(['-', ' ', '_'], "");
+        [
+            ("thin", 100),
+            ("hairline", 100),
+            ("extralight", 200),
+            ("ultralight", 200),
+            ("semibold", 600),
+            ("demibold", 600),
+            ("extrabold", 800),
+            ("ultrabold", 800),
+            ("black", 900),
+            ("heavy", 900),
+            ("light", 300),
+            ("medium", 500),
+            ("bold", 700),
+        ]
+        .iter()
+        .find(|(name, _)| style.contains(name))
+        .map(|(_, weight)| *weight)
+        .unwrap_or(400)
+    }
+
+    pub fn is_italic(&self) -> bool {
+        let style = self.style.to_lowercase();
+        style.contains("italic") || style.contains("oblique")
+    }
+}
+
+/// Outline of a character of a font, exported along with the animation so
+/// that the font does not need to be installed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FontChar {
+    #[serde(rename = "ch")]
+    pub character: String,
+    #[serde(rename = "fFamily")]
+    pub family: String,
+    pub style: String,
+    /// Font size the character was exported at
+    pub size: f32,
+    /// Advance of the character at a font size of 100
+    #[serde(rename = "w")]
+    pub width: f32,
+    #[serde(default)]
+    pub data: FontCharData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FontCharData {
+    #[serde(default)]
+    pub shapes: Vec<ShapeLayer>,
+}
+
+impl FontChar {
+    /// Outline of the character at a font size of 100, from its start on the
+    /// baseline
+    pub fn beziers(&self) -> Vec<Bezier> {
+        fn collect(shapes: &[ShapeLayer], beziers: &mut Vec<Bezier>) {
+            for shape in shapes {
+                match &shape.shape {
+                    Shape::Group { shapes } => collect(shapes, beziers),
+                    Shape::Path { d, .. } => beziers.extend(d.initial_value()),
+                    _ => {}
+                }
+            }
+        }
+        let mut beziers = vec![];
+        collect(&self.data.shapes, &mut beziers);
+        beziers
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -334,11 +569,21 @@ pub struct ShapeLayer {
     pub shape: Shape,
 }
 
+/// Text range data of a glyph. The text, ranges and units are shared by all
+/// glyphs of the text
 #[derive(Debug, Clone)]
 pub struct TextRangeInfo {
-    pub value: Vec<Vec<char>>,
+    pub value: Arc<[Vec<char>]>,
     pub index: (usize, usize), // line, char
-    pub ranges: Vec<TextRange>,
+    pub ranges: Arc<[TextRange]>,
+    /// Units of the text, for each range
+    pub units: Arc<[TextUnits]>,
+    /// Center of the anchor group of the glyph on the baseline, relative to
+    /// the glyph, around which animators scale and rotate it
+    pub center: f32,
+    /// Width of the anchor group and font size
+    pub group_size: Vector2D,
+    pub alignment: Option<Animated<Vector2D>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -562,6 +807,23 @@ impl Default for TextJustify {
     }
 }
 
+/// Vertical alignment of paragraph text within its box
+#[derive(
+    serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
+#[repr(u8)]
+pub enum TextVerticalJustify {
+    Top = 0,
+    Center = 1,
+    Bottom = 2,
+}
+
+impl Default for TextVerticalJustify {
+    fn default() -> Self {
+        TextVerticalJustify::Top
+    }
+}
+
 #[derive(serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum TextCaps {
@@ -576,7 +838,9 @@ impl Default for TextCaps {
     }
 }
 
-#[derive(serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy)]
+#[derive(
+    serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
 #[repr(u8)]
 pub enum TextBased {
     Characters = 1,
@@ -585,7 +849,19 @@ pub enum TextBased {
     Lines = 4,
 }
 
-#[derive(serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy)]
+/// Units of the start, end and offset of a range selector
+#[derive(
+    serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
+#[repr(u8)]
+pub enum TextRangeUnits {
+    Percent = 1,
+    Index = 2,
+}
+
+#[derive(
+    serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
 #[repr(u8)]
 pub enum TextShape {
     Square = 1,
@@ -608,6 +884,37 @@ pub enum MatteMode {
     InvertedLuma = 4,
 }
 
+/// Luminance coefficients used by luma mattes, same as SVG's
+/// `luminanceToAlpha` and the `LUMA` of the shader
+const LUMA: [f32; 3] = [0.2125, 0.7154, 0.0721];
+
+impl MatteMode {
+    pub fn is_luma(&self) -> bool {
+        matches!(self, MatteMode::Luma | MatteMode::InvertedLuma)
+    }
+
+    pub fn is_inverted(&self) -> bool {
+        matches!(self, MatteMode::InvertedAlpha | MatteMode::InvertedLuma)
+    }
+
+    /// How much of a matted pixel is kept, given the straight-alpha RGBA color
+    /// (components in `0.0..=1.0`) of the matte source at the same position
+    pub fn coverage(&self, color: [f32; 4]) -> f32 {
+        let [r, g, b, a] = color;
+        let [lr, lg, lb] = LUMA;
+        let value = match self {
+            MatteMode::Normal => return 1.0,
+            MatteMode::Alpha | MatteMode::InvertedAlpha => a,
+            MatteMode::Luma | MatteMode::InvertedLuma => (lr * r + lg * g + lb * b) * a,
+        };
+        if self.is_inverted() {
+            1.0 - value
+        } else {
+            value
+        }
+    }
+}
+
 #[derive(
     serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy, PartialEq,
 )]
@@ -705,6 +1012,27 @@ pub struct Stroke {
     pub color: Animated<Rgb>,
 }
 
+impl Stroke {
+    /// A solid stroke without dashes
+    pub fn new(
+        color: Animated<Rgb>,
+        width: Animated<f32>,
+        opacity: Animated<f32>,
+        line_cap: LineCap,
+        line_join: LineJoin,
+    ) -> Stroke {
+        Stroke {
+            line_cap,
+            line_join,
+            miter_limit: 4.0,
+            opacity,
+            width,
+            dashes: vec![],
+            color,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(from = "ColorListHelper", into = "ColorListHelper")]
 pub struct ColorList {
@@ -718,6 +1046,36 @@ pub struct GradientColor {
     pub color: Rgba,
 }
 
+impl GradientColor {
+    /// Color of the gradient made of `stops`, sorted by offset, at `offset`.
+    /// Offsets outside of the stops are clamped to the first or the last stop
+    pub fn sample(stops: &[GradientColor], offset: f32) -> Rgba {
+        let next = match stops.iter().position(|stop| stop.offset >= offset) {
+            Some(0) => return stops[0].color,
+            Some(index) => index,
+            None => {
+                return stops
+                    .last()
+                    .map(|stop| stop.color)
+                    .unwrap_or(Rgba::new_u8(0, 0, 0, 0))
+            }
+        };
+        let (prev, next) = (&stops[next - 1], &stops[next]);
+        let t = if next.offset > prev.offset {
+            (offset - prev.offset) / (next.offset - prev.offset)
+        } else {
+            0.0
+        };
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Rgba::new_u8(
+            channel(prev.color.r, next.color.r),
+            channel(prev.color.g, next.color.g),
+            channel(prev.color.b, next.color.b),
+            channel(prev.color.a, next.color.a),
+        )
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Gradient {
     #[serde(rename = "s")]
@@ -728,6 +1086,87 @@ pub struct Gradient {
     pub gradient_ty: GradientType,
     #[serde(rename = "g")]
     pub colors: ColorList,
+    /// Highlight length of a radial gradient, in percentage of its radius
+    #[serde(rename = "h", default)]
+    pub highlight_length: Option<Animated<f32>>,
+    /// Highlight angle of a radial gradient in degrees, relative to the
+    /// direction from `start` to `end`
+    #[serde(rename = "a", default)]
+    pub highlight_angle: Option<Animated<f32>>,
+}
+
+impl Gradient {
+    /// Whether the geometry or the colors of this gradient change over time
+    pub fn is_animated(&self) -> bool {
+        self.colors.colors.is_animated()
+            || self.start.is_animated()
+            || self.end.is_animated()
+            || self
+                .highlight_length
+                .as_ref()
+                .map(|h| h.is_animated())
+                .unwrap_or(false)
+            || self
+                .highlight_angle
+                .as_ref()
+                .map(|a| a.is_animated())
+                .unwrap_or(false)
+    }
+
+    /// Focal point of a radial gradient at `frame`. It stays strictly inside the
+    /// gradient circle, as After Effects does
+    pub fn focal_point(&self, frame: f32) -> Vector2D {
+        let start = self.start.value(frame);
+        let end = self.end.value(frame);
+        let length = self
+            .highlight_length
+            .as_ref()
+            .map(|h| h.value(frame) / 100.0)
+            .unwrap_or(0.0)
+            .clamp(-0.99, 0.99);
+        let angle = self
+            .highlight_angle
+            .as_ref()
+            .map(|a| a.value(frame).to_radians())
+            .unwrap_or(0.0);
+        let direction = end - start;
+        let distance = direction.length() * length;
+        let angle = direction.y.atan2(direction.x) + angle;
+        start + Vector2D::new(angle.cos(), angle.sin()) * distance
+    }
+
+    /// Offset of `point` along the gradient at `frame`, 0.0 being the first
+    /// stop and 1.0 the last one. Values are not clamped
+    pub fn offset(&self, frame: f32, point: Vector2D) -> f32 {
+        let start = self.start.value(frame);
+        let end = self.end.value(frame);
+        match self.gradient_ty {
+            GradientType::Linear => {
+                let v = end - start;
+                let len = v.square_length();
+                if len == 0.0 {
+                    0.0
+                } else {
+                    (point - start).dot(v) / len
+                }
+            }
+            GradientType::Radial => {
+                let radius = (end - start).length();
+                if radius == 0.0 {
+                    return 1.0;
+                }
+                // Find the circle interpolated between the focal point (t = 0) and the
+                // gradient circle (t = 1) which passes through `point`
+                let focal = self.focal_point(frame);
+                let d = point - focal;
+                let e = start - focal;
+                let a = e.square_length() - radius * radius;
+                let b = d.dot(e);
+                let c = d.square_length();
+                (b - (b * b - a * c).max(0.0).sqrt()) / a
+            }
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -887,42 +1326,62 @@ pub struct TextAnimationData {
     pub ranges: Vec<TextRange>,
     #[serde(rename = "d")]
     pub document: TextData,
-    #[serde(rename = "m")]
-    options: TextAlignmentOptions,
-    #[serde(rename = "p")]
-    follow_path: TextFollowPath,
+    #[serde(rename = "m", default)]
+    pub options: TextAlignmentOptions,
+    #[serde(rename = "p", default)]
+    pub follow_path: TextFollowPath,
 }
 
+/// Properties of a text animator, applied to the glyphs its range selects
 #[derive(Deserialize, Debug, Clone)]
 pub struct TextStyle {
+    #[serde(rename = "a", default)]
+    pub anchor: Option<Animated<Vector3D>>,
+    #[serde(rename = "p", default)]
+    pub position: Option<Animated<Vector3D>>,
+    #[serde(rename = "s", default)]
+    pub scale: Option<Animated<Vector2D>>,
+    #[serde(rename = "sk", default)]
+    pub skew: Option<Animated<f32>>,
+    #[serde(rename = "sa", default)]
+    pub skew_axis: Option<Animated<f32>>,
+    #[serde(rename = "r", alias = "rz", default)]
+    pub rotation: Option<Animated<f32>>,
+    #[serde(rename = "rx", default)]
+    pub rotation_x: Option<Animated<f32>>,
+    #[serde(rename = "ry", default)]
+    pub rotation_y: Option<Animated<f32>>,
+    #[serde(rename = "o", default)]
+    pub opacity: Option<Animated<f32>>,
     #[serde(rename = "sw", default)]
-    stroke_width: Option<Animated<f32>>,
+    pub stroke_width: Option<Animated<f32>>,
     #[serde(rename = "sc", default)]
-    stroke_color: Option<Animated<Rgb>>,
+    pub stroke_color: Option<Animated<Rgb>>,
     #[serde(rename = "sh", default)]
-    stroke_hue: Option<Animated<f32>>,
+    pub stroke_hue: Option<Animated<f32>>,
     #[serde(rename = "ss", default)]
-    stroke_saturation: Option<Animated<f32>>,
+    pub stroke_saturation: Option<Animated<f32>>,
     #[serde(rename = "sb", default)]
-    stroke_brightness: Option<Animated<f32>>,
+    pub stroke_brightness: Option<Animated<f32>>,
     #[serde(rename = "so", default)]
-    stroke_opacity: Option<Animated<f32>>,
+    pub stroke_opacity: Option<Animated<f32>>,
     #[serde(rename = "fc", default)]
-    fill_color: Option<Animated<Rgb>>,
+    pub fill_color: Option<Animated<Rgb>>,
     #[serde(rename = "fh", default)]
-    fill_hue: Option<Animated<f32>>,
+    pub fill_hue: Option<Animated<f32>>,
     #[serde(rename = "fs", default)]
-    fill_saturation: Option<Animated<f32>>,
+    pub fill_saturation: Option<Animated<f32>>,
     #[serde(rename = "fb", default)]
-    fill_brightness: Option<Animated<f32>>,
+    pub fill_brightness: Option<Animated<f32>>,
+    #[serde(rename = "fo", default)]
+    pub fill_opacity: Option<Animated<f32>>,
+    /// Tracking, added to the advance of each glyph
     #[serde(rename = "t", default)]
     pub letter_spacing: Option<Animated<f32>>,
     #[serde(rename = "bl", default)]
-    blur: Option<Animated<f32>>,
+    pub blur: Option<Animated<f32>>,
     #[serde(rename = "ls", default)]
     pub line_spacing: Option<Animated<f32>>,
-    #[serde(flatten)]
-    transform: Option<Transform>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -939,24 +1398,28 @@ pub struct TextRange {
 pub struct TextRangeSelector {
     #[serde(rename = "t", deserialize_with = "bool_from_int")]
     expressible: bool,
+    /// Ease of the influence towards its maximum, from -100 to 100
     #[serde(rename = "xe")]
-    max_ease: Animated<f32>,
+    pub max_ease: Animated<f32>,
+    /// Ease of the influence towards its minimum, from -100 to 100
     #[serde(rename = "ne")]
-    min_ease: Animated<f32>,
+    pub min_ease: Animated<f32>,
+    /// Amount of the influence in percent, negative amounts inverting it
     #[serde(rename = "a")]
-    max_amount: Animated<f32>,
+    pub max_amount: Animated<f32>,
     #[serde(rename = "b")]
-    based_on: TextBased,
+    pub based_on: TextBased,
     #[serde(rename = "rn", deserialize_with = "bool_from_int")]
-    randomize: bool,
+    pub randomize: bool,
     #[serde(rename = "sh")]
-    shape: TextShape,
+    pub shape: TextShape,
     #[serde(rename = "o", default)]
-    offset: Option<Animated<f32>>,
+    pub offset: Option<Animated<f32>>,
     #[serde(rename = "r")]
-    pub range_units: TextBased,
+    pub range_units: TextRangeUnits,
+    /// Smoothness of square selectors in percent
     #[serde(rename = "sm", default)]
-    selector_smoothness: Option<Animated<f32>>,
+    pub selector_smoothness: Option<Animated<f32>>,
     #[serde(rename = "s", default)]
     pub start: Option<Animated<f32>>,
     #[serde(rename = "e", default)]
@@ -975,11 +1438,54 @@ pub struct TextData {
     pub keyframes: Vec<KeyFrame<TextDocument>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct TextAlignmentOptions {}
+/// Anchor point of text animators
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TextAlignmentOptions {
+    /// Offset of the anchor point from the center of its group, in percents
+    /// of half of its width and of the font size
+    #[serde(rename = "a", default)]
+    pub alignment: Option<Animated<Vector2D>>,
+    #[serde(rename = "g", default)]
+    pub grouping: TextGrouping,
+}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct TextFollowPath {}
+/// Glyphs sharing an anchor point, which animators scale and rotate around
+#[derive(
+    serde_repr::Serialize_repr, serde_repr::Deserialize_repr, Debug, Clone, Copy, PartialEq,
+)]
+#[repr(u8)]
+pub enum TextGrouping {
+    Characters = 1,
+    Words = 2,
+    Lines = 3,
+    All = 4,
+}
+
+impl Default for TextGrouping {
+    fn default() -> Self {
+        TextGrouping::Characters
+    }
+}
+
+/// Text laid out along a mask of its layer
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TextFollowPath {
+    /// Index of the mask in the layer
+    #[serde(rename = "m", default)]
+    pub mask: Option<usize>,
+    #[serde(rename = "f", default)]
+    pub first_margin: Option<Animated<f32>>,
+    #[serde(rename = "l", default)]
+    pub last_margin: Option<Animated<f32>>,
+    /// Whether glyphs are rotated along the path
+    #[serde(rename = "p", default)]
+    pub perpendicular: Option<Animated<f32>>,
+    /// Whether glyphs are spread from the first to the last margin
+    #[serde(rename = "a", default)]
+    pub force_alignment: Option<Animated<f32>>,
+    #[serde(rename = "r", default)]
+    pub reverse: Option<Animated<f32>>,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TextDocument {
@@ -1002,24 +1508,46 @@ pub struct TextDocument {
         serialize_with = "array_from_rgba",
         default
     )]
-    stroke_color: Rgba,
+    pub stroke_color: Rgba,
     #[serde(rename = "sw", default)]
-    stroke_width: f32,
+    pub stroke_width: f32,
+    /// Whether the stroke of the text is drawn over its fill
     #[serde(rename = "of", default)]
-    stroke_above_fill: bool,
+    pub stroke_above_fill: bool,
+    /// Distance between baselines, from the font metrics when missing
     #[serde(rename = "lh", default)]
-    line_height: Option<f32>,
+    pub line_height: Option<f32>,
     #[serde(rename = "j", default)]
     pub justify: TextJustify,
     #[serde(rename = "ls", default)]
     pub baseline_shift: f32,
-    // TODO:
-    #[serde(default)]
-    sz: Vec<f32>,
-    #[serde(default)]
-    ps: Vec<f32>,
-    #[serde(default)]
-    ca: TextCaps,
+    /// Size of the box of paragraph text, empty for point text
+    #[serde(rename = "sz", default)]
+    pub box_size: Vec<f32>,
+    /// Top left corner of the box of paragraph text
+    #[serde(rename = "ps", default)]
+    pub box_position: Vec<f32>,
+    #[serde(rename = "vj", default)]
+    pub vertical_justify: TextVerticalJustify,
+    #[serde(rename = "ca", default)]
+    pub caps: TextCaps,
+    /// Space added after each character in thousandths of an em
+    #[serde(rename = "tr", default)]
+    pub tracking: f32,
+}
+
+impl TextDocument {
+    /// Box which paragraph text wraps into, `None` for point text
+    pub fn text_box(&self) -> Option<Rect<f32>> {
+        let [width, height] = self.box_size[..] else {
+            return None;
+        };
+        let (x, y) = match self.box_position[..] {
+            [x, y] => (x, y),
+            _ => (0.0, 0.0),
+        };
+        Some(rect(x, y, width, height))
+    }
 }
 
 impl Default for TextDocument {
@@ -1035,9 +1563,11 @@ impl Default for TextDocument {
             baseline_shift: 0.0,
             value: String::new(),
             justify: TextJustify::Left,
-            sz: vec![],
-            ps: vec![],
-            ca: TextCaps::Regular,
+            box_size: vec![],
+            box_position: vec![],
+            vertical_justify: TextVerticalJustify::Top,
+            caps: TextCaps::Regular,
+            tracking: 0.0,
         }
     }
 }
@@ -1077,12 +1607,28 @@ pub enum MaskMode {
     Difference,
 }
 
-fn mat4(anchor: Vector2D, position: Vector2D, scale: Vector2D, rotation: f32) -> Mat4 {
-    let anchor = Vec3::new(anchor.x, anchor.y, 0.0);
+fn mat4(
+    anchor: Vector3D,
+    position: Vector3D,
+    scale: Vector2D,
+    rotation: Vector3D,
+    orientation: Vector3D,
+) -> Mat4 {
+    let anchor = Vec3::new(anchor.x, anchor.y, anchor.z);
     let scale = Vec3::new(scale.x, scale.y, 1.0);
-    let position = Vec3::new(position.x, position.y, 0.0);
+    let position = Vec3::new(position.x, position.y, position.z);
     Mat4::from_translation(position)
-        * Mat4::from_rotation_z(rotation * std::f32::consts::PI / 180.0)
+        * rotation_mat4(orientation)
+        * rotation_mat4(rotation)
         * Mat4::from_scale(scale)
         * Mat4::from_translation(-anchor)
 }
+
+/// Rotate around the z, y then x axes by the given degrees. Like lottie-web,
+/// positive x and y rotations turn the top and the right side of a layer away
+/// from the viewer
+pub fn rotation_mat4(degrees: Vector3D) -> Mat4 {
+    Mat4::from_rotation_x(-degrees.x.to_radians())
+        * Mat4::from_rotation_y(-degrees.y.to_radians())
+        * Mat4::from_rotation_z(degrees.z.to_radians())
+}
@@ -0,0 +1,156 @@
+use super::BlendMode;
+
+/// Luminosity of a color, as defined by the W3C compositing spec
+fn lum([r, g, b]: [f32; 3]) -> f32 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn clip_color(c: [f32; 3]) -> [f32; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    let mut c = c;
+    if n < 0.0 {
+        c = c.map(|v| l + (v - l) * l / (l - n));
+    }
+    if x > 1.0 {
+        c = c.map(|v| l + (v - l) * (1.0 - l) / (x - l));
+    }
+    c
+}
+
+fn set_lum(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(c);
+    clip_color(c.map(|v| v + d))
+}
+
+fn sat(c: [f32; 3]) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn set_sat(c: [f32; 3], s: f32) -> [f32; 3] {
+    let mut index = [0, 1, 2];
+    index.sort_by(|a, b| c[*a].total_cmp(&c[*b]));
+    let [min, mid, max] = index;
+    let mut result = [0.0; 3];
+    if c[max] > c[min] {
+        result[mid] = (c[mid] - c[min]) * s / (c[max] - c[min]);
+        result[max] = s;
+    }
+    result
+}
+
+fn multiply(cb: f32, cs: f32) -> f32 {
+    cb * cs
+}
+
+fn screen(cb: f32, cs: f32) -> f32 {
+    cb + cs - cb * cs
+}
+
+fn hard_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        multiply(cb, 2.0 * cs)
+    } else {
+        screen(cb, 2.0 * cs - 1.0)
+    }
+}
+
+fn soft_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+    } else {
+        let d = if cb <= 0.25 {
+            ((16.0 * cb - 12.0) * cb + 4.0) * cb
+        } else {
+            cb.sqrt()
+        };
+        cb + (2.0 * cs - 1.0) * (d - cb)
+    }
+}
+
+fn color_dodge(cb: f32, cs: f32) -> f32 {
+    if cb == 0.0 {
+        0.0
+    } else if cs >= 1.0 {
+        1.0
+    } else {
+        (cb / (1.0 - cs)).min(1.0)
+    }
+}
+
+fn color_burn(cb: f32, cs: f32) -> f32 {
+    if cb >= 1.0 {
+        1.0
+    } else if cs == 0.0 {
+        0.0
+    } else {
+        1.0 - ((1.0 - cb) / cs).min(1.0)
+    }
+}
+
+impl BlendMode {
+    /// Whether this mode blends each color channel on its own
+    pub fn is_separable(&self) -> bool {
+        !matches!(
+            self,
+            BlendMode::Hue | BlendMode::Saturation | BlendMode::Color | BlendMode::Luminosity
+        )
+    }
+
+    /// Mix a `source` color with the `backdrop` color behind it, following the
+    /// W3C compositing spec. Colors are straight RGB in `0.0..=1.0`
+    pub fn blend(&self, backdrop: [f32; 3], source: [f32; 3]) -> [f32; 3] {
+        let separable = |f: fn(f32, f32) -> f32| {
+            [
+                f(backdrop[0], source[0]),
+                f(backdrop[1], source[1]),
+                f(backdrop[2], source[2]),
+            ]
+        };
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Multiply => separable(multiply),
+            BlendMode::Screen => separable(screen),
+            BlendMode::Overlay => separable(|cb, cs| hard_light(cs, cb)),
+            BlendMode::Darken => separable(f32::min),
+            BlendMode::Lighten => separable(f32::max),
+            BlendMode::ColorDodge => separable(color_dodge),
+            BlendMode::ColorBurn => separable(color_burn),
+            BlendMode::HighLight => separable(hard_light),
+            BlendMode::SoftLight => separable(soft_light),
+            BlendMode::Difference => separable(|cb, cs| (cb - cs).abs()),
+            BlendMode::Exclusion => separable(|cb, cs| cb + cs - 2.0 * cb * cs),
+            BlendMode::Add => separable(|cb, cs| (cb + cs).min(1.0)),
+            BlendMode::HardMix => separable(|cb, cs| if cb + cs >= 1.0 { 1.0 } else { 0.0 }),
+            BlendMode::Hue => set_lum(set_sat(source, sat(backdrop)), lum(backdrop)),
+            BlendMode::Saturation => set_lum(set_sat(backdrop, sat(source)), lum(backdrop)),
+            BlendMode::Color => set_lum(source, lum(backdrop)),
+            BlendMode::Luminosity => set_lum(backdrop, lum(source)),
+        }
+    }
+
+    /// Composite a `source` pixel over a `backdrop` pixel with this blend mode.
+    /// Both are straight-alpha RGBA in `0.0..=1.0`, as is the result
+    pub fn composite(&self, backdrop: [f32; 4], source: [f32; 4]) -> [f32; 4] {
+        let [rb, gb, bb, ab] = backdrop;
+        let [rs, gs, bs, a_s] = source;
+        let blended = self.blend([rb, gb, bb], [rs, gs, bs]);
+        let alpha = a_s + ab * (1.0 - a_s);
+        if alpha == 0.0 {
+            return [0.0; 4];
+        }
+        let channel = |index: usize, cb: f32, cs: f32| {
+            // The source color is mixed with the blended color where the
+            // backdrop is opaque
+            let cs = (1.0 - ab) * cs + ab * blended[index];
+            (a_s * cs + ab * cb * (1.0 - a_s)) / alpha
+        };
+        [
+            channel(0, rb, rs),
+            channel(1, gb, gs),
+            channel(2, bb, bs),
+            alpha,
+        ]
+    }
+}
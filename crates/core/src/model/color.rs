@@ -2,7 +2,7 @@ use std::str::FromStr;
 
 use super::helpers::{FromTo, Value};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Rgba {
     pub r: u8,
     pub g: u8,
@@ -74,6 +74,11 @@ impl Rgb {
     pub fn new_u8(r: u8, g: u8, b: u8) -> Rgb {
         Rgb { r, g, b }
     }
+
+    /// Channels from 0 to 1
+    pub fn to_f32(self) -> [f32; 3] {
+        [self.r, self.g, self.b].map(|channel| channel as f32 / 255.0)
+    }
 }
 
 impl FromTo<Value> for Rgba {
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+use super::helpers::{bool_from_int, int_from_bool, EffectValueHelper};
+use super::{Animated, Rgba, Vector2D};
+
+/// A layer effect, grouped by the effect type (`ty`) After Effects exports
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "EffectGroup", into = "EffectGroup")]
+pub enum Effect {
+    /// Expression controls, e.g. a slider or a color control
+    Custom(EffectGroup),
+    Tint(EffectGroup),
+    Fill(EffectGroup),
+    Stroke(EffectGroup),
+    Tritone(EffectGroup),
+    Levels(EffectGroup),
+    DropShadow(EffectGroup),
+    RadialWipe(EffectGroup),
+    DisplacementMap(EffectGroup),
+    Matte3(EffectGroup),
+    GaussianBlur(EffectGroup),
+    Twirl(EffectGroup),
+    MeshWarp(EffectGroup),
+    Wavy(EffectGroup),
+    Spherize(EffectGroup),
+    Puppet(EffectGroup),
+    Unknown(EffectGroup),
+}
+
+impl Effect {
+    pub fn group(&self) -> &EffectGroup {
+        match self {
+            Effect::Custom(group)
+            | Effect::Tint(group)
+            | Effect::Fill(group)
+            | Effect::Stroke(group)
+            | Effect::Tritone(group)
+            | Effect::Levels(group)
+            | Effect::DropShadow(group)
+            | Effect::RadialWipe(group)
+            | Effect::DisplacementMap(group)
+            | Effect::Matte3(group)
+            | Effect::GaussianBlur(group)
+            | Effect::Twirl(group)
+            | Effect::MeshWarp(group)
+            | Effect::Wavy(group)
+            | Effect::Spherize(group)
+            | Effect::Puppet(group)
+            | Effect::Unknown(group) => group,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.group().enabled
+    }
+
+    /// Whether this effect is taken into account when rendering. Expression
+    /// controls have no visual output, so they are always supported. Strokes
+    /// are only drawn on shape layers
+    pub fn is_supported(&self) -> bool {
+        matches!(
+            self,
+            Effect::Custom(_)
+                | Effect::GaussianBlur(_)
+                | Effect::DropShadow(_)
+                | Effect::Fill(_)
+                | Effect::Tint(_)
+                | Effect::Tritone(_)
+                | Effect::Levels(_)
+                | Effect::Stroke(_)
+        )
+    }
+}
+
+impl From<EffectGroup> for Effect {
+    fn from(group: EffectGroup) -> Self {
+        match group.ty {
+            5 => Effect::Custom(group),
+            20 => Effect::Tint(group),
+            21 => Effect::Fill(group),
+            22 => Effect::Stroke(group),
+            23 => Effect::Tritone(group),
+            24 => Effect::Levels(group),
+            25 => Effect::DropShadow(group),
+            26 => Effect::RadialWipe(group),
+            27 => Effect::DisplacementMap(group),
+            28 => Effect::Matte3(group),
+            29 => Effect::GaussianBlur(group),
+            30 => Effect::Twirl(group),
+            31 => Effect::MeshWarp(group),
+            32 => Effect::Wavy(group),
+            33 => Effect::Spherize(group),
+            34 => Effect::Puppet(group),
+            _ => Effect::Unknown(group),
+        }
+    }
+}
+
+impl From<Effect> for EffectGroup {
+    fn from(effect: Effect) -> Self {
+        effect.group().clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectGroup {
+    pub ty: u8,
+    #[serde(rename = "nm", default)]
+    pub name: Option<String>,
+    #[serde(rename = "mn", default)]
+    pub match_name: Option<String>,
+    #[serde(rename = "ix", default)]
+    pub index: Option<u32>,
+    #[serde(
+        rename = "en",
+        deserialize_with = "bool_from_int",
+        serialize_with = "int_from_bool",
+        default = "default_enabled"
+    )]
+    pub enabled: bool,
+    /// Parameters of the effect, in the order After Effects lists them
+    #[serde(rename = "ef", default)]
+    pub values: Vec<EffectValue>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl EffectGroup {
+    /// Numeric parameter at `index`, which can be a slider, an angle, a
+    /// checkbox, a dropdown or a layer index
+    pub fn number(&self, index: usize) -> Option<&Animated<f32>> {
+        match self.values.get(index)? {
+            EffectValue::Slider(v)
+            | EffectValue::Angle(v)
+            | EffectValue::Checkbox(v)
+            | EffectValue::Dropdown(v)
+            | EffectValue::Layer(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self, index: usize) -> Option<&Animated<Rgba>> {
+        match self.values.get(index)? {
+            EffectValue::Color(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn point(&self, index: usize) -> Option<&Animated<Vector2D>> {
+        match self.values.get(index)? {
+            EffectValue::Point(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// A parameter of an effect, typed by its `ty`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "EffectValueHelper", into = "EffectValueHelper")]
+pub enum EffectValue {
+    Slider(Animated<f32>),
+    /// Angle in degrees
+    Angle(Animated<f32>),
+    Color(Animated<Rgba>),
+    Point(Animated<Vector2D>),
+    /// 0 or 1
+    Checkbox(Animated<f32>),
+    /// 1-based index of the selected item
+    Dropdown(Animated<f32>),
+    /// Index (`ind`) of a layer in the same composition
+    Layer(Animated<f32>),
+    /// Values carrying no data for rendering, e.g. group separators, and
+    /// values which could not be parsed
+    Ignored(u8),
+}
+
+impl EffectValue {
+    /// Whether the value has a type carrying data but was ignored, because it
+    /// is missing or malformed
+    pub fn is_invalid(&self) -> bool {
+        matches!(self, EffectValue::Ignored(0 | 1 | 2 | 3 | 4 | 7 | 10))
+    }
+}
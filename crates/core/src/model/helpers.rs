@@ -137,6 +137,7 @@ impl<'de> serde::Deserialize<'de> for LayerContent {
                     let v = TextAnimationData::deserialize(v).map_err(D::Error::custom)?;
                     LayerContent::Text(v)
                 }
+                13 => LayerContent::Camera(Camera::deserialize(value).map_err(D::Error::custom)?),
                 // 7 => LayerContent::Null(Type3::deserialize(value).unwrap()),
                 _type => LayerContent::Empty, //panic!("unsupported type {:?}", type_),
             },
@@ -324,26 +325,67 @@ impl From<ColorListHelper> for ColorList {
 }
 
 fn f32_to_gradient_colors(data: &Vec<f32>, color_count: usize) -> Vec<GradientColor> {
-    if data.len() == color_count * 4 {
-        // Rgb color
-        data.chunks(4)
-            .map(|chunk| GradientColor {
-                offset: chunk[0],
-                color: Rgba::new_f32(chunk[1], chunk[2], chunk[3], 1.0),
-            })
-            .collect()
-    } else if data.len() == color_count * 4 + color_count * 2 {
-        // Rgba color
-        (&data[0..(color_count * 4)])
-            .chunks(4)
-            .zip((&data[(color_count * 4)..]).chunks(2))
-            .map(|(chunk, opacity)| GradientColor {
-                offset: chunk[0],
-                color: Rgba::new_f32(chunk[1], chunk[2], chunk[3], opacity[1]),
+    // `color_count` RGB stops come first, optionally followed by opacity stops which
+    // have their own offsets and count
+    let color_len = (color_count * 4).min(data.len());
+    let colors = data[..color_len]
+        .chunks_exact(4)
+        .map(|chunk| (chunk[0], [chunk[1], chunk[2], chunk[3]]))
+        .collect::<Vec<_>>();
+    let opacities = data[color_len..]
+        .chunks_exact(2)
+        .map(|chunk| (chunk[0], [chunk[1]]))
+        .collect::<Vec<_>>();
+    if opacities.is_empty() {
+        return colors
+            .into_iter()
+            .map(|(offset, [r, g, b])| GradientColor {
+                offset,
+                color: Rgba::new_f32(r, g, b, 1.0),
             })
-            .collect()
-    } else {
-        unimplemented!()
+            .collect();
+    }
+    // Every color stop is kept, so that two of them at the same offset still make
+    // a hard edge, and opacity stops add the offsets no color stop has
+    let mut stops = colors
+        .iter()
+        .map(|&(offset, rgb)| (offset, rgb, interpolate_stops(&opacities, offset)))
+        .collect::<Vec<_>>();
+    for &(offset, alpha) in &opacities {
+        if stops.iter().all(|(o, ..)| (o - offset).abs() >= 1e-4) {
+            stops.push((offset, interpolate_stops(&colors, offset), alpha));
+        }
+    }
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    stops
+        .into_iter()
+        .map(|(offset, [r, g, b], [a])| GradientColor {
+            offset,
+            color: Rgba::new_f32(r, g, b, a),
+        })
+        .collect()
+}
+
+/// Linearly interpolate values of sorted `stops` at `offset`, values before the
+/// first stop or after the last one are clamped
+fn interpolate_stops<const N: usize>(stops: &[(f32, [f32; N])], offset: f32) -> [f32; N] {
+    match stops.iter().position(|(o, _)| *o >= offset) {
+        Some(0) => stops[0].1,
+        Some(index) => {
+            let (o0, v0) = stops[index - 1];
+            let (o1, v1) = stops[index];
+            let t = if o1 > o0 {
+                (offset - o0) / (o1 - o0)
+            } else {
+                0.0
+            };
+            let mut result = v0;
+            for (r, (a, b)) in result.iter_mut().zip(v0.iter().zip(v1.iter())) {
+                *r = a + (b - a) * t;
+            }
+            result
+        }
+        None => stops.last().map(|(_, v)| *v).unwrap_or([1.0; N]),
     }
 }
 
@@ -389,3 +431,50 @@ fn gradient_colors_to_f32(data: &Vec<GradientColor>) -> Vec<f32> {
     }
     start
 }
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct EffectValueHelper {
+    ty: u8,
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    value: Option<serde_json::Value>,
+}
+
+impl From<EffectValueHelper> for EffectValue {
+    fn from(helper: EffectValueHelper) -> Self {
+        let value = match helper.value {
+            Some(value) => value,
+            None => return EffectValue::Ignored(helper.ty),
+        };
+        // A malformed value only disables the effect using it
+        let value = match helper.ty {
+            0 => Animated::deserialize(value).map(EffectValue::Slider),
+            1 => Animated::deserialize(value).map(EffectValue::Angle),
+            2 => Animated::deserialize(value).map(EffectValue::Color),
+            3 => Animated::deserialize(value).map(EffectValue::Point),
+            4 => Animated::deserialize(value).map(EffectValue::Checkbox),
+            7 => Animated::deserialize(value).map(EffectValue::Dropdown),
+            10 => Animated::deserialize(value).map(EffectValue::Layer),
+            ty => return EffectValue::Ignored(ty),
+        };
+        value.unwrap_or(EffectValue::Ignored(helper.ty))
+    }
+}
+
+impl From<EffectValue> for EffectValueHelper {
+    fn from(value: EffectValue) -> Self {
+        let (ty, value) = match value {
+            EffectValue::Slider(v) => (0, serde_json::to_value(v)),
+            EffectValue::Angle(v) => (1, serde_json::to_value(v)),
+            EffectValue::Color(v) => (2, serde_json::to_value(v)),
+            EffectValue::Point(v) => (3, serde_json::to_value(v)),
+            EffectValue::Checkbox(v) => (4, serde_json::to_value(v)),
+            EffectValue::Dropdown(v) => (7, serde_json::to_value(v)),
+            EffectValue::Layer(v) => (10, serde_json::to_value(v)),
+            EffectValue::Ignored(ty) => return EffectValueHelper { ty, value: None },
+        };
+        EffectValueHelper {
+            ty,
+            value: value.ok(),
+        }
+    }
+}
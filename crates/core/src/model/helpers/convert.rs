@@ -1,4 +1,4 @@
-use super::{Bezier, Rgb, TextDocument, Value, Vector2D};
+use super::{Bezier, Rgb, TextDocument, Value, Vector2D, Vector3D};
 
 pub trait FromTo<T> {
     fn from(v: T) -> Self;
@@ -16,6 +16,18 @@ impl FromTo<Value> for Vector2D {
     }
 }
 
+impl FromTo<Value> for Vector3D {
+    fn from(v: Value) -> Self {
+        let v = v.as_f32_vec().unwrap();
+        let component = |index: usize| v.get(index).cloned().unwrap_or(0.0);
+        Vector3D::new(v[0], component(1), component(2))
+    }
+
+    fn to(self) -> Value {
+        Value::List(vec![self.x, self.y, self.z])
+    }
+}
+
 impl FromTo<Value> for f32 {
     fn from(v: Value) -> Self {
         let v = v.as_f32_vec().unwrap();
@@ -1,3 +1,4 @@
+use crate::prelude::MotionBlur;
 use crate::Lottie;
 
 pub struct WindowConfig {
@@ -5,16 +6,43 @@ pub struct WindowConfig {
     pub show_inspector: bool,
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Default)]
 pub enum Target {
+    #[default]
     Default,
     Mask,
 }
 
+/// Settings of a headless rendering. Optional settings are left to the
+/// composition with `..Default::default()`
+#[derive(Default)]
 pub struct HeadlessConfig {
     pub target: Target,
     pub filename: String,
     pub frame: Option<u32>,
+    /// Samples per frame of layers with motion blur, overriding the ones of
+    /// the composition. A single sample disables motion blur
+    pub motion_blur_samples: Option<u32>,
+    /// Shutter angle in degrees, overriding the one of the composition
+    pub shutter_angle: Option<f32>,
+}
+
+impl HeadlessConfig {
+    /// Motion blur of `lottie` with the overrides of this config, `None` if
+    /// no layer enables motion blur or if a single sample is taken
+    pub fn motion_blur(&self, lottie: &Lottie) -> Option<MotionBlur> {
+        if !lottie.timeline().items().any(|layer| layer.motion_blur) {
+            return None;
+        }
+        let mut motion_blur = lottie.model.motion_blur.unwrap_or_default();
+        if let Some(samples) = self.motion_blur_samples {
+            motion_blur.samples_per_frame = samples;
+        }
+        if let Some(shutter_angle) = self.shutter_angle {
+            motion_blur.shutter_angle = shutter_angle;
+        }
+        (motion_blur.samples_per_frame > 1).then_some(motion_blur)
+    }
 }
 
 pub enum Config {
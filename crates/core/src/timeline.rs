@@ -2,15 +2,18 @@ use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 
-use crate::model::{Animated, Asset, Layer, LayerContent, MatteMode, Model, Shape};
+use crate::model::{
+    Animated, Asset, BlendMode, Effect, Layer, LayerContent, MatteMode, Model, Shape,
+};
 use slotmap::SlotMap;
 
 use crate::font::FontDB;
+use crate::layer::camera::LayerCamera;
 use crate::layer::frame::{FrameInfo, FrameTransformHierarchy};
 use crate::layer::hierarchy::TransformHierarchy;
 use crate::layer::staged::{ContentInfo, StagedLayer, TargetRef};
 use crate::prelude::{RenderableContent, StagedLayerMask};
-use crate::Error;
+use crate::{Diagnostic, Error};
 
 slotmap::new_key_type! {
     pub struct Id;
@@ -29,6 +32,9 @@ pub struct Timeline {
     frame_rate: f32,
     index_id_map: HashMap<u32, Id>,
     store: SlotMap<Id, StagedLayer>,
+    diagnostics: Vec<Diagnostic>,
+    /// Camera layers of the root composition, topmost first
+    cameras: Vec<LayerCamera>,
 }
 
 impl Timeline {
@@ -74,6 +80,16 @@ impl Timeline {
         self.store.get(id)
     }
 
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// The topmost camera of the root composition active at `frame`. Without
+    /// a camera, 3D layers are drawn without perspective
+    pub fn camera(&self, frame: f32) -> Option<&LayerCamera> {
+        self.cameras.iter().find(|camera| camera.is_active(frame))
+    }
+
     pub(crate) fn new(model: &Model, fontdb: &FontDB, root_path: &str) -> Result<Self, Error> {
         let mut timeline = Timeline {
             start_frame: 0.0,
@@ -81,9 +97,24 @@ impl Timeline {
             frame_rate: 0.0,
             index_id_map: HashMap::new(),
             store: SlotMap::with_key(),
+            diagnostics: fontdb.diagnostics().to_vec(),
+            cameras: model
+                .layers
+                .iter()
+                .filter(|layer| !layer.hidden)
+                .filter_map(|layer| {
+                    LayerCamera::new(
+                        layer,
+                        &model.layers,
+                        model.width as f32,
+                        model.height as f32,
+                    )
+                })
+                .collect(),
         };
         let default_parent_map: Rc<RefCell<HashMap<u32, Id>>> = Rc::default();
         let default_standby_map: Rc<RefCell<HashMap<u32, Vec<Id>>>> = Rc::default();
+        let default_matte_map: MatteMap = Rc::default();
         let mut force_zindex_ids = HashSet::new();
         let mut layers = model
             .layers
@@ -97,11 +128,13 @@ impl Timeline {
                 parent: None,
                 parent_map: default_parent_map.clone(),
                 standby_map: default_standby_map.clone(),
+                matte_map: default_matte_map.clone(),
                 time_remapping: layer.time_remapping(),
             })
             .collect::<VecDeque<_>>();
         let default_frame_rate = model.frame_rate;
         let mut previous = None;
+        let mut previous_matte_ids = vec![];
         while !layers.is_empty() {
             let LayerInfo {
                 layer,
@@ -111,10 +144,12 @@ impl Timeline {
                 parent,
                 parent_map,
                 standby_map,
+                matte_map,
                 time_remapping,
             } = layers.pop_front().unwrap();
             let index = layer.index;
             let parent_index = layer.parent_index;
+            let matte_parent = layer.matte_parent;
             let mut assets = vec![];
             match &layer.content {
                 LayerContent::PreCompositionRef(r) => {
@@ -124,6 +159,7 @@ impl Timeline {
                             let default_parent_map: Rc<RefCell<HashMap<u32, Id>>> = Rc::default();
                             let default_standby_map: Rc<RefCell<HashMap<u32, Vec<Id>>>> =
                                 Rc::default();
+                            let default_matte_map: MatteMap = Rc::default();
                             for (index, asset_layer) in asset.layers.iter().enumerate() {
                                 let asset_layer = asset_layer.clone();
                                 let zindex = index as f32 * step;
@@ -135,6 +171,7 @@ impl Timeline {
                                     parent: None,
                                     standby_map: default_standby_map.clone(),
                                     parent_map: default_parent_map.clone(),
+                                    matte_map: default_matte_map.clone(),
                                     time_remapping: None,
                                 });
                             }
@@ -160,6 +197,7 @@ impl Timeline {
                                 parent: None,
                                 parent_map: Default::default(),
                                 standby_map: Default::default(),
+                                matte_map: Default::default(),
                                 time_remapping: None,
                             });
                         }
@@ -169,8 +207,54 @@ impl Timeline {
                 _ => {}
             }
 
-            let content = ContentInfo::from_layer(layer.clone(), model, fontdb, root_path)?;
+            for effect in &layer.effects {
+                // The Stroke effect is only drawn with the shapes of a layer
+                let shape_only = matches!(effect, Effect::Stroke(_))
+                    && !matches!(layer.content, LayerContent::Shape(_));
+                if effect.enabled() && (!effect.is_supported() || shape_only) {
+                    let group = effect.group();
+                    timeline.diagnostics.push(Diagnostic::UnsupportedEffect {
+                        layer: layer.name.clone(),
+                        name: group.name.clone(),
+                        ty: group.ty,
+                    });
+                }
+                if effect.enabled() {
+                    let group = effect.group();
+                    for (index, value) in group.values.iter().enumerate() {
+                        if value.is_invalid() {
+                            timeline.diagnostics.push(Diagnostic::InvalidEffectValue {
+                                layer: layer.name.clone(),
+                                name: group.name.clone(),
+                                index,
+                            });
+                        }
+                    }
+                }
+            }
+            // Blend modes are applied to the drawn content of a layer, which
+            // precomposition layers have none of
+            if let (LayerContent::PreCompositionRef(_), Some(mode)) =
+                (&layer.content, layer.blend_mode)
+            {
+                if mode != BlendMode::Normal {
+                    timeline
+                        .diagnostics
+                        .push(Diagnostic::PrecompositionBlendMode {
+                            layer: layer.name.clone(),
+                            mode,
+                        });
+                }
+            }
+            let content = ContentInfo::from_layer(
+                layer.clone(),
+                model,
+                fontdb,
+                root_path,
+                &mut timeline.diagnostics,
+            )?;
             let mut ids = vec![];
+            let mut overlay_id = None;
             match content {
                 ContentInfo::Simple(c) => ids.push(timeline.add_item(c.into_stage_layer(&layer))),
                 ContentInfo::TextKeyframes(mut keyframes) => {
@@ -178,13 +262,28 @@ impl Timeline {
                         *end = layer.end_frame;
                     }
                     for keyframe in keyframes {
-                        let mut layer = keyframe.content.into_stage_layer(&layer);
-                        layer.start_frame = keyframe.start_frame;
-                        layer.end_frame = keyframe.end_frame;
-                        ids.push(timeline.add_item(layer))
+                        let mut content = keyframe.content;
+                        // Glyphs blurred by animators are drawn as layers of
+                        // their own, which the blur effect is applied to
+                        let blurred = content.take_blurred_glyphs(keyframe.end_frame);
+                        let mut staged = content.into_stage_layer(&layer);
+                        staged.start_frame = keyframe.start_frame;
+                        staged.end_frame = keyframe.end_frame;
+                        ids.push(timeline.add_item(staged));
+                        for (glyph, blur) in blurred {
+                            let mut staged = glyph.into_stage_layer(&layer);
+                            staged.start_frame = keyframe.start_frame;
+                            staged.end_frame = keyframe.end_frame;
+                            staged.effects.push(blur);
+                            ids.push(timeline.add_item(staged));
+                        }
                     }
                 }
-                ContentInfo::ContentWithMasks { content, masks } => {
+                ContentInfo::ContentWithMasks {
+                    content,
+                    masks,
+                    overlay,
+                } => {
                     let (layers, matte_modes): (Vec<StagedLayer>, Vec<MatteMode>) = masks
                         .into_iter()
                         .map(|m| (m.0.into_stage_layer(&layer), m.1))
@@ -194,6 +293,7 @@ impl Timeline {
                         .into_iter()
                         .map(|mut layer| {
                             layer.is_mask = true;
+                            layer.effects.clear();
                             timeline.add_item(layer)
                         })
                         .collect();
@@ -203,35 +303,58 @@ impl Timeline {
                             mode: matte_mode,
                         })
                     }
+                    overlay_id =
+                        overlay.map(|overlay| timeline.add_item(overlay.into_stage_layer(&layer)));
+                    ids.extend(overlay_id);
                     ids.push(timeline.add_item(target_layer));
                 }
             }
+            // Items drawn into a track matte when the layer is used as one,
+            // its own masks being left out
+            let matte_ids = ids
+                .iter()
+                .copied()
+                .filter(|id| !timeline.store[*id].is_mask)
+                .collect::<Vec<_>>();
             for id in &ids {
-                let matte_mode = timeline.store.get(*id).unwrap().matte_mode;
-                if let (Some(id), Some(mode)) = (previous, matte_mode) {
-                    if mode != MatteMode::Normal {
-                        let prev = timeline.store.get_mut(id).unwrap();
-                        prev.is_mask = true;
-                    }
-                }
                 let staged = timeline.store.get_mut(*id).unwrap();
                 staged.target = target_ref.clone();
                 staged.parent = parent;
+                staged.composition = parent;
                 staged.zindex = zindex;
                 staged.frame_rate = default_frame_rate;
                 staged.frame_transform.time_remapping = time_remapping.clone();
                 staged.frame_transform.frame_rate = default_frame_rate;
-                if let (Some(id), Some(mode)) = (previous, matte_mode) {
-                    if mode != MatteMode::Normal {
-                        staged
-                            .mask_hierarchy
-                            .stack
-                            .push(StagedLayerMask { id, mode });
-                    }
+                if layer.is_matte_source {
+                    staged.is_mask = true;
                 }
                 previous = Some(*id);
             }
+            // The overlay is drawn right above the content of the layer
+            if let Some(staged) = overlay_id.and_then(|id| timeline.store.get_mut(id)) {
+                staged.zindex -= child_index_window / 2.0;
+            }
             let id = previous.unwrap().clone();
+            if let Some(mode) = layer.matte_mode.filter(|mode| *mode != MatteMode::Normal) {
+                match matte_parent {
+                    Some(matte_index) => {
+                        let sources = matte_map.borrow().sources.get(&matte_index).cloned();
+                        match sources {
+                            Some(sources) => timeline.set_matte(&matte_ids, &sources, mode),
+                            // The matte source comes later in this composition
+                            None => matte_map
+                                .borrow_mut()
+                                .pending
+                                .entry(matte_index)
+                                .or_default()
+                                .push((matte_ids.clone(), mode)),
+                        }
+                    }
+                    // Without an explicit matte parent, the matte source is the layer right
+                    // above
+                    None => timeline.set_matte(&matte_ids, &previous_matte_ids, mode),
+                }
+            }
             for mut info in assets {
                 info.parent = Some(id);
                 layers.push_back(info);
@@ -239,6 +362,13 @@ impl Timeline {
             if let Some(ind) = index {
                 parent_map.borrow_mut().insert(ind, id);
             }
+            if let Some(ind) = index {
+                matte_map
+                    .borrow_mut()
+                    .sources
+                    .insert(ind, matte_ids.clone());
+            }
+            previous_matte_ids = matte_ids;
             for id in ids {
                 if let Some(index) = parent_index {
                     if let Some(parent_id) = parent_map.borrow().get(&index) {
@@ -253,6 +383,12 @@ impl Timeline {
             }
 
             if let Some(index) = index {
+                let pending = matte_map.borrow_mut().pending.remove(&index);
+                if let Some(sources) = matte_map.borrow().sources.get(&index) {
+                    for (targets, mode) in pending.into_iter().flatten() {
+                        timeline.set_matte(&targets, sources, mode);
+                    }
+                }
                 for child_id in standby_map
                     .borrow_mut()
                     .remove(&index)
@@ -275,6 +411,32 @@ impl Timeline {
         Ok(timeline)
     }
 
+    /// Use the items of a matte source layer as the track matte of every item
+    /// in `targets`, which leave out the own masks of the matted layer. The
+    /// sources will then only be rendered as a matte, all of them into the
+    /// mask of the first one
+    fn set_matte(&mut self, targets: &[Id], sources: &[Id], mode: MatteMode) {
+        let Some(&source) = sources.first() else {
+            return;
+        };
+        for (index, id) in sources.iter().enumerate() {
+            if let Some(layer) = self.store.get_mut(*id) {
+                layer.is_mask = true;
+                if index > 0 {
+                    layer.mask_group = Some(source);
+                }
+            }
+        }
+        for id in targets {
+            if let Some(target) = self.store.get_mut(*id) {
+                target
+                    .mask_hierarchy
+                    .stack
+                    .push(StagedLayerMask { id: source, mode });
+            }
+        }
+    }
+
     /// Lottie's parenting does not share zindex, so we have to fix it to align
     /// to the usual transformation hierarchy logic in almost every renderer
     fn fix_zindex(&mut self, force_zindex_ids: HashSet<Id>) {
@@ -365,6 +527,17 @@ impl Timeline {
     }
 }
 
+/// Staged items of the layers of a composition which can be used as a matte,
+/// and matted items waiting for their matte source, both keyed by the source's
+/// index
+#[derive(Default)]
+struct Mattes {
+    sources: HashMap<u32, Vec<Id>>,
+    pending: HashMap<u32, Vec<(Vec<Id>, MatteMode)>>,
+}
+
+type MatteMap = Rc<RefCell<Mattes>>;
+
 struct LayerInfo {
     layer: Layer,
     zindex: f32,
@@ -373,5 +546,6 @@ struct LayerInfo {
     parent: Option<Id>,
     parent_map: Rc<RefCell<HashMap<u32, Id>>>,
     standby_map: Rc<RefCell<HashMap<u32, Vec<Id>>>>,
+    matte_map: MatteMap,
     time_remapping: Option<Animated<f32>>,
 }
@@ -1,7 +1,23 @@
 use std::fs;
 use std::io::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use lottie_core::prelude::{Animated, GradientFill, Stroke, TextRange, Transform, Vector2D};
+use glam::Vec3;
+use lottie_core::prelude::{
+    glyph_beziers, script_runs, shape_paragraph, visual_order, Animated, Bezier, BlendMode,
+    ColorList, DropShadow, Effect, EffectValue, FontChar, FontList, GaussianBlur, GlyphStyle,
+    GradientColor, GradientFill, KeyFrame, Layer, LayerCamera, LayerContent, LayerEffect,
+    MaskStroke, MatteMode, Model, MotionBlur, PathMeasure, RenderableContent, Rgba, Shape,
+    ShapeGroup, ShapeLayer, ShapedCluster, StagedLayer, Stroke, StrokePaintStyle,
+    StyledShapeIterator, TextAnimationData, TextBased, TextCaps, TextDocument, TextGrouping,
+    TextJustify, TextRange, TextRangeInfo, TextRangeUnits, TextShape, TextUnits,
+    TextVerticalJustify, Transform, Vector2D, Vector3D,
+};
+use lottie_core::{
+    Diagnostic, Error as LottieError, FontDB, FontFallbacks, FontOptions, FontProvider, Lerp,
+    Lottie, OfflineFontProvider,
+};
 
 #[test]
 fn test_transform_complex() -> Result<(), Error> {
@@ -39,6 +55,46 @@ fn test_gradient_fill() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_radial_gradient_fill() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/radial_gradient_fill.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let d: GradientFill = serde_path_to_error::deserialize(d).unwrap();
+    let focal = d.gradient.focal_point(0.0);
+    assert!(focal.x.abs() < 1e-3);
+    assert!((focal.y - 50.0).abs() < 1e-3);
+    assert!(d.gradient.offset(0.0, focal).abs() < 1e-3);
+    for point in [Vector2D::new(100.0, 0.0), Vector2D::new(0.0, -100.0)] {
+        assert!((d.gradient.offset(0.0, point) - 1.0).abs() < 1e-3);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_gradient_hard_stop_with_opacity() -> Result<(), Error> {
+    // Red up to the middle, then blue, with opacity stops of their own
+    let colors = serde_json::json!({
+        "p": 4,
+        "k": {
+            "a": 0,
+            "k": [
+                0, 1, 0, 0, 0.5, 1, 0, 0, 0.5, 0, 0, 1, 1, 0, 0, 1,
+                0, 1, 0.25, 0.5, 0.75, 0.5
+            ]
+        }
+    });
+    let colors: ColorList = serde_json::from_value(colors)?;
+    let stops = colors.colors.initial_value();
+    let offsets = stops.iter().map(|stop| stop.offset).collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0.0, 0.25, 0.5, 0.5, 0.75, 1.0]);
+    // Both sides of the hard edge are kept, with the opacity at their offset
+    let (red, blue) = (stops[2].color, stops[3].color);
+    assert_eq!((red.r, red.b, red.a), (255, 0, 127));
+    assert_eq!((blue.r, blue.b, blue.a), (0, 255, 127));
+    assert_eq!(stops[4].color.b, 255);
+    Ok(())
+}
+
 #[test]
 fn test_text_range() -> Result<(), Error> {
     let file = fs::File::open("../../fixtures/segments/text_range.json")?;
@@ -56,3 +112,1278 @@ fn test_legacy_animated_position() -> Result<(), Error> {
     println!("{:?}", d);
     Ok(())
 }
+
+#[test]
+fn test_matte_parent() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/matte_parent.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let d: Layer = serde_path_to_error::deserialize(d).unwrap();
+    assert_eq!(d.matte_mode, Some(MatteMode::Luma));
+    assert_eq!(d.matte_parent, Some(1));
+    assert!(!d.is_matte_source);
+    Ok(())
+}
+
+/// Staged items of the layers of a model fixture, with fonts of no directory
+fn staged_layers(name: &str) -> Result<Lottie, Error> {
+    let file = fs::File::open(format!("../../fixtures/segments/{}.json", name))?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let model: Model = serde_path_to_error::deserialize(d).unwrap();
+    let fontdb = FontDB::with_options(FontOptions::offline(vec![])).unwrap();
+    Ok(Lottie::with_fontdb(model, fontdb, "").unwrap())
+}
+
+fn staged_named<'a>(lottie: &'a Lottie, name: &str) -> Vec<&'a StagedLayer> {
+    lottie
+        .timeline()
+        .items()
+        .filter(|item| item.name.as_deref() == Some(name))
+        .collect()
+}
+
+#[test]
+fn test_matte_source_masks() -> Result<(), Error> {
+    let lottie = staged_layers("matte_source_masks")?;
+    let source = staged_named(&lottie, "Source");
+    // The mask of the source is staged before its content
+    assert_eq!(source.len(), 2);
+    let (mask, content) = (source[0], source[1]);
+    assert!(mask.is_mask && content.is_mask);
+    assert_eq!(content.mask_hierarchy.masks().len(), 1);
+    assert_eq!(content.mask_hierarchy.masks()[0].id, mask.id);
+    assert_eq!(content.mask_group, None);
+    assert_eq!(mask.mask_group, None);
+
+    // The matte is the masked content, not the mask of the source
+    let matted = staged_named(&lottie, "Matted");
+    assert_eq!(matted.len(), 1);
+    let mattes = matted[0].mask_hierarchy.masks();
+    assert_eq!(mattes.len(), 1);
+    assert_eq!(mattes[0].id, content.id);
+    assert_eq!(mattes[0].mode, MatteMode::Alpha);
+    Ok(())
+}
+
+#[test]
+fn test_matte_parent_layers() -> Result<(), Error> {
+    let lottie = staged_layers("matte_parent_layers")?;
+    let source = staged_named(&lottie, "Source")[0];
+    let between = staged_named(&lottie, "Between")[0];
+    assert!(source.is_mask);
+    // The layer between the matte source and its target is neither
+    assert!(!between.is_mask);
+    assert!(between.mask_hierarchy.is_empty());
+
+    let matted = staged_named(&lottie, "Matted")[0];
+    assert!(!matted.is_mask);
+    let mattes = matted.mask_hierarchy.masks();
+    assert_eq!(mattes.len(), 1);
+    assert_eq!(mattes[0].id, source.id);
+    assert_eq!(mattes[0].mode, MatteMode::InvertedAlpha);
+    Ok(())
+}
+
+#[test]
+fn test_nested_matte() -> Result<(), Error> {
+    let lottie = staged_layers("nested_matte")?;
+    let outer = staged_named(&lottie, "Outer")[0];
+    let source = staged_named(&lottie, "Source")[0];
+    assert!(outer.is_mask && source.is_mask);
+    // A matte source keeps its own track matte
+    let mattes = source.mask_hierarchy.masks();
+    assert_eq!(mattes.len(), 1);
+    assert_eq!(mattes[0].id, outer.id);
+    assert_eq!(mattes[0].mode, MatteMode::Alpha);
+
+    let matted = staged_named(&lottie, "Matted")[0];
+    let mattes = matted.mask_hierarchy.masks();
+    assert_eq!(mattes.len(), 1);
+    assert_eq!(mattes[0].id, source.id);
+    Ok(())
+}
+
+#[test]
+fn test_luma_matte_coverage() {
+    let white = [1.0, 1.0, 1.0, 1.0];
+    let black = [0.0, 0.0, 0.0, 1.0];
+    assert!((MatteMode::Luma.coverage(white) - 1.0).abs() < 1e-3);
+    assert_eq!(MatteMode::Luma.coverage(black), 0.0);
+    assert_eq!(MatteMode::Alpha.coverage(black), 1.0);
+    assert!((MatteMode::InvertedLuma.coverage(white)).abs() < 1e-3);
+    assert_eq!(MatteMode::InvertedLuma.coverage([1.0, 1.0, 1.0, 0.0]), 1.0);
+}
+
+#[test]
+fn test_gradient_lerp_different_stop_counts() {
+    let stop = |offset, v| GradientColor {
+        offset,
+        color: Rgba::new_u8(v, v, v, 255),
+    };
+    let from = vec![stop(0.0, 0), stop(1.0, 200)];
+    let to = vec![stop(0.0, 0), stop(0.5, 0), stop(1.0, 0)];
+    // `lerp` returns `other` at 0.0 and `self` at 1.0
+    let result = to.lerp(&from, 0.5);
+    assert_eq!(result.len(), 3);
+    assert_eq!(result[1].offset, 0.5);
+    assert_eq!(result[1].color.r, 50);
+    assert_eq!(result[2].color.r, 100);
+
+    // Both sides of a hard stop keep their own color
+    let from = vec![stop(0.0, 0), stop(0.5, 0), stop(0.5, 200), stop(1.0, 200)];
+    let to = vec![stop(0.0, 100), stop(1.0, 100)];
+    let result = to.lerp(&from, 0.5);
+    let offsets = result.iter().map(|stop| stop.offset).collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0.0, 0.5, 0.5, 1.0]);
+    assert_eq!(result[1].color.r, 50);
+    assert_eq!(result[2].color.r, 150);
+}
+
+#[test]
+fn test_blend_modes() {
+    let backdrop = [0.2, 0.5, 0.8];
+    let source = [0.9, 0.1, 0.4];
+    for (mode, expected) in [
+        (BlendMode::Multiply, [0.18, 0.05, 0.32]),
+        (BlendMode::Screen, [0.92, 0.55, 0.88]),
+        (BlendMode::Difference, [0.7, 0.4, 0.4]),
+        (BlendMode::Luminosity, [0.13, 0.43, 0.73]),
+    ] {
+        let result = mode.blend(backdrop, source);
+        assert!(result
+            .iter()
+            .zip(expected)
+            .all(|(a, b)| (a - b).abs() < 1e-4));
+    }
+    // A transparent backdrop leaves the source untouched
+    let result = BlendMode::Multiply.composite([0.0; 4], [0.9, 0.1, 0.4, 0.5]);
+    assert_eq!(result, [0.9, 0.1, 0.4, 0.5]);
+}
+
+#[test]
+fn test_layer_effects() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/layer_effects.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let d: Layer = serde_path_to_error::deserialize(d).unwrap();
+    assert_eq!(d.effects.len(), 2);
+    let shadow = match &d.effects[0] {
+        Effect::DropShadow(group) => group,
+        effect => panic!("unexpected effect {:?}", effect),
+    };
+    assert!(shadow.enabled);
+    assert_eq!(shadow.color(0).unwrap().initial_value().a, 255);
+    assert_eq!(shadow.number(2).unwrap().initial_value(), 135.0);
+    assert!(d.effects[0].is_supported());
+    let slider = &d.effects[1];
+    assert!(matches!(slider, Effect::Custom(_)));
+    assert!(!slider.enabled());
+    assert!(matches!(slider.group().values[1], EffectValue::Ignored(6)));
+    assert!(!slider.group().values[1].is_invalid());
+    // A malformed value is ignored instead of failing the layer
+    assert!(matches!(slider.group().values[2], EffectValue::Ignored(0)));
+    assert!(slider.group().values[2].is_invalid());
+    Ok(())
+}
+
+#[test]
+fn test_gaussian_blur() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/gaussian_blur.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let effect: Effect = serde_path_to_error::deserialize(d).unwrap();
+    assert!(effect.is_supported());
+    assert_eq!(
+        LayerEffect::resolve(&effect, 0.0),
+        Some(LayerEffect::GaussianBlur(GaussianBlur {
+            sigma_x: 0.0,
+            sigma_y: 0.0,
+            repeat_edge: false,
+        }))
+    );
+    let blur = match LayerEffect::resolve(&effect, 10.0) {
+        Some(LayerEffect::GaussianBlur(blur)) => blur,
+        effect => panic!("unexpected effect {:?}", effect),
+    };
+    // Horizontal only
+    assert!((blur.sigma_x - 6.0).abs() < 1e-4);
+    assert_eq!(blur.sigma_y, 0.0);
+
+    // A single opaque pixel is spread along its row, keeping its energy
+    let (width, height) = (41, 3);
+    let mut pixels = vec![0_u8; width * height * 4];
+    let center = (width + width / 2) * 4;
+    pixels[center..center + 4].copy_from_slice(&[255, 255, 255, 255]);
+    blur.apply(&mut pixels, width, height);
+    let alpha = |x: usize, y: usize| pixels[(y * width + x) * 4 + 3] as u32;
+    assert!(alpha(20, 1) < 255);
+    assert_eq!(alpha(19, 1), alpha(21, 1));
+    assert!(alpha(19, 1) < alpha(20, 1));
+    let total = (0..width).map(|x| alpha(x, 1)).sum::<u32>();
+    assert!((250..=260).contains(&total));
+    assert!((0..width).all(|x| alpha(x, 0) == 0 && alpha(x, 2) == 0));
+
+    // Edges fade out unless edge pixels are repeated
+    let opaque = vec![255_u8; width * height * 4];
+    let mut pixels = opaque.clone();
+    blur.apply(&mut pixels, width, height);
+    assert!(pixels[3] < 255);
+    let mut pixels = opaque.clone();
+    GaussianBlur {
+        repeat_edge: true,
+        ..blur
+    }
+    .apply(&mut pixels, width, height);
+    assert_eq!(pixels, opaque);
+    Ok(())
+}
+
+#[test]
+fn test_drop_shadow() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/layer_effects.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let d: Layer = serde_path_to_error::deserialize(d).unwrap();
+    let shadow = match LayerEffect::resolve(&d.effects[0], 0.0) {
+        Some(LayerEffect::DropShadow(shadow)) => shadow,
+        effect => panic!("unexpected effect {:?}", effect),
+    };
+    assert_eq!(shadow.color, Rgba::new_u8(0, 0, 0, 128));
+    // 135 degrees points to the bottom right
+    assert!((shadow.offset.x - 7.071).abs() < 1e-3);
+    assert!((shadow.offset.y - 7.071).abs() < 1e-3);
+    assert_eq!(shadow.sigma, 5.0);
+    assert!(!shadow.shadow_only);
+    // The disabled slider control is not rendered
+    assert_eq!(LayerEffect::resolve(&d.effects[1], 0.0), None);
+
+    // A hard shadow of a single opaque pixel, moved 2 pixels to the right
+    let (width, height) = (5, 1);
+    let mut pixels = vec![0_u8; width * height * 4];
+    pixels[4..8].copy_from_slice(&[255, 0, 0, 255]);
+    let shadow = DropShadow {
+        color: Rgba::new_u8(0, 0, 255, 255),
+        offset: Vector2D::new(2.0, 0.0),
+        sigma: 0.0,
+        shadow_only: false,
+    };
+    let mut result = pixels.clone();
+    shadow.apply(&mut result, width, height);
+    assert_eq!(&result[4..8], &[255, 0, 0, 255]);
+    assert_eq!(&result[12..16], &[0, 0, 255, 255]);
+    assert!(result[..4].iter().chain(&result[16..]).all(|v| *v == 0));
+    let mut result = pixels.clone();
+    DropShadow {
+        shadow_only: true,
+        ..shadow
+    }
+    .apply(&mut result, width, height);
+    assert_eq!(&result[4..8], &[0, 0, 0, 0]);
+    assert_eq!(&result[12..16], &[0, 0, 255, 255]);
+    Ok(())
+}
+
+#[test]
+fn test_color_effects() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/color_effects.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let d: Layer = serde_path_to_error::deserialize(d).unwrap();
+    assert!(d.effects.iter().all(|effect| effect.is_supported()));
+    let effects = d
+        .effects
+        .iter()
+        .map(|effect| LayerEffect::resolve(effect, 0.0).unwrap())
+        .collect::<Vec<_>>();
+    let close = |a: [f32; 4], b: [f32; 4]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-3);
+
+    let LayerEffect::Fill(fill) = &effects[0] else {
+        panic!("unexpected effect {:?}", effects[0]);
+    };
+    assert!(close(
+        fill.transform([0.0, 1.0, 0.0, 1.0]),
+        [1.0, 0.0, 0.0, 0.5]
+    ));
+    let mut pixels = vec![0, 128, 0, 128];
+    effects[0].apply(&mut pixels, 1, 1);
+    assert_eq!(pixels, vec![64, 0, 0, 64]);
+
+    // White is mapped to yellow, then mixed by half with the original color
+    let LayerEffect::Tint(tint) = &effects[1] else {
+        panic!("unexpected effect {:?}", effects[1]);
+    };
+    assert!(close(tint.transform([1.0; 4]), [1.0, 1.0, 0.5, 1.0]));
+
+    let LayerEffect::Tritone(tritone) = &effects[2] else {
+        panic!("unexpected effect {:?}", effects[2]);
+    };
+    assert!(close(
+        tritone.transform([0.5, 0.5, 0.5, 1.0]),
+        [0.0, 1.0, 0.0, 1.0]
+    ));
+    assert!(close(
+        tritone.transform([0.0, 0.0, 0.0, 1.0]),
+        [0.0, 0.0, 0.0, 1.0]
+    ));
+
+    // Input levels stretch the RGB range, red is then darkened
+    let LayerEffect::Levels(levels) = &effects[3] else {
+        panic!("unexpected effect {:?}", effects[3]);
+    };
+    assert!(close(
+        levels.transform([0.5, 0.5, 0.9, 1.0]),
+        [0.25, 0.5, 1.0, 1.0]
+    ));
+    assert!(close(
+        levels.transform([0.1, 0.1, 0.1, 0.5]),
+        [0.0, 0.0, 0.0, 0.5]
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_stroke_effect() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/stroke_effect.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let d: Layer = serde_path_to_error::deserialize(d).unwrap();
+    assert!(d.effects[0].is_supported());
+    let Effect::Stroke(group) = &d.effects[0] else {
+        panic!("unexpected effect {:?}", d.effects[0]);
+    };
+    let stroke = MaskStroke::new(group, &d.masks_properties, 0.0, 10.0).unwrap();
+    assert_eq!(stroke.paint_style, StrokePaintStyle::RevealOriginal);
+    // Both masks are drawn with 4 strokes for the soft brush
+    assert_eq!(stroke.shapes.len(), 8);
+    let parts = |index: usize| match &stroke.shapes[index].shape {
+        Shape::Group { shapes } => match (&shapes[1].shape, &shapes[2].shape) {
+            (Shape::Trim(trim), Shape::Stroke(stroke)) => (trim.clone(), stroke.clone()),
+            shape => panic!("unexpected shapes {:?}", shape),
+        },
+        shape => panic!("unexpected shape {:?}", shape),
+    };
+
+    let (_, inner) = parts(0);
+    let (trim, outer) = parts(3);
+    assert_eq!(inner.width.initial_value(), 6.25);
+    assert_eq!(outer.width.initial_value(), 10.0);
+    // The brush hardness is animated, so the soft edge narrows over time
+    assert!((inner.width.value(5.0) - 8.125).abs() < 1e-3);
+    assert!((inner.width.value(10.0) - 10.0).abs() < 1e-3);
+    assert_eq!(outer.color.initial_value().r, 255);
+    // Stacked strokes add up to the opacity of the effect
+    let opacity = outer.opacity.initial_value() / 100.0;
+    assert!((1.0 - (1.0 - opacity).powi(4) - 0.5).abs() < 1e-4);
+
+    // The first mask is a quarter of the total length, so it is fully drawn
+    // once the end reaches 25%
+    assert_eq!(trim.start.initial_value(), 0.0);
+    assert!((trim.end.value(2.0) - 80.0).abs() < 1e-3);
+    assert_eq!(trim.end.value(5.0), 100.0);
+    let (trim, _) = parts(4);
+    assert_eq!(trim.end.value(2.0), 0.0);
+    assert!((trim.end.value(5.0) - 100.0 / 3.0).abs() < 1e-3);
+    assert!((trim.end.value(10.0) - 100.0).abs() < 1e-3);
+    Ok(())
+}
+
+#[test]
+fn test_stroke_effect_on_transparent() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/stroke_effect.json")?;
+    let mut layer: serde_json::Value = serde_json::from_reader(file)?;
+    // Paint the stroke on transparent, with the first mask clipping the layer
+    layer["ef"][0]["ef"][10]["v"]["k"] = 2.into();
+    layer["masksProperties"][0]["mode"] = "a".into();
+    let model = serde_json::json!({
+        "v": "5.7.4",
+        "fr": 30,
+        "ip": 0,
+        "op": 10,
+        "w": 200,
+        "h": 400,
+        "ddd": 0,
+        "assets": [],
+        "layers": [layer],
+    });
+    let model: Model = serde_json::from_value(model)?;
+    let fontdb = FontDB::with_options(FontOptions::offline(vec![])).unwrap();
+    let lottie = Lottie::with_fontdb(model, fontdb, "").unwrap();
+
+    let items = staged_named(&lottie, "Handwriting");
+    let shapes = |item: &StagedLayer| match &item.content {
+        RenderableContent::Shape(group) => group.shapes.len(),
+        content => panic!("unexpected content {:?}", content),
+    };
+    // The masks are followed by the stroke and the emptied content
+    assert_eq!(items.len(), 4);
+    let (stroke, content) = (items[2], items[3]);
+    assert!(!stroke.is_mask);
+    assert_eq!(shapes(stroke), 8);
+    assert!(stroke.mask_hierarchy.is_empty());
+    assert_eq!(shapes(content), 0);
+    assert_eq!(content.mask_hierarchy.masks().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_camera_layers() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/camera_layers.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let layers: Vec<Layer> = serde_path_to_error::deserialize(d).unwrap();
+    assert!(layers[2].is_3d);
+    // Y rotations turn the right side of a layer away from the viewer
+    let card = layers[2].transform.as_ref().unwrap().value(0.0);
+    let right = card.transform_point3(Vec3::new(10.0, 0.0, 0.0));
+    assert!(right.abs_diff_eq(Vec3::new(100.0, 50.0, 260.0), 1e-3));
+
+    assert!(LayerCamera::new(&layers[1], &layers, 200.0, 100.0).is_none());
+    let camera = LayerCamera::new(&layers[0], &layers, 200.0, 100.0).unwrap();
+    let close = |a: Option<Vector2D>, b: Vector2D| (a.unwrap() - b).length() < 1e-3;
+    // The camera is moved by its parent, looking at the center of the
+    // composition from the distance of its zoom
+    assert!(close(
+        camera.project(Vector3D::new(110.0, 50.0, 0.0), 0.0),
+        Vector2D::new(110.0, 50.0)
+    ));
+    // Twice as far from the camera, offsets from the center are halved
+    assert!(close(
+        camera.project(Vector3D::new(110.0, 70.0, 500.0), 0.0),
+        Vector2D::new(105.0, 60.0)
+    ));
+    assert!(camera
+        .project(Vector3D::new(100.0, 50.0, -600.0), 0.0)
+        .is_none());
+    assert!((camera.depth(Vector3D::new(100.0, 50.0, 250.0), 0.0) - 750.0).abs() < 1e-3);
+    Ok(())
+}
+
+#[test]
+fn test_motion_blur() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/motion_blur.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let model: Model = serde_path_to_error::deserialize(d).unwrap();
+    assert!(model.layers[0].motion_blur);
+    assert!(!model.layers[1].motion_blur);
+    let motion_blur = model.motion_blur.unwrap();
+    assert_eq!(motion_blur.samples_per_frame, 4);
+    // A 360 degrees shutter opening on the frame spans until the next one
+    let offsets = (0..4)
+        .map(|index| motion_blur.sample_offset(index))
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0.125, 0.375, 0.625, 0.875]);
+    // After Effects defaults center a half frame shutter on the frame
+    let motion_blur = MotionBlur::default();
+    assert_eq!(motion_blur.sample_offset(0), -0.25 + 0.5 / 32.0);
+    assert_eq!(motion_blur.sample_offset(15), 0.25 - 0.5 / 32.0);
+    Ok(())
+}
+
+#[test]
+fn test_text_range_selector() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/text_range.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let range: TextRange = serde_path_to_error::deserialize(d).unwrap();
+    let lines = vec!["hello world".chars().collect::<Vec<_>>(), vec!['a', 'b']];
+
+    // Spaces belong to the word before them, line breaks end words
+    let words = TextUnits::new(&lines, TextBased::Words, false);
+    assert_eq!(words.count, 3);
+    assert_eq!(words.indices[5], 0);
+    assert_eq!(words.indices[6], 1);
+    assert_eq!(words.indices[11], 2);
+    let letters = TextUnits::new(&lines, TextBased::CharactersExcludingSpaces, false);
+    assert_eq!(letters.count, 12);
+    assert_eq!(letters.indices[5], letters.indices[6]);
+    let lines_units = TextUnits::new(&lines, TextBased::Lines, false);
+    assert_eq!(lines_units.count, 2);
+    let mut random = TextUnits::new(&lines, TextBased::Characters, true).indices;
+    random.sort();
+    assert_eq!(random, (0..13).collect::<Vec<_>>());
+
+    // The start and end are swapped and shifted by the offset, selecting
+    // units 3 to 19
+    let selector = &range.selector;
+    assert_eq!(selector.range_units, TextRangeUnits::Index);
+    let influences = (0..24)
+        .map(|index| selector.influence(index, 24, 0.0))
+        .collect::<Vec<_>>();
+    assert_eq!(influences[2], 0.0);
+    assert_eq!(influences[3], 1.0);
+    assert_eq!(influences[19], 1.0);
+    assert_eq!(influences[20], 0.0);
+
+    let mut selector = selector.clone();
+    selector.range_units = TextRangeUnits::Percent;
+    selector.start = None;
+    selector.end = None;
+    selector.offset = None;
+    selector.shape = TextShape::RampUp;
+    assert_eq!(selector.influence(1, 4, 0.0), 0.375);
+    selector.shape = TextShape::Triangle;
+    assert_eq!(selector.influence(1, 4, 0.0), 0.75);
+    assert_eq!(selector.influence(2, 4, 0.0), 0.75);
+    selector.max_amount = Animated::from_value(-50.0);
+    assert_eq!(selector.influence(1, 4, 0.0), -0.375);
+    Ok(())
+}
+
+#[test]
+fn test_text_animator() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/text_range.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let mut range: TextRange = serde_path_to_error::deserialize(d).unwrap();
+    let animator = range.style.as_mut().unwrap();
+    animator.opacity = Some(Animated::from_value(50.0));
+    animator.position = Some(Animated::from_value(Vector3D::new(10.0, 0.0, 0.0)));
+    let lines = vec!["hello world".chars().collect::<Vec<_>>(), vec!['a', 'b']];
+    let units = TextUnits::new(&lines, TextBased::Characters, false);
+    let info = |index| TextRangeInfo {
+        value: lines.clone().into(),
+        index,
+        ranges: vec![range.clone()].into(),
+        units: vec![units.clone()].into(),
+        center: 5.0,
+        group_size: Vector2D::new(10.0, 36.0),
+        alignment: None,
+    };
+
+    // The first glyphs are outside of the range
+    let mut style = GlyphStyle::default();
+    info((0, 0)).apply(&mut style, 0.0);
+    assert_eq!(style, GlyphStyle::default());
+
+    let mut style = GlyphStyle::default();
+    info((0, 3)).apply(&mut style, 0.0);
+    assert_eq!(style.opacity, 0.5);
+    assert_eq!(style.transform.w_axis.truncate(), Vec3::new(10.0, 0.0, 0.0));
+    let fill_color = [0.196, 0.314, 0.69];
+    for (channel, expected) in style.fill_color.into_iter().zip(fill_color) {
+        assert!((channel - expected).abs() < 1.0 / 255.0);
+    }
+    assert_eq!(style.stroke_color, [1.0, 0.0, 0.0]);
+    Ok(())
+}
+
+#[test]
+fn test_text_animator_blur() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/text_range.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let mut range: TextRange = serde_path_to_error::deserialize(d).unwrap();
+    // The first two glyphs are blurred from 0 to 10 over 10 frames
+    range.selector.start = Some(Animated::from_value(0.0));
+    range.selector.end = Some(Animated::from_value(2.0));
+    range.selector.offset = None;
+    let keyframe = |start_value, end_value, start_frame| KeyFrame {
+        start_value,
+        end_value,
+        start_frame,
+        end_frame: start_frame + 10.0,
+        easing_out: None,
+        easing_in: None,
+    };
+    range.style.as_mut().unwrap().blur = Some(Animated {
+        animated: true,
+        keyframes: vec![keyframe(0.0, 10.0, 0.0), keyframe(10.0, 10.0, 10.0)],
+    });
+    let lines = vec!["ABC".chars().collect::<Vec<_>>()];
+    let units = TextUnits::new(&lines, TextBased::Characters, false);
+    let layer = |name: Option<&str>, shape| ShapeLayer {
+        name: name.map(str::to_string),
+        hidden: false,
+        shape,
+    };
+    // Glyphs of a line, followed by the transform of the line
+    let mut shapes = lines[0]
+        .iter()
+        .enumerate()
+        .map(|(index, c)| {
+            let path = Shape::Path {
+                d: Animated::from_value(vec![]),
+                text_range: Some(TextRangeInfo {
+                    value: lines.clone().into(),
+                    index: (0, index),
+                    ranges: vec![range.clone()].into(),
+                    units: vec![units.clone()].into(),
+                    center: 5.0,
+                    group_size: Vector2D::new(10.0, 36.0),
+                    alignment: None,
+                }),
+            };
+            let shapes = vec![layer(None, path)];
+            layer(Some(&c.to_string()), Shape::Group { shapes })
+        })
+        .collect::<Vec<_>>();
+    shapes.push(layer(None, Shape::Transform(Transform::default())));
+    let line = layer(Some("ABC"), Shape::Group { shapes });
+    let root = layer(None, Shape::Group { shapes: vec![line] });
+    let mut content = RenderableContent::Shape(ShapeGroup { shapes: vec![root] });
+    let blurred = content.take_blurred_glyphs(60.0);
+
+    // Blurred glyphs are taken out of their line with its transform
+    let line_shapes = |content: &RenderableContent| {
+        let RenderableContent::Shape(group) = content else {
+            panic!("text is not a shape");
+        };
+        let Shape::Group { shapes: lines } = &group.shapes[0].shape else {
+            panic!("not a group");
+        };
+        assert_eq!(lines[0].name.as_deref(), Some("ABC"));
+        let Shape::Group { shapes } = &lines[0].shape else {
+            panic!("not a group");
+        };
+        assert!(matches!(shapes.last().unwrap().shape, Shape::Transform(_)));
+        shapes
+            .iter()
+            .filter_map(|shape| shape.name.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(line_shapes(&content), vec!["C"]);
+    let names = blurred
+        .iter()
+        .map(|(glyph, _)| line_shapes(glyph))
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![vec!["A"], vec!["B"]]);
+
+    // The blur is sampled at every frame
+    let (_, effect) = &blurred[0];
+    let sigma = |frame: f32| match LayerEffect::resolve(effect, frame) {
+        Some(LayerEffect::GaussianBlur(blur)) => {
+            assert_eq!(blur.sigma_x, blur.sigma_y);
+            blur.sigma_x
+        }
+        effect => panic!("unexpected effect {:?}", effect),
+    };
+    assert_eq!(sigma(0.0), 0.0);
+    assert!((sigma(5.0) - 1.5).abs() < 1e-4);
+    assert!((sigma(30.0) - 3.0).abs() < 1e-4);
+    Ok(())
+}
+
+#[test]
+fn test_text_box() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/text_document_box.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let document: TextDocument = serde_path_to_error::deserialize(d).unwrap();
+    assert_eq!(document.vertical_justify, TextVerticalJustify::Center);
+    let text_box = document.text_box().unwrap();
+    assert_eq!(text_box.origin.to_tuple(), (-120.0, -60.0));
+    assert_eq!(text_box.size.to_tuple(), (240.0, 120.0));
+    assert!(matches!(document.caps, TextCaps::SmallCaps));
+    assert_eq!(document.tracking, 50.0);
+    assert_eq!(document.line_height, Some(43.2));
+    assert_eq!(document.stroke_color, Rgba::new_u8(255, 0, 0, 255));
+    assert_eq!(document.stroke_width, 2.0);
+    assert!(document.stroke_above_fill);
+
+    // Point text has no box
+    assert!(TextDocument::default().text_box().is_none());
+    Ok(())
+}
+
+/// Text layer of the `text_layout` fixture, whose font only has the embedded
+/// characters "A", "B" and " "
+fn text_layout_fixture() -> Result<(Model, Layer, TextAnimationData), Error> {
+    let file = fs::File::open("../../fixtures/segments/text_layout.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let model: Model = serde_path_to_error::deserialize(d).unwrap();
+    let layer = model.layers[0].clone();
+    let LayerContent::Text(text) = layer.content.clone() else {
+        panic!("not a text layer");
+    };
+    Ok((model, layer, text))
+}
+
+/// Lines of `text` laid out without any installed font
+fn lay_out_text(model: &Model, layer: &Layer, text: &TextAnimationData) -> Vec<ShapeLayer> {
+    let fontdb = FontDB::with_options(FontOptions::offline(vec![])).unwrap();
+    let mut diagnostics = vec![];
+    let content =
+        RenderableContent::from_text(text, layer, model, &fontdb, &mut diagnostics).unwrap();
+    assert!(diagnostics.is_empty());
+    let RenderableContent::Shape(group) = &content.keyframes[0].start_value else {
+        panic!("text is not a shape");
+    };
+    group_shapes(&group.shapes[0]).clone()
+}
+
+fn group_shapes(shape: &ShapeLayer) -> &Vec<ShapeLayer> {
+    match &shape.shape {
+        Shape::Group { shapes } => shapes,
+        _ => panic!("not a group"),
+    }
+}
+
+/// Transform of a line or of a glyph
+fn group_transform(shape: &ShapeLayer) -> &Transform {
+    group_shapes(shape)
+        .iter()
+        .find_map(|shape| match &shape.shape {
+            Shape::Transform(transform) => Some(transform),
+            _ => None,
+        })
+        .unwrap()
+}
+
+fn group_position(shape: &ShapeLayer) -> Vector3D {
+    group_transform(shape)
+        .position
+        .as_ref()
+        .unwrap()
+        .initial_value()
+}
+
+#[test]
+fn test_text_box_layout() -> Result<(), Error> {
+    let (model, layer, mut text) = text_layout_fixture()?;
+    let document = &mut text.document.keyframes[0].start_value;
+    document.box_size = vec![250.0, 250.0];
+    document.box_position = vec![0.0, 0.0];
+    let lines = lay_out_text(&model, &layer, &text);
+    // Lines break at their last space, which is dropped
+    let names = lines
+        .iter()
+        .map(|line| line.name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![Some("AB AB".to_string()), Some("BA".to_string())]
+    );
+    let offsets = group_shapes(&lines[0])
+        .iter()
+        .filter(|glyph| glyph.name.is_some())
+        .map(|glyph| group_position(glyph).x)
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0.0, 60.0, 110.0, 130.0, 190.0]);
+
+    // Lines are 120 high, and the first baseline is one ascent of 75 below
+    // the top of the box
+    let baselines = |text: &TextAnimationData| {
+        lay_out_text(&model, &layer, text)
+            .iter()
+            .map(|line| group_position(line).y)
+            .collect::<Vec<_>>()
+    };
+    let mut vertical = |justify: TextVerticalJustify| {
+        text.document.keyframes[0].start_value.vertical_justify = justify;
+        baselines(&text)
+    };
+    assert_eq!(vertical(TextVerticalJustify::Top), vec![75.0, 195.0]);
+    assert_eq!(vertical(TextVerticalJustify::Center), vec![80.0, 200.0]);
+    assert_eq!(vertical(TextVerticalJustify::Bottom), vec![85.0, 205.0]);
+
+    // Lines overflowing the bottom of the box are hidden
+    text.document.keyframes[0].start_value.box_size = vec![250.0, 200.0];
+    let mut vertical = |justify: TextVerticalJustify| {
+        text.document.keyframes[0].start_value.vertical_justify = justify;
+        baselines(&text)
+    };
+    assert_eq!(vertical(TextVerticalJustify::Top), vec![75.0]);
+    assert_eq!(vertical(TextVerticalJustify::Center), vec![115.0]);
+    assert_eq!(vertical(TextVerticalJustify::Bottom), vec![155.0]);
+    Ok(())
+}
+
+/// Start of the glyphs of each line of `text`
+fn glyph_offsets(model: &Model, layer: &Layer, text: &TextAnimationData) -> Vec<Vec<f32>> {
+    lay_out_text(model, layer, text)
+        .iter()
+        .map(|line| {
+            group_shapes(line)
+                .iter()
+                .filter(|glyph| glyph.name.is_some())
+                .map(|glyph| group_position(glyph).x)
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_text_line_layout() -> Result<(), Error> {
+    let (model, layer, mut text) = text_layout_fixture()?;
+    let document = &mut text.document.keyframes[0].start_value;
+    document.box_size = vec![250.0, 250.0];
+    document.box_position = vec![0.0, 0.0];
+    let mut justify = |justify: TextJustify| {
+        text.document.keyframes[0].start_value.justify = justify;
+        glyph_offsets(&model, &layer, &text)
+    };
+    // Wrapped lines are stretched to the box by their inner spaces, and the
+    // last line of the paragraph is aligned on its own
+    let full = vec![0.0, 60.0, 110.0, 140.0, 200.0];
+    assert_eq!(
+        justify(TextJustify::LastLineFull),
+        vec![full.clone(), vec![0.0, 50.0]]
+    );
+    assert_eq!(
+        justify(TextJustify::LastLineLeft),
+        vec![full.clone(), vec![0.0, 50.0]]
+    );
+    assert_eq!(
+        justify(TextJustify::LastLineRight),
+        vec![full.clone(), vec![140.0, 190.0]]
+    );
+    assert_eq!(
+        justify(TextJustify::LastLineCenter),
+        vec![full, vec![70.0, 120.0]]
+    );
+    // Other modes don't stretch lines
+    assert_eq!(
+        justify(TextJustify::Right),
+        vec![vec![10.0, 70.0, 120.0, 140.0, 200.0], vec![140.0, 190.0]]
+    );
+
+    // Small caps are capitals scaled down
+    let (model, layer, mut text) = text_layout_fixture()?;
+    let document = &mut text.document.keyframes[0].start_value;
+    document.value = "ab".to_string();
+    document.caps = TextCaps::SmallCaps;
+    let lines = lay_out_text(&model, &layer, &text);
+    assert_eq!(lines[0].name.as_deref(), Some("AB"));
+    let offsets = glyph_offsets(&model, &layer, &text);
+    assert!((offsets[0][1] - 42.0).abs() < 1e-4);
+    let Shape::Path { d, .. } = &group_shapes(&group_shapes(&lines[0])[0])[0].shape else {
+        panic!("not a path");
+    };
+    let corner = d.initial_value()[0].verticies[2];
+    assert!((corner - Vector2D::new(42.0, -49.0)).length() < 1e-4);
+
+    // Tracking is added after each glyph, in thousandths of the size
+    let (model, layer, mut text) = text_layout_fixture()?;
+    text.document.keyframes[0].start_value.tracking = 100.0;
+    assert_eq!(
+        glyph_offsets(&model, &layer, &text),
+        vec![vec![0.0, 70.0, 130.0, 160.0, 230.0, 290.0, 320.0, 380.0]]
+    );
+    // but not after the last glyph of an aligned line
+    text.document.keyframes[0].start_value.justify = TextJustify::Right;
+    assert_eq!(
+        glyph_offsets(&model, &layer, &text),
+        vec![vec![
+            -440.0, -370.0, -310.0, -280.0, -210.0, -150.0, -120.0, -60.0
+        ]]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_text_stroke_order() -> Result<(), Error> {
+    let (model, layer, mut text) = text_layout_fixture()?;
+    let document = &mut text.document.keyframes[0].start_value;
+    document.stroke_width = 2.0;
+    document.stroke_color = Rgba::new_u8(255, 0, 0, 255);
+    // Whether the groups of the first glyph are stroked, in the order they
+    // are drawn from the top, as the renderer draws the first sibling above
+    // the next ones
+    let mut stroked = |stroke_above_fill: bool| {
+        text.document.keyframes[0].start_value.stroke_above_fill = stroke_above_fill;
+        let lines = lay_out_text(&model, &layer, &text);
+        let glyph = ShapeGroup {
+            shapes: group_shapes(&group_shapes(&lines[0])[0]).clone(),
+        };
+        glyph
+            .styled_shapes()
+            .map(|styled| {
+                let shapes = group_shapes(&styled.shape);
+                let has = |stroke: bool| {
+                    shapes.iter().any(|shape| match shape.shape {
+                        Shape::Stroke(_) => stroke,
+                        Shape::Fill(_) => !stroke,
+                        _ => false,
+                    })
+                };
+                // Each group has a single style
+                assert_ne!(has(true), has(false));
+                has(true)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(stroked(true), vec![true, false]);
+    assert_eq!(stroked(false), vec![false, true]);
+
+    // Without a stroke, the glyph is a single filled path
+    text.document.keyframes[0].start_value.stroke_width = 0.0;
+    let lines = lay_out_text(&model, &layer, &text);
+    let glyph = group_shapes(&group_shapes(&lines[0])[0]);
+    assert!(matches!(glyph[0].shape, Shape::Path { .. }));
+    assert!(matches!(glyph[1].shape, Shape::Fill(_)));
+    Ok(())
+}
+
+#[test]
+fn test_text_path_layout() -> Result<(), Error> {
+    // "AB" along the square mask of the layer, 400 long
+    let (model, layer, mut text) = text_layout_fixture()?;
+    text.document.keyframes[0].start_value.value = "AB".to_string();
+    text.follow_path.mask = Some(0);
+    // Center of each glyph on the path, and its rotation
+    let placed = |text: &TextAnimationData| {
+        let lines = lay_out_text(&model, &layer, text);
+        // Glyphs are moved to their line on their own
+        assert_eq!(group_position(&lines[0]), Vector3D::zero());
+        group_shapes(&lines[0])
+            .iter()
+            .filter(|glyph| glyph.name.is_some())
+            .map(|glyph| {
+                let transform = group_transform(glyph);
+                let position = group_position(glyph);
+                let anchor = transform.anchor.as_ref().unwrap().initial_value();
+                (
+                    Vector2D::new(position.x, position.y),
+                    transform.rotation.initial_value(),
+                    Vector2D::new(anchor.x, anchor.y),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let assert_placed = |text: &TextAnimationData, expected: [(Vector2D, f32); 2]| {
+        for ((point, angle, _), (expected_point, expected_angle)) in
+            placed(text).into_iter().zip(expected)
+        {
+            assert!((point - expected_point).length() < 0.01);
+            assert!((angle - expected_angle).abs() < 0.01);
+        }
+    };
+    let margin = |value: f32| Some(Animated::from_value(value));
+
+    // Glyphs start at the first margin
+    text.follow_path.first_margin = margin(10.0);
+    assert_placed(
+        &text,
+        [
+            (Vector2D::new(40.0, 0.0), 0.0),
+            (Vector2D::new(95.0, 0.0), 0.0),
+        ],
+    );
+    // and turn with the path
+    text.follow_path.first_margin = margin(60.0);
+    assert_placed(
+        &text,
+        [
+            (Vector2D::new(90.0, 0.0), 0.0),
+            (Vector2D::new(100.0, 45.0), 90.0),
+        ],
+    );
+    // unless they are not perpendicular to it
+    text.follow_path.perpendicular = margin(0.0);
+    assert_placed(
+        &text,
+        [
+            (Vector2D::new(90.0, 0.0), 0.0),
+            (Vector2D::new(100.0, 45.0), 0.0),
+        ],
+    );
+    text.follow_path.perpendicular = None;
+
+    // Right aligned text ends at the last margin
+    text.follow_path.first_margin = None;
+    text.follow_path.last_margin = margin(10.0);
+    text.document.keyframes[0].start_value.justify = TextJustify::Right;
+    assert_placed(
+        &text,
+        [
+            (Vector2D::new(0.0, 90.0), -90.0),
+            (Vector2D::new(0.0, 35.0), -90.0),
+        ],
+    );
+
+    // Forced alignment spreads the glyphs from the first to the last margin
+    text.follow_path.first_margin = margin(10.0);
+    text.follow_path.last_margin = margin(20.0);
+    text.follow_path.force_alignment = margin(1.0);
+    assert_placed(
+        &text,
+        [
+            (Vector2D::new(40.0, 0.0), 0.0),
+            (Vector2D::new(0.0, 45.0), -90.0),
+        ],
+    );
+
+    // Glyphs turn around their center on the baseline
+    text.document.keyframes[0].start_value.baseline_shift = 10.0;
+    let anchors = placed(&text)
+        .into_iter()
+        .map(|(_, _, anchor)| anchor)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        anchors,
+        vec![Vector2D::new(30.0, 10.0), Vector2D::new(25.0, 10.0)]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_text_missing_glyph() -> Result<(), Error> {
+    let (model, layer, mut text) = text_layout_fixture()?;
+    text.document.keyframes[0].start_value.value = "ACB".to_string();
+    // Without any installed font, the missing character is left out
+    let fontdb = FontDB::with_options(FontOptions::offline(vec![])).unwrap();
+    let mut diagnostics = vec![];
+    let content =
+        RenderableContent::from_text(&text, &layer, &model, &fontdb, &mut diagnostics).unwrap();
+    let RenderableContent::Shape(group) = &content.keyframes[0].start_value else {
+        panic!("text is not a shape");
+    };
+    let line = &group_shapes(&group.shapes[0])[0];
+    assert_eq!(line.name.as_deref(), Some("ACB"));
+    let glyphs = &group_shapes(line)[..3];
+    let offsets = glyphs
+        .iter()
+        .map(|glyph| group_position(glyph).x)
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0.0, 60.0, 60.0]);
+    let Shape::Path { d, .. } = &group_shapes(&glyphs[1])[0].shape else {
+        panic!("not a path");
+    };
+    assert!(d.initial_value().is_empty());
+    assert_eq!(
+        diagnostics,
+        vec![Diagnostic::MissingGlyph {
+            layer: Some("Text".to_string()),
+            font: "Test-Regular".to_string(),
+            character: 'C',
+            fallback: None,
+        }]
+    );
+
+    // `Lottie::new` falls back to the bundled Fira Mono
+    let lottie = Lottie::new(model.clone(), fontkit::FontKit::new(), "").unwrap();
+    let mut diagnostics = vec![];
+    let content =
+        RenderableContent::from_text(&text, &layer, &model, lottie.fontdb(), &mut diagnostics)
+            .unwrap();
+    let RenderableContent::Shape(group) = &content.keyframes[0].start_value else {
+        panic!("text is not a shape");
+    };
+    let line = &group_shapes(&group.shapes[0])[0];
+    let glyphs = &group_shapes(line)[..3];
+    let offsets = glyphs
+        .iter()
+        .map(|glyph| group_position(glyph).x)
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, vec![0.0, 60.0, 120.0]);
+    let Shape::Path { d, .. } = &group_shapes(&glyphs[1])[0].shape else {
+        panic!("not a path");
+    };
+    assert!(!d.initial_value().is_empty());
+    assert!(matches!(
+        &diagnostics[..],
+        [Diagnostic::MissingGlyph { fallback: Some(fallback), .. }] if fallback == "Fira Mono"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_text_follow_path() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/text_follow_path.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let text: TextAnimationData = serde_path_to_error::deserialize(d).unwrap();
+    assert_eq!(text.follow_path.mask, Some(0));
+    assert_eq!(text.options.grouping, TextGrouping::Words);
+    let first_margin = text.follow_path.first_margin.unwrap();
+    assert_eq!(first_margin.initial_value(), 10.0);
+
+    // A square of 100 by 100, starting at its top left corner
+    let square = vec![Bezier {
+        closed: true,
+        verticies: vec![
+            Vector2D::new(0.0, 0.0),
+            Vector2D::new(100.0, 0.0),
+            Vector2D::new(100.0, 100.0),
+            Vector2D::new(0.0, 100.0),
+        ],
+        in_tangent: vec![Vector2D::zero(); 4],
+        out_tangent: vec![Vector2D::zero(); 4],
+    }];
+    let close = |(point, angle): (Vector2D, f32), expected: Vector2D, expected_angle: f32| {
+        assert!((point - expected).length() < 0.01);
+        assert!((angle - expected_angle).abs() < 0.01);
+    };
+    let measure = PathMeasure::new(&square, false);
+    assert!((measure.length() - 400.0).abs() < 0.01);
+    close(measure.sample(150.0), Vector2D::new(100.0, 50.0), 90.0);
+    // Closed paths wrap around
+    close(measure.sample(450.0), Vector2D::new(50.0, 0.0), 0.0);
+    let reversed = PathMeasure::new(&square, true);
+    close(reversed.sample(50.0), Vector2D::new(0.0, 50.0), 90.0);
+    Ok(())
+}
+
+#[test]
+fn test_text_shaping_runs() {
+    let runs = |text: &str| {
+        script_runs(text)
+            .into_iter()
+            .map(|(range, script)| (range, script.short_name()))
+            .collect::<Vec<_>>()
+    };
+    // Spaces and punctuation belong to the run they follow
+    assert_eq!(runs("Hello שלום!"), vec![(0..6, "Latn"), (6..15, "Hebr")]);
+    // Leading digits take the script of the text after them
+    assert_eq!(runs("1 abc"), vec![(0..5, "Latn")]);
+    // Right-to-left runs are reversed, nested left-to-right ones are not
+    assert_eq!(visual_order(&[0, 0, 1, 1, 1, 0]), vec![0, 1, 4, 3, 2, 5]);
+    assert_eq!(visual_order(&[1, 2, 2, 1]), vec![3, 1, 2, 0]);
+}
+
+#[test]
+fn test_text_shaping() -> Result<(), Error> {
+    // Fira Mono, and a font with a square `A` glyph only
+    let fira = fs::read("../../assets/FiraMono-Regular.ttf")?;
+    let square = fs::read("../../fixtures/fonts/SquareA.ttf")?;
+    let faces = [
+        rustybuzz::Face::from_slice(&fira, 0).unwrap(),
+        rustybuzz::Face::from_slice(&square, 0).unwrap(),
+    ];
+    let ranges = |clusters: &[ShapedCluster]| {
+        clusters
+            .iter()
+            .map(|cluster| cluster.range.clone())
+            .collect::<Vec<_>>()
+    };
+    let faces_of = |clusters: &[ShapedCluster]| {
+        clusters
+            .iter()
+            .map(|cluster| cluster.face)
+            .collect::<Vec<_>>()
+    };
+
+    // A base letter and its mark make a single cluster
+    let clusters = shape_paragraph(&faces, "e\u{301}x", |_| vec![0]);
+    assert_eq!(ranges(&clusters), vec![0..3, 3..4]);
+
+    // Right-to-left clusters come out in the order of the text
+    let clusters = shape_paragraph(&faces, "a אב", |_| vec![0]);
+    assert_eq!(ranges(&clusters), vec![0..1, 1..2, 2..4, 4..6]);
+    let levels = clusters
+        .iter()
+        .map(|cluster| cluster.level)
+        .collect::<Vec<_>>();
+    assert_eq!(levels, vec![0, 0, 1, 1]);
+
+    // Clusters a face has no glyph for are shaped with the next face of the
+    // chain, and kept with glyph 0 at the end of the chain
+    let clusters = shape_paragraph(&faces, "AB", |_| vec![1, 0]);
+    assert_eq!(ranges(&clusters), vec![0..1, 1..2]);
+    assert_eq!(faces_of(&clusters), vec![1, 0]);
+    assert_eq!(clusters[0].glyphs[0].id, 1);
+    assert_ne!(clusters[1].glyphs[0].id, 0);
+    let clusters = shape_paragraph(&faces, "AB", |_| vec![1]);
+    assert_eq!(faces_of(&clusters), vec![1, 1]);
+    assert_eq!(clusters[1].glyphs[0].id, 0);
+
+    // Outlines are scaled and flipped, closed contours not repeating their
+    // first point
+    let beziers = glyph_beziers(&faces[1], 1, 0.1, Vector2D::new(10.0, 0.0));
+    assert_eq!(beziers.len(), 1);
+    assert!(beziers[0].closed);
+    assert_eq!(
+        beziers[0].verticies,
+        vec![
+            Vector2D::new(20.0, 0.0),
+            Vector2D::new(20.0, -70.0),
+            Vector2D::new(60.0, -70.0),
+            Vector2D::new(60.0, 0.0),
+        ]
+    );
+    assert!(beziers[0]
+        .in_tangent
+        .iter()
+        .chain(&beziers[0].out_tangent)
+        .all(|tangent| *tangent == Vector2D::zero()));
+    // Quadratic curves are elevated to cubic ones
+    let o = shape_paragraph(&faces, "o", |_| vec![0])[0].glyphs[0].id;
+    let beziers = glyph_beziers(&faces[0], o, 0.1, Vector2D::zero());
+    assert_eq!(beziers.len(), 2);
+    for bezier in &beziers {
+        assert!(bezier.closed);
+        assert_eq!(bezier.in_tangent.len(), bezier.verticies.len());
+        assert_eq!(bezier.out_tangent.len(), bezier.verticies.len());
+        assert_ne!(bezier.verticies.first(), bezier.verticies.last());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_font_chars() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/font_chars.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let chars: Vec<FontChar> = serde_path_to_error::deserialize(d).unwrap();
+    assert_eq!(chars.len(), 2);
+    let a = &chars[0];
+    assert_eq!((a.character.as_str(), a.family.as_str()), ("A", "Arial"));
+    assert_eq!(a.width, 66.7);
+    let beziers = a.beziers();
+    assert_eq!(beziers.len(), 1);
+    assert!(beziers[0].closed);
+    assert_eq!(beziers[0].verticies[1], Vector2D::new(33.35, -71.6));
+    // Spaces have no outline
+    assert!(chars[1].beziers().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_font_matching() -> Result<(), Error> {
+    let file = fs::File::open("../../fixtures/segments/font_list.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let fonts: FontList = serde_path_to_error::deserialize(d).unwrap();
+    let styles = fonts
+        .list
+        .iter()
+        .map(|font| (font.weight_value(), font.is_italic()))
+        .collect::<Vec<_>>();
+    // Weights are guessed from style names unless set explicitly
+    assert_eq!(styles, vec![(700, true), (600, false), (300, false)]);
+    assert_eq!(fonts.list[1].class.as_deref(), Some("sans-serif"));
+
+    let fallbacks = FontFallbacks::default();
+    assert_eq!(fallbacks.default, vec!["Fira Mono".to_string()]);
+    assert!(fallbacks.scripts.contains_key("Arab"));
+    assert!(fallbacks.classes.contains_key("serif"));
+    Ok(())
+}
+
+#[test]
+fn test_font_provider() -> Result<(), Error> {
+    struct Unreachable(Arc<Mutex<Vec<String>>>);
+
+    impl FontProvider for Unreachable {
+        fn font_from_url(&self, url: &str) -> Result<Option<Vec<u8>>, LottieError> {
+            self.0.lock().unwrap().push(url.to_string());
+            Err(LottieError::FontNotLoaded(url.to_string()))
+        }
+    }
+
+    let file = fs::File::open("../../fixtures/segments/font_provider.json")?;
+    let d = &mut serde_json::Deserializer::from_reader(file);
+    let model: Model = serde_path_to_error::deserialize(d).unwrap();
+    let requested = Arc::new(Mutex::new(vec![]));
+    // Directories which don't exist are skipped
+    let mut options = FontOptions::offline(vec![PathBuf::from("/nonexistent/fonts")]);
+    // Without the bundled Fira Mono, there is no font to fall back to
+    assert!(options.files.is_empty());
+    options.provider = Box::new(Unreachable(requested.clone()));
+    let mut fontdb = FontDB::with_options(options).unwrap();
+    fontdb.load_fonts_from_model(&model).unwrap();
+    assert_eq!(
+        *requested.lock().unwrap(),
+        vec!["https://example.com/remote.ttf"]
+    );
+
+    let diagnostics = fontdb.diagnostics();
+    assert!(matches!(
+        &diagnostics[0],
+        Diagnostic::FontNotLoaded { name, .. } if name == "Remote-Regular"
+    ));
+    assert_eq!(
+        diagnostics[1..],
+        [
+            Diagnostic::MissingFont {
+                name: "Remote-Regular".to_string(),
+                family: "Remote".to_string(),
+            },
+            Diagnostic::MissingFont {
+                name: "Missing-Regular".to_string(),
+                family: "Missing".to_string(),
+            },
+        ]
+    );
+    assert!(OfflineFontProvider
+        .font_from_url("https://example.com/remote.ttf")
+        .unwrap()
+        .is_none());
+    Ok(())
+}
@@ -1,12 +1,12 @@
 // #![feature(path_file_prefix)]
 use std::fs::{self, File};
 use std::io::{BufWriter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 // use bevy_prototype_debug_lines::{DebugLines, DebugLinesPlugin};
 use anyhow::Error;
 use clap::Parser;
-use lottie_core::{Config, HeadlessConfig, Lottie, Renderer, Target, WindowConfig};
+use lottie_core::{Config, FontOptions, HeadlessConfig, Lottie, Renderer, Target, WindowConfig};
 use lottie_renderer_bevy::BevyRenderer;
 use smol::pin;
 use smol::stream::StreamExt;
@@ -35,6 +35,21 @@ struct Args {
     inspector: bool,
     #[clap(long)]
     scale: Option<f32>,
+    /// Samples per frame of layers with motion blur in headless mode, the
+    /// composition's setting is used by default
+    #[clap(long)]
+    motion_blur_samples: Option<u32>,
+    /// Shutter angle of motion blur in degrees in headless mode, the
+    /// composition's setting is used by default
+    #[clap(long)]
+    shutter_angle: Option<f32>,
+    /// Never download fonts, and only use the fonts of `font-dir`
+    #[clap(long, action)]
+    offline: bool,
+    /// Directory of fonts, may be repeated. Defaults to the fonts of the
+    /// system
+    #[clap(long)]
+    font_dir: Vec<PathBuf>,
 }
 
 // fn axis_system(mut lines: ResMut<DebugLines>) {
@@ -64,7 +79,15 @@ fn main() -> Result<(), Error> {
     }
     let root_path = &*root_path.to_string_lossy();
     let f = fs::File::open(path).unwrap();
-    let mut lottie = Lottie::from_reader(f, root_path).unwrap();
+    let mut fonts = if args.offline {
+        FontOptions::offline(vec![])
+    } else {
+        FontOptions::system()
+    };
+    if !args.font_dir.is_empty() {
+        fonts.directories = args.font_dir.clone();
+    }
+    let mut lottie = Lottie::from_reader_with_fonts(f, root_path, fonts).unwrap();
     lottie.scale = args.scale.unwrap_or(1.0);
     let final_timestamp = (lottie.model.end_frame / lottie.model.frame_rate * 1000.0) as i32;
     let (mut renderer, frame_stream) = BevyRenderer::new();
@@ -73,6 +96,8 @@ fn main() -> Result<(), Error> {
             target: Target::Default,
             filename,
             frame: args.frame,
+            motion_blur_samples: args.motion_blur_samples,
+            shutter_angle: args.shutter_angle,
         })
     } else {
         Config::Window(WindowConfig {
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use lottie_core::prelude::{Id as TimelineItemId, LayerCamera, StagedLayer, Vector3D};
+use lottie_core::Lottie;
+
+use crate::material::LottieMaterial;
+use crate::utils::flip_z;
+use crate::{LottieAnimationInfo, LottieComp};
+
+/// Depth of projected vertices in the clip space of the 2D cameras. Shapes are
+/// ordered by their z translation instead, so the depth only has to stay
+/// inside of the camera frustum
+const PROJECTED_DEPTH: f32 = -0.5;
+
+/// Shapes of 3D layers, projected by the camera of the composition
+#[derive(Component)]
+pub struct Projected(pub TimelineItemId);
+
+/// Move a projection of the composition to world coordinates, `root` being
+/// the transform of the composition
+fn world_projection(projection: Mat4, root: Mat4) -> Mat4 {
+    let matrix = root * flip_z(projection) * root.inverse();
+    // Replace the z row by a multiple of the w row, which stays constant once
+    // divided by w
+    let mut rows = matrix.transpose();
+    rows.z_axis = rows.w_axis * PROJECTED_DEPTH;
+    rows.transpose()
+}
+
+/// Project 3D layers with the camera of the composition, and draw them from
+/// the farthest to the closest one
+pub fn camera_system(
+    comps: Query<(&LottieComp, &Transform)>,
+    mut layers: Query<&mut Transform, Without<LottieComp>>,
+    shapes: Query<(&Handle<LottieMaterial>, &Projected)>,
+    mut materials: ResMut<Assets<LottieMaterial>>,
+    info: Res<LottieAnimationInfo>,
+) {
+    if info.paused {
+        return;
+    }
+    let Ok((comp, root)) = comps.get_single() else {
+        return;
+    };
+    let current_frame = info.current_time * info.frame_rate;
+    let lottie = &comp.lottie;
+    let timeline = lottie.timeline();
+    let camera = timeline.camera(current_frame);
+
+    // Layers are ordered by their z translation, which is also projected by
+    // the camera. The distance each layer is moved by to be sorted is undone
+    // before projecting its shapes
+    let order = camera
+        .map(|camera| depth_order(lottie, camera, current_frame))
+        .unwrap_or_default();
+    let mut offsets = HashMap::new();
+    for layer in timeline
+        .items()
+        .filter(|layer| layer.is_3d && !layer.is_mask)
+    {
+        let Some(frame) = layer.frame_transform_hierarchy.value(current_frame) else {
+            continue;
+        };
+        let Some(mut transform) = info
+            .entities
+            .get(&layer.id)
+            .and_then(|entity| layers.get_mut(*entity).ok())
+        else {
+            continue;
+        };
+        let z = flip_z(layer.transform.value(frame)).w_axis.z - layer.zindex;
+        let sorted = order.get(&layer.id).map(|zindex| -zindex).unwrap_or(z);
+        transform.translation.z = sorted;
+        offsets.insert(layer.id, z - sorted);
+    }
+    let offset = |id: TimelineItemId| {
+        std::iter::successors(timeline.item(id), |layer| {
+            layer.parent.and_then(|id| timeline.item(id))
+        })
+        .filter_map(|layer| offsets.get(&layer.id))
+        .sum::<f32>()
+    };
+
+    let camera = camera
+        .map(|camera| {
+            world_projection(camera.view_projection(current_frame), root.compute_matrix())
+        })
+        .unwrap_or(Mat4::IDENTITY);
+    for (handle, Projected(id)) in shapes.iter() {
+        let camera = camera * Mat4::from_translation(Vec3::Z * offset(*id));
+        if materials.get(handle).map(|m| m.camera) == Some(camera) {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(handle) {
+            material.camera = camera;
+        }
+    }
+}
+
+/// Zindex of the active 3D layers once sorted by their distance to `camera`,
+/// relative to their parents. Consecutive 3D layers swap their zindexes, 2D
+/// layers between them keeping their place
+fn depth_order(lottie: &Lottie, camera: &LayerCamera, frame: f32) -> HashMap<TimelineItemId, f32> {
+    let timeline = lottie.timeline();
+    let absolute_zindex = |layer: &StagedLayer| -> f32 {
+        std::iter::successors(Some(layer), |layer| {
+            layer.parent.and_then(|id| timeline.item(id))
+        })
+        .map(|layer| layer.zindex)
+        .sum()
+    };
+    let mut layers = timeline
+        .items()
+        .filter(|layer| !layer.is_mask)
+        .filter(|layer| layer.frame_transform_hierarchy.value(frame).is_some())
+        .map(|layer| (layer, absolute_zindex(layer)))
+        .collect::<Vec<_>>();
+    layers.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut result = HashMap::new();
+    for group in layers.split(|(layer, _)| !layer.is_3d) {
+        let mut zindexes = group.iter().map(|(_, zindex)| *zindex).collect::<Vec<_>>();
+        let mut group = group
+            .iter()
+            .map(|(layer, zindex)| {
+                let depth = camera.depth(anchor(lottie, layer, frame), frame);
+                (layer.id, depth, *zindex)
+            })
+            .collect::<Vec<_>>();
+        // The closest layer takes the smallest zindex, which is drawn last
+        group.sort_by(|a, b| a.1.total_cmp(&b.1));
+        zindexes.sort_by(f32::total_cmp);
+        result.extend(
+            group
+                .into_iter()
+                .zip(zindexes)
+                .map(|((id, _, own), zindex)| {
+                    // Zindexes are relative to the parent layer
+                    let layer = timeline.item(id).unwrap();
+                    (id, zindex - own + layer.zindex)
+                }),
+        );
+    }
+    result
+}
+
+/// Anchor point of `layer` in the composition
+fn anchor(lottie: &Lottie, layer: &StagedLayer, frame: f32) -> Vector3D {
+    let timeline = lottie.timeline();
+    let matrix = std::iter::successors(Some(layer), |layer| {
+        layer.parent.and_then(|id| timeline.item(id))
+    })
+    .fold(Mat4::IDENTITY, |matrix, layer| {
+        let frame = layer.frame_transform_hierarchy.value(frame).unwrap_or(0.0);
+        layer.transform.value(frame) * matrix
+    });
+    let anchor = layer
+        .transform
+        .anchor
+        .as_ref()
+        .map(|anchor| {
+            let frame = layer.frame_transform_hierarchy.value(frame).unwrap_or(0.0);
+            anchor.value(frame)
+        })
+        .unwrap_or_else(Vector3D::zero);
+    let point = matrix.transform_point3(Vec3::new(anchor.x, anchor.y, anchor.z));
+    Vector3D::new(point.x, point.y, point.z)
+}
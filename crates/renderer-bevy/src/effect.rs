@@ -0,0 +1,427 @@
+use bevy::asset::{Asset, AssetPath};
+use bevy::math::primitives::Rectangle;
+use bevy::prelude::*;
+use bevy::reflect::TypePath;
+use bevy::render::camera::RenderTarget;
+use bevy::render::mesh::MeshVertexBufferLayout;
+use bevy::render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, ShaderRef, ShaderType, SpecializedMeshPipelineError,
+};
+use bevy::render::texture::BevyDefault;
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{Material2d, Material2dKey, MaterialMesh2dBundle};
+use lottie_core::prelude::{Effect, LayerEffect, LevelsCurve, Rgba, StagedLayer};
+use wgpu::{
+    BlendState, Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+
+use crate::render::FrameTracker;
+use crate::LottieAnimationInfo;
+
+/// Render layer of the quads drawn by effect passes, only seen by the cameras
+/// of these passes
+pub const EFFECT_PASS_RENDER_LAYER: u8 = 2;
+
+/// A fullscreen pass of a layer effect. Passes read the texture written by
+/// the previous one, the first pass reads the contents of the layer
+#[derive(Asset, AsBindGroup, Clone, TypePath)]
+#[bind_group_data(EffectMaterialKey)]
+pub struct EffectMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub input: Handle<Image>,
+    #[uniform(2)]
+    pub params: EffectParams,
+    /// Input of the first pass of the effect, for passes drawing over it
+    #[texture(3)]
+    #[sampler(4)]
+    pub original: Handle<Image>,
+    /// Whether this draws the result of all passes into the scene, instead of
+    /// replacing the pixels of the pass target
+    pub composite: bool,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, ShaderType)]
+pub struct EffectParams {
+    /// 0 copies the input, 1 blurs it along `direction`, 2 casts a shadow of
+    /// it, 3 draws the original over it, then 4 to 7 are the fill, tint,
+    /// tritone and levels color corrections
+    pub kind: u32,
+    /// Sample the edge pixels instead of transparency outside the input
+    pub repeat_edge: u32,
+    /// Standard deviation of the blur, in pixels
+    pub sigma: f32,
+    pub direction: Vec2,
+    /// Linear color of the shadow, its alpha being the shadow opacity, or the
+    /// sRGB colors of color corrections: fill color, tint black and white, or
+    /// tritone highlights, midtones and shadows
+    pub colors: [Vec4; 3],
+    /// Offset of the shadow, in pixels
+    pub offset: Vec2,
+    /// Skip drawing the original
+    pub shadow_only: u32,
+    /// Fill opacity, tint amount or tritone blend with original
+    pub amount: f32,
+    /// RGB, red, green, blue and alpha levels
+    pub levels: [LevelsUniform; 5],
+}
+
+#[derive(Clone, Copy, Default, PartialEq, ShaderType)]
+pub struct LevelsUniform {
+    /// Input black, input white, gamma and output black
+    pub curve: Vec4,
+    pub output_white: f32,
+}
+
+impl From<LevelsCurve> for LevelsUniform {
+    fn from(curve: LevelsCurve) -> Self {
+        LevelsUniform {
+            curve: Vec4::new(
+                curve.input_black,
+                curve.input_white,
+                curve.gamma,
+                curve.output_black,
+            ),
+            output_white: curve.output_white,
+        }
+    }
+}
+
+/// Straight sRGB components of `color`
+fn srgb(color: Rgba) -> Vec4 {
+    Vec4::new(
+        color.r as f32,
+        color.g as f32,
+        color.b as f32,
+        color.a as f32,
+    ) / 255.0
+}
+
+impl EffectParams {
+    /// Parameters of the `stage`-th pass of `effect`, rendered at `scale`
+    pub fn new(effect: &LayerEffect, stage: usize, scale: f32) -> Self {
+        match effect {
+            LayerEffect::GaussianBlur(blur) => {
+                let (sigma, direction) = if stage == 0 {
+                    (blur.sigma_x, Vec2::X)
+                } else {
+                    (blur.sigma_y, Vec2::Y)
+                };
+                EffectParams {
+                    kind: 1,
+                    repeat_edge: blur.repeat_edge as u32,
+                    sigma: sigma * scale,
+                    direction,
+                    ..default()
+                }
+            }
+            LayerEffect::DropShadow(shadow) => match stage {
+                0 => {
+                    let Rgba { r, g, b, a } = shadow.color;
+                    let color = Color::rgba_u8(r, g, b, a).as_linear_rgba_f32().into();
+                    EffectParams {
+                        kind: 2,
+                        colors: [color, Vec4::ZERO, Vec4::ZERO],
+                        offset: Vec2::new(shadow.offset.x, shadow.offset.y) * scale,
+                        ..default()
+                    }
+                }
+                1 | 2 => EffectParams {
+                    kind: 1,
+                    sigma: shadow.sigma * scale,
+                    direction: if stage == 1 { Vec2::X } else { Vec2::Y },
+                    ..default()
+                },
+                _ => EffectParams {
+                    kind: 3,
+                    shadow_only: shadow.shadow_only as u32,
+                    ..default()
+                },
+            },
+            LayerEffect::Fill(fill) => EffectParams {
+                kind: 4,
+                colors: [srgb(fill.color), Vec4::ZERO, Vec4::ZERO],
+                amount: fill.opacity,
+                ..default()
+            },
+            LayerEffect::Tint(tint) => EffectParams {
+                kind: 5,
+                colors: [srgb(tint.black), srgb(tint.white), Vec4::ZERO],
+                amount: tint.amount,
+                ..default()
+            },
+            LayerEffect::Tritone(tritone) => EffectParams {
+                kind: 6,
+                colors: [
+                    srgb(tritone.highlights),
+                    srgb(tritone.midtones),
+                    srgb(tritone.shadows),
+                ],
+                amount: tritone.blend,
+                ..default()
+            },
+            LayerEffect::Levels(levels) => EffectParams {
+                kind: 7,
+                levels: [
+                    levels.rgb.into(),
+                    levels.red.into(),
+                    levels.green.into(),
+                    levels.blue.into(),
+                    levels.alpha.into(),
+                ],
+                ..default()
+            },
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct EffectMaterialKey {
+    composite: bool,
+}
+
+impl From<&EffectMaterial> for EffectMaterialKey {
+    fn from(material: &EffectMaterial) -> Self {
+        Self {
+            composite: material.composite,
+        }
+    }
+}
+
+impl Material2d for EffectMaterial {
+    fn fragment_shader() -> ShaderRef {
+        let current_exe = std::env::current_exe().unwrap();
+        let mut path = current_exe.clone();
+        path.push("assets/effect.wgsl");
+
+        while !path.exists() && path.parent().is_some() {
+            path.pop();
+            path.pop();
+            path.pop();
+            path.push("assets/effect.wgsl");
+        }
+        AssetPath::from(path).into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _: &MeshVertexBufferLayout,
+        key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Layer textures are drawn over a transparent target, so their colors
+        // are already multiplied by their alpha
+        let blend = if key.bind_group_data.composite {
+            Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING)
+        } else {
+            None
+        };
+        let fragment = descriptor.fragment.as_mut().unwrap();
+        for target in fragment.targets.iter_mut().flatten() {
+            target.blend = blend;
+        }
+        Ok(())
+    }
+}
+
+/// Pass of an effect, whose parameters are updated every frame
+#[derive(Component)]
+pub struct EffectPass {
+    effect: Effect,
+    stage: usize,
+    scale: f32,
+}
+
+/// Number of passes needed to render `effect`
+fn pass_count(effect: &LayerEffect) -> usize {
+    match effect {
+        // Horizontal then vertical
+        LayerEffect::GaussianBlur(_) => 2,
+        // Shadow, blurred like above, then the original over it
+        LayerEffect::DropShadow(_) => 4,
+        LayerEffect::Fill(_)
+        | LayerEffect::Tint(_)
+        | LayerEffect::Tritone(_)
+        | LayerEffect::Levels(_) => 1,
+    }
+}
+
+/// Effects of `layer` which are rendered
+pub fn layer_effects(layer: &StagedLayer) -> impl Iterator<Item = (&Effect, LayerEffect)> {
+    layer.effects.iter().filter_map(|effect| {
+        LayerEffect::resolve(effect, layer.start_frame).map(|resolved| (effect, resolved))
+    })
+}
+
+/// Number of cameras spawned by [`EffectLayer::spawn`] for `layer`
+pub fn camera_count(layer: &StagedLayer) -> usize {
+    1 + layer_effects(layer)
+        .map(|(_, effect)| pass_count(&effect))
+        .sum::<usize>()
+}
+
+fn offscreen_image(size: Extent3d, images: &mut Assets<Image>) -> Handle<Image> {
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("effect_texture"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    images.add(image)
+}
+
+/// Offscreen rendering of a layer with effects. The contents of the layer are
+/// drawn to their own `render_layer`, then every effect pass draws a quad
+/// sampling the previous texture, and the last texture is composited into the
+/// scene
+pub struct EffectLayer<'a> {
+    pub layer: &'a StagedLayer,
+    /// Depth of the layer in the scene
+    pub zindex: f32,
+    pub images: &'a mut Assets<Image>,
+    pub materials: &'a mut Assets<EffectMaterial>,
+    pub meshes: &'a mut Assets<Mesh>,
+    pub size: Extent3d,
+    pub scale: f32,
+    /// Render layer the contents of the layer are drawn to
+    pub render_layer: u8,
+    /// Cameras the result of the effects is visible to
+    pub composite_layers: RenderLayers,
+    /// Order of the next camera
+    pub order: &'a mut isize,
+    /// Number of passes spawned so far, used to keep their quads apart
+    pub pass_index: &'a mut usize,
+}
+
+impl<'a> EffectLayer<'a> {
+    pub fn spawn(self, commands: &mut Commands) {
+        let EffectLayer {
+            layer,
+            zindex,
+            images,
+            materials,
+            meshes,
+            size,
+            scale,
+            render_layer,
+            composite_layers,
+            order,
+            pass_index,
+        } = self;
+        let mut input = offscreen_image(size, images);
+        commands
+            .spawn(Camera2dBundle {
+                camera: Camera {
+                    target: RenderTarget::Image(input.clone()),
+                    order: *order,
+                    clear_color: ClearColorConfig::Custom(Color::NONE),
+                    ..default()
+                },
+                transform: Transform::from_scale(Vec3::new(1.0, -1.0, 1.0)),
+                ..default()
+            })
+            .insert(RenderLayers::layer(render_layer));
+        *order += 1;
+
+        let quad = meshes.add(Rectangle::new(size.width as f32, size.height as f32));
+        let tracker = FrameTracker(layer.frame_transform_hierarchy.clone());
+        for (effect, resolved) in layer_effects(layer) {
+            let original = input.clone();
+            for stage in 0..pass_count(&resolved) {
+                let output = offscreen_image(size, images);
+                // Passes share a render layer, each camera only sees its own quad
+                *pass_index += 1;
+                let position = Vec3::new(*pass_index as f32 * 2.0 * size.width as f32, 0.0, 0.0);
+                let material = materials.add(EffectMaterial {
+                    input: input.clone(),
+                    params: EffectParams::new(&resolved, stage, scale),
+                    original: original.clone(),
+                    composite: false,
+                });
+                commands
+                    .spawn(MaterialMesh2dBundle {
+                        mesh: quad.clone().into(),
+                        material,
+                        transform: Transform::from_translation(position - Vec3::Z),
+                        ..default()
+                    })
+                    .insert((
+                        RenderLayers::layer(EFFECT_PASS_RENDER_LAYER),
+                        EffectPass {
+                            effect: effect.clone(),
+                            stage,
+                            scale,
+                        },
+                        FrameTracker(layer.frame_transform_hierarchy.clone()),
+                    ));
+                commands
+                    .spawn(Camera2dBundle {
+                        camera: Camera {
+                            target: RenderTarget::Image(output.clone()),
+                            order: *order,
+                            clear_color: ClearColorConfig::Custom(Color::NONE),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(position),
+                        ..default()
+                    })
+                    .insert(RenderLayers::layer(EFFECT_PASS_RENDER_LAYER));
+                *order += 1;
+                input = output;
+            }
+        }
+
+        let material = materials.add(EffectMaterial {
+            original: input.clone(),
+            input,
+            params: EffectParams::default(),
+            composite: true,
+        });
+        commands
+            .spawn(MaterialMesh2dBundle {
+                mesh: quad.into(),
+                material,
+                // Textures are rendered by flipped cameras like the scene
+                transform: Transform::from_scale(Vec3::new(1.0, -1.0, 1.0))
+                    .with_translation(Vec3::new(0.0, 0.0, zindex * -1.0)),
+                ..default()
+            })
+            .insert((composite_layers, tracker));
+    }
+}
+
+/// Update the parameters of effect passes to the current frame
+pub fn effect_system(
+    passes: Query<(&EffectPass, &Handle<EffectMaterial>, &FrameTracker)>,
+    mut materials: ResMut<Assets<EffectMaterial>>,
+    info: Res<LottieAnimationInfo>,
+) {
+    if info.paused {
+        return;
+    }
+    let current_frame = info.current_time * info.frame_rate;
+    for (pass, handle, tracker) in passes.iter() {
+        let Some(frame) = tracker.value(current_frame) else {
+            continue;
+        };
+        let Some(effect) = LayerEffect::resolve(&pass.effect, frame) else {
+            continue;
+        };
+        let params = EffectParams::new(&effect, pass.stage, pass.scale);
+        if materials.get(handle).map(|m| m.params) == Some(params) {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(handle) {
+            material.params = params;
+        }
+    }
+}
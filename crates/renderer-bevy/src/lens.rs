@@ -1,7 +1,7 @@
-use bevy::prelude::{Transform, Vec2};
+use bevy::prelude::{Color, Transform, Vec2};
 use bevy_tweening::Lens;
 use lottie_core::prelude::{
-    Animated, Bezier, OpacityHierarchy, PathFactory, TextBased, TextRangeInfo, TextRangeSelector,
+    Animated, Bezier, Fill, GlyphStyle, OpacityHierarchy, PathFactory, Rgb, Stroke, TextRangeInfo,
     Transform as LottieTransform, TransformHierarchy, TrimInfo,
 };
 use lyon::algorithms::measure::PathMeasurements;
@@ -9,7 +9,8 @@ use lyon::algorithms::measure::SampleType::Normalized;
 use lyon::geom::euclid::approxeq::ApproxEq;
 use lyon::path::Path as LyonPath;
 
-use crate::shape::{DrawMode, Path};
+use crate::shape::{DrawMode, Path, SolidOrGradient};
+use crate::utils::flip_z;
 
 pub struct PathLens {
     pub(crate) start: Vec<Bezier>,
@@ -91,6 +92,92 @@ impl Lens<DrawMode> for StrokeWidthLens {
     }
 }
 
+/// Lerp the color of a solid fill, the alpha channel is left to [OpacityLens]
+pub struct FillColorLens {
+    pub(crate) start: Rgb,
+    pub(crate) end: Rgb,
+}
+
+impl Lens<DrawMode> for FillColorLens {
+    fn lerp(&mut self, target: &mut DrawMode, ratio: f32) {
+        if let Some(fill) = target.fill.as_mut() {
+            lerp_solid_color(&mut fill.color, &self.start, &self.end, ratio);
+        }
+    }
+}
+
+/// Lerp the color of a solid stroke, the alpha channel is left to
+/// [OpacityLens]
+pub struct StrokeColorLens {
+    pub(crate) start: Rgb,
+    pub(crate) end: Rgb,
+}
+
+impl Lens<DrawMode> for StrokeColorLens {
+    fn lerp(&mut self, target: &mut DrawMode, ratio: f32) {
+        if let Some(stroke) = target.stroke.as_mut() {
+            lerp_solid_color(&mut stroke.color, &self.start, &self.end, ratio);
+        }
+    }
+}
+
+/// Apply the colors, opacities and stroke width of text animators to a
+/// glyph, on top of the fill and stroke of the text document
+pub struct TextStyleLens {
+    pub(crate) info: TextRangeInfo,
+    pub(crate) frames: f32,
+    pub(crate) opacity: OpacityHierarchy,
+    pub(crate) fill: Option<Fill>,
+    pub(crate) stroke: Option<Stroke>,
+}
+
+impl Lens<DrawMode> for TextStyleLens {
+    fn lerp(&mut self, target: &mut DrawMode, ratio: f32) {
+        let frame = self.frames * ratio;
+        let mut style = GlyphStyle::default();
+        if let Some(fill) = self.fill.as_ref() {
+            style.fill_color = fill.color.value(frame).to_f32();
+            style.fill_opacity = fill.opacity.value(frame) / 100.0;
+        }
+        if let Some(stroke) = self.stroke.as_ref() {
+            style.stroke_color = stroke.color.value(frame).to_f32();
+            style.stroke_opacity = stroke.opacity.value(frame) / 100.0;
+            style.stroke_width = stroke.width.value(frame);
+        }
+        self.info.apply(&mut style, frame);
+        let opacity = self.opacity.value(frame) * style.opacity;
+        if let Some(fill) = target.fill.as_mut() {
+            set_solid_color(&mut fill.color, style.fill_color);
+            fill.opacity = opacity * style.fill_opacity;
+        }
+        if let Some(stroke) = target.stroke.as_mut() {
+            set_solid_color(&mut stroke.color, style.stroke_color);
+            stroke.opacity = opacity * style.stroke_opacity;
+            stroke.options.line_width = style.stroke_width;
+        }
+    }
+}
+
+fn set_solid_color(color: &mut SolidOrGradient, [r, g, b]: [f32; 3]) {
+    if let SolidOrGradient::Solid(color) = color {
+        *color = Color::rgba(r, g, b, color.a());
+    }
+}
+
+/// Interpolate each sRGB channel linearly like lottie-web does, without
+/// rounding to 8 bits in between
+fn lerp_solid_color(color: &mut SolidOrGradient, start: &Rgb, end: &Rgb, ratio: f32) {
+    if let SolidOrGradient::Solid(color) = color {
+        let channel = |s: u8, e: u8| (s as f32 + (e as f32 - s as f32) * ratio) / 255.0;
+        *color = Color::rgba(
+            channel(start.r, end.r),
+            channel(start.g, end.g),
+            channel(start.b, end.b),
+            color.a(),
+        );
+    }
+}
+
 /// Lerp [LottieTransform] as a whole
 pub struct TransformLens {
     pub(crate) data: LottieTransform,
@@ -104,36 +191,15 @@ pub struct TransformLens {
 impl Lens<Transform> for TransformLens {
     fn lerp(&mut self, target: &mut Transform, ratio: f32) {
         let frame = self.frames * ratio;
-        let value = self.data.value(frame);
-        *target = Transform::from_matrix(value);
-        target.translation.z = self.zindex;
-        target.translation.x += self.mask_offset.x / self.transform_hierarchy.scale_x(frame);
-
+        let mut value = self.data.value(frame);
         if let Some(info) = self.text_range.as_ref() {
-            for range in &info.ranges {
-                let appliable = lerp_index_in_text_range(
-                    &range.selector,
-                    frame,
-                    &info.value,
-                    info.index.0,
-                    info.index.1,
-                );
-                if !appliable {
-                    continue;
-                }
-                // TODO: support more selector attributes
-                let styles = match range.style.as_ref() {
-                    Some(s) => s,
-                    None => continue,
-                };
-                let letter_spacing = styles
-                    .letter_spacing
-                    .as_ref()
-                    .map(|l| l.value(frame))
-                    .unwrap_or(0.0);
-                target.translation.x += info.index.1 as f32 * letter_spacing;
-            }
+            let mut style = GlyphStyle::default();
+            info.apply(&mut style, frame);
+            value *= style.transform;
         }
+        *target = Transform::from_matrix(flip_z(value));
+        target.translation.z += self.zindex;
+        target.translation.x += self.mask_offset.x / self.transform_hierarchy.scale_x(frame);
     }
 }
 
@@ -163,34 +229,6 @@ impl Lens<DrawMode> for OpacityLens {
     }
 }
 
-fn lerp_index_in_text_range(
-    selector: &TextRangeSelector,
-    frame: f32,
-    value: &Vec<Vec<char>>,
-    line: usize,
-    c: usize,
-) -> bool {
-    let start = selector
-        .start
-        .as_ref()
-        .map(|start| start.value(frame))
-        .unwrap_or(0.0)
-        .round() as usize;
-    let end = selector
-        .end
-        .as_ref()
-        .map(|end| end.value(frame))
-        .unwrap_or(std::f32::MAX)
-        .round() as usize;
-    match selector.range_units {
-        TextBased::Characters => {
-            let index = (0..line).map(|i| value[i].len()).sum::<usize>() + c;
-            return index >= start && index < end;
-        }
-        _ => unimplemented!(),
-    }
-}
-
 pub struct PathFactoryLens {
     pub(crate) start_frame: f32,
     pub(crate) end_frame: f32,
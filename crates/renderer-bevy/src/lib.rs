@@ -11,7 +11,7 @@ use bevy::render::camera::RenderTarget;
 use bevy::render::render_resource::TextureFormat;
 use bevy::render::renderer::RenderDevice;
 use bevy::render::view::RenderLayers;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
 use bevy::window::{ExitCondition, PrimaryWindow};
 use bevy::winit::WinitPlugin;
 // use bevy_diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
@@ -24,12 +24,14 @@ pub use bevy;
 use bevy::prelude::Transform;
 use bevy::render::texture::{BevyDefault, Image};
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use lottie_core::prelude::{Id as TimelineItemId, StyledShape};
+use lottie_core::prelude::{BlendMode, Id as TimelineItemId, MotionBlur, StagedLayer, StyledShape};
 use lottie_core::*;
 use shape::{DrawMode, Path};
 use system::component_animator_system;
 use wgpu::{Extent3d, TextureDescriptor, TextureDimension, TextureUsages};
 
+mod camera;
+mod effect;
 mod frame_capture;
 // mod gradient;
 mod lens;
@@ -41,13 +43,19 @@ mod system;
 mod tween;
 mod utils;
 
+use camera::camera_system;
+use effect::{effect_system, EffectLayer, EffectMaterial};
 use frame_capture::{ImageCopier, ImageCopyPlugin, ImageToSave};
-use material::LottieMaterial;
+use material::{gradient_ramp_data, GradientDataUniform, LottieMaterial};
 use ordered_float::OrderedFloat;
 use plugin::LottiePlugin;
 use bevy::ecs::query::With;
 use render::*;
 
+/// First render layer used by backdrop and effect layer cameras, 0 being the
+/// main scene, 1 the masks and 2 the effect passes
+const OFFSCREEN_RENDER_LAYER: u8 = 3;
+
 #[derive(Component)]
 pub struct LottieComp {
     lottie: Lottie,
@@ -77,6 +85,10 @@ pub struct LottieAnimationInfo {
     height: f32,
     finished_once: bool,
     entities: HashMap<TimelineItemId, Entity>,
+    /// Shutter sampled by headless exports for layers with motion blur
+    motion_blur: Option<MotionBlur>,
+    /// Index of the shutter sample rendered for the current frame
+    sample: u32,
 }
 
 impl LottieAnimationInfo {
@@ -95,6 +107,55 @@ impl LottieAnimationInfo {
     pub fn current_time(&self) -> f32 {
         self.current_time
     }
+
+    fn samples(&self) -> u32 {
+        self.motion_blur
+            .map(|motion_blur| motion_blur.samples_per_frame.max(1))
+            .unwrap_or(1)
+    }
+
+    /// Move to the next sample of the current frame, or to the next frame
+    /// once every sample is rendered
+    fn advance(&mut self, delta: f32) {
+        self.sample += 1;
+        if self.sample >= self.samples() {
+            self.sample = 0;
+            self.current_time += delta;
+        }
+    }
+}
+
+/// Sum of the captured samples of the frame being exported with motion blur
+#[derive(Resource, Default)]
+struct SampleAccumulator {
+    sums: Vec<u32>,
+    count: u32,
+}
+
+impl SampleAccumulator {
+    fn add(&mut self, data: &[u8]) {
+        if self.sums.len() != data.len() {
+            self.sums = vec![0; data.len()];
+            self.count = 0;
+        }
+        for (sum, value) in self.sums.iter_mut().zip(data) {
+            *sum += *value as u32;
+        }
+        self.count += 1;
+    }
+
+    /// Average of the samples added since the last call
+    fn take_average(&mut self) -> Vec<u8> {
+        let count = self.count.max(1);
+        let data = self
+            .sums
+            .iter()
+            .map(|sum| ((sum + count / 2) / count) as u8)
+            .collect();
+        self.sums.fill(0);
+        self.count = 0;
+        data
+    }
 }
 
 #[derive(Resource)]
@@ -195,6 +256,11 @@ impl Renderer for BevyRenderer {
             .add_systems(Update, component_animator_system::<Transform>)
             .add_systems(Update, component_animator_system::<Path>)
             .add_systems(Update, component_animator_system::<DrawMode>)
+            .add_systems(Update, effect_system.before(animate_system))
+            .add_systems(
+                Update,
+                camera_system.after(component_animator_system::<Transform>),
+            )
             .add_systems(Update, animate_system)
             .add_systems(Startup, setup_system);
 
@@ -213,6 +279,13 @@ impl Renderer for BevyRenderer {
         }
 
         let frame_rate = lottie.model.frame_rate as f64;
+        let samples = match &config {
+            Config::Headless(headless) => headless
+                .motion_blur(&lottie)
+                .map(|motion_blur| motion_blur.samples_per_frame)
+                .unwrap_or(1),
+            Config::Window(_) => 1,
+        };
         self.app.insert_resource(LottieGlobals {
             lottie: Some(lottie),
             capturing,
@@ -227,8 +300,9 @@ impl Renderer for BevyRenderer {
                     sender: self.frame_sender.clone(),
                     closed: Arc::default(),
                 })
+                .init_resource::<SampleAccumulator>()
                 .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
-                    1.0 / frame_rate,
+                    1.0 / frame_rate / samples as f64,
                 )))
                 .add_systems(Last, save_img);
         } else {
@@ -247,6 +321,7 @@ fn setup_system(
     mut image_assets: ResMut<Assets<Image>>,
     mut audio_assets: ResMut<Assets<AudioSource>>,
     mut material_assets: ResMut<Assets<LottieMaterial>>,
+    mut effect_materials: ResMut<Assets<EffectMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
     window: Query<&Window, With<PrimaryWindow>>,
     render_device: Res<RenderDevice>,
@@ -265,7 +340,9 @@ fn setup_system(
         .timeline()
         .items()
         .filter(|layer| layer.is_mask)
-        .count() as u32;
+        .map(|layer| layer.mask_group.unwrap_or(layer.id))
+        .collect::<HashSet<_>>()
+        .len() as u32;
     let width = (lottie.model.width as f32 * lottie.scale).round() as u32;
     let height = (lottie.model.height as f32 * lottie.scale).round() as u32;
     let root_translation = Vec3::new(
@@ -297,13 +374,171 @@ fn setup_system(
     };
     mask.resize(mask_size);
     let mask_texture_handle = image_assets.add(mask);
+
+    // Layers are spawned as children of their parents, so their zindex is
+    // relative to the parent's
+    let absolute_zindex = |layer: &StagedLayer| -> f32 {
+        std::iter::successors(Some(layer), |layer| {
+            layer.parent.and_then(|id| lottie.timeline().item(id))
+        })
+        .map(|layer| layer.zindex)
+        .sum()
+    };
+    // Layers with a blend mode sample a backdrop texture, which one camera per
+    // layer renders from all the layers behind it. Backdrops are rendered from
+    // back to front, so a blended layer can be part of another one's backdrop
+    let mut blended_layers = lottie
+        .timeline()
+        .items()
+        .filter(|layer| !layer.is_mask && layer.blend_mode != BlendMode::Normal)
+        .map(|layer| (layer.id, absolute_zindex(layer)))
+        .collect::<Vec<_>>();
+    blended_layers.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let max_offscreen = RenderLayers::TOTAL_LAYERS - OFFSCREEN_RENDER_LAYER as usize;
+    if blended_layers.len() > max_offscreen {
+        log::warn!(
+            "only {} layers can have blend modes, {} are ignored",
+            max_offscreen,
+            blended_layers.len() - max_offscreen
+        );
+        blended_layers.truncate(max_offscreen);
+    }
+    // Layers with effects are rendered offscreen with the layers of their
+    // precompositions, then composited into the scene or into the layer with
+    // effects they are part of
+    let composition_depth = |layer: &StagedLayer| {
+        std::iter::successors(Some(layer), |layer| {
+            layer.composition.and_then(|id| lottie.timeline().item(id))
+        })
+        .count()
+    };
+    let mut effect_layers = lottie
+        .timeline()
+        .items()
+        .filter(|layer| !layer.is_mask && effect::layer_effects(layer).next().is_some())
+        .collect::<Vec<_>>();
+    // Nested layers are rendered first
+    effect_layers.sort_by_key(|layer| std::cmp::Reverse(composition_depth(layer)));
+    let max_effect_layers = max_offscreen - blended_layers.len();
+    if effect_layers.len() > max_effect_layers {
+        log::warn!(
+            "only {} layers can have effects, {} are ignored",
+            max_effect_layers,
+            effect_layers.len() - max_effect_layers
+        );
+        effect_layers.truncate(max_effect_layers);
+    }
+    let effect_render_layers = effect_layers
+        .iter()
+        .enumerate()
+        .map(|(index, layer)| {
+            let render_layer = OFFSCREEN_RENDER_LAYER as usize + blended_layers.len() + index;
+            (layer.id, render_layer as u8)
+        })
+        .collect::<HashMap<_, _>>();
+    // Render layer of the closest layer with effects `id` is part of
+    let effect_render_layer = |id: Option<TimelineItemId>| {
+        std::iter::successors(id.and_then(|id| lottie.timeline().item(id)), |layer| {
+            layer.composition.and_then(|id| lottie.timeline().item(id))
+        })
+        .find_map(|layer| effect_render_layers.get(&layer.id).copied())
+    };
+    let effect_cameras = effect_layers
+        .iter()
+        .map(|layer| effect::camera_count(layer))
+        .sum::<usize>();
+    let offscreen_cameras = (blended_layers.len() + effect_cameras) as isize;
+
+    let offscreen_size = Extent3d {
+        width: std::cmp::max(1, width),
+        height: std::cmp::max(1, height),
+        depth_or_array_layers: 1,
+    };
+    let mut backdrops = HashMap::new();
+    for (index, (id, _)) in blended_layers.iter().enumerate() {
+        let mut backdrop = Image {
+            texture_descriptor: TextureDescriptor {
+                label: Some("backdrop_texture"),
+                size: offscreen_size,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::bevy_default(),
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+            ..default()
+        };
+        backdrop.resize(offscreen_size);
+        let handle = image_assets.add(backdrop);
+        let backdrop_camera = Camera2dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(handle.clone()),
+                // After the mask and effect cameras, before the main camera
+                order: index as isize - blended_layers.len() as isize,
+                clear_color: ClearColorConfig::Custom(Color::NONE),
+                ..default()
+            },
+            transform: Transform::from_scale(Vec3::new(1.0, -1.0, 1.0)),
+            ..default()
+        };
+        commands
+            .spawn(backdrop_camera)
+            .insert(RenderLayers::layer(OFFSCREEN_RENDER_LAYER + index as u8));
+        backdrops.insert(*id, handle);
+    }
+    // A layer is visible to the backdrop cameras of every blended layer in
+    // front of it
+    let scene_layers = |zindex: f32| {
+        blended_layers
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, blended_zindex))| zindex > *blended_zindex)
+            .fold(RenderLayers::layer(0), |layers, (index, _)| {
+                layers.with(OFFSCREEN_RENDER_LAYER + index as u8)
+            })
+    };
+    let render_layers = |layer: &StagedLayer| match effect_render_layer(Some(layer.id)) {
+        Some(render_layer) => RenderLayers::layer(render_layer),
+        None => scene_layers(absolute_zindex(layer)),
+    };
+
+    let mut order = -offscreen_cameras;
+    let mut pass_index = 0;
+    for layer in &effect_layers {
+        let composite_layers = match effect_render_layer(layer.composition) {
+            Some(render_layer) => RenderLayers::layer(render_layer),
+            None => scene_layers(absolute_zindex(layer)),
+        };
+        let zindex = absolute_zindex(layer);
+        EffectLayer {
+            layer,
+            zindex,
+            images: &mut image_assets,
+            materials: &mut effect_materials,
+            meshes: &mut meshes,
+            size: offscreen_size,
+            scale: lottie.scale,
+            render_layer: effect_render_layers[&layer.id],
+            composite_layers,
+            order: &mut order,
+            pass_index: &mut pass_index,
+        }
+        .spawn(&mut commands);
+    }
+
     let mask_camera = Camera2dBundle {
         camera_2d: Camera2d {
             // clear_color: ClearColorConfig::Custom(Color::NONE),
         },
         camera: Camera {
             target: RenderTarget::Image(mask_texture_handle.clone()),
-            order: -1,
+            order: -1 - offscreen_cameras,
+            // Luma mattes read the color of the mask texture, so it must not
+            // inherit the (possibly opaque) global clear color
+            clear_color: ClearColorConfig::Custom(Color::NONE),
             ..default()
         },
         transform: Transform::from_scale(Vec3::new(1.0, -1.0, 1.0)).with_translation(Vec3::new(
@@ -383,6 +618,10 @@ fn setup_system(
 
     commands.spawn(camera);
 
+    let motion_blur = match &lottie_globals.config {
+        Config::Headless(headless) => headless.motion_blur(&lottie),
+        Config::Window(_) => None,
+    };
     let mut info = LottieAnimationInfo {
         start_frame: lottie.model.start_frame,
         end_frame: lottie.model.end_frame,
@@ -393,6 +632,8 @@ fn setup_system(
         height: lottie.model.height as f32,
         finished_once: false,
         entities: HashMap::new(),
+        motion_blur,
+        sample: 0,
     };
 
     let root_entity = commands
@@ -440,6 +681,8 @@ fn setup_system(
                 mask_count,
                 model_size: Vec2::new(lottie.model.width as f32, lottie.model.height as f32),
                 scale,
+                render_layers: RenderLayers::layer(1),
+                backdrop: None,
             }
             .spawn(&mut commands)
             .unwrap();
@@ -474,6 +717,8 @@ fn setup_system(
                 mask_count,
                 model_size: Vec2::new(lottie.model.width as f32, lottie.model.height as f32),
                 scale,
+                render_layers: render_layers(layer),
+                backdrop: backdrops.get(&layer.id).cloned(),
             }
             .spawn(&mut commands)
             .unwrap();
@@ -515,22 +760,30 @@ fn animate_system(
         Option<(&AudioSink, Has<LottieAudio>)>,
         &FrameTracker,
     )>,
-    mut transform_animation: Query<(&mut Animator<Transform>, &FrameTracker)>,
-    mut path_animation: Query<(&mut Animator<Path>, &FrameTracker)>,
-    mut draw_mode_animation: Query<(&mut Animator<DrawMode>, &FrameTracker)>,
+    mut transform_animation: Query<(&mut Animator<Transform>, &FrameTracker, Has<MotionBlurred>)>,
+    mut path_animation: Query<(&mut Animator<Path>, &FrameTracker, Has<MotionBlurred>)>,
+    mut draw_mode_animation: Query<(&mut Animator<DrawMode>, &FrameTracker, Has<MotionBlurred>)>,
+    gradient_animation: Query<(
+        &GradientAnimator,
+        &Handle<LottieMaterial>,
+        &FrameTracker,
+        Has<MotionBlurred>,
+    )>,
+    mut materials: ResMut<Assets<LottieMaterial>>,
+    mut images: ResMut<Assets<Image>>,
     mut info: ResMut<LottieAnimationInfo>,
     lottie: Res<LottieGlobals>,
     time: Res<Time>,
 ) {
     let capturing = lottie.capturing;
     if info.paused {
-        for (mut a, _) in transform_animation.iter_mut() {
+        for (mut a, ..) in transform_animation.iter_mut() {
             a.state = AnimatorState::Paused;
         }
-        for (mut a, _) in path_animation.iter_mut() {
+        for (mut a, ..) in path_animation.iter_mut() {
             a.state = AnimatorState::Paused;
         }
-        for (mut a, _) in draw_mode_animation.iter_mut() {
+        for (mut a, ..) in draw_mode_animation.iter_mut() {
             a.state = AnimatorState::Paused;
         }
         return;
@@ -543,20 +796,33 @@ fn animate_system(
     if info.current_time >= info.end_frame / info.frame_rate {
         info.finished_once = true;
         if capturing {
-            info.current_time += delta;
+            info.advance(delta);
             return;
         } else {
             info.current_time = 0.0;
         }
     }
     let current_frame = info.current_time * info.frame_rate;
+    // Layers with motion blur are rendered once per sample of the shutter,
+    // the samples being averaged when the frame is captured
+    let blurred_frame = info
+        .motion_blur
+        .map(|motion_blur| current_frame + motion_blur.sample_offset(info.sample))
+        .unwrap_or(current_frame);
+    let sample_frame = |blurred: bool| {
+        if blurred {
+            blurred_frame
+        } else {
+            current_frame
+        }
+    };
 
-    for (mut a, tracker) in transform_animation.iter_mut() {
+    for (mut a, tracker, blurred) in transform_animation.iter_mut() {
         let total = a.tweenable().duration().as_secs_f32();
         if total == 0.0 {
             a.tweenable_mut()
                 .set_elapsed(Duration::from_secs_f32(total));
-        } else if let Some(frame) = tracker.value(current_frame) {
+        } else if let Some(frame) = tracker.value(sample_frame(blurred)) {
             a.state = AnimatorState::Playing;
             let secs = (frame / tracker.frame_rate()).max(0.0);
             a.tweenable_mut().set_elapsed(Duration::from_secs_f32(secs));
@@ -565,12 +831,12 @@ fn animate_system(
         }
     }
 
-    for (mut a, tracker) in path_animation.iter_mut() {
+    for (mut a, tracker, blurred) in path_animation.iter_mut() {
         let total = a.tweenable().duration().as_secs_f32();
         if total == 0.0 {
             a.tweenable_mut()
                 .set_elapsed(Duration::from_secs_f32(total));
-        } else if let Some(frame) = tracker.value(current_frame) {
+        } else if let Some(frame) = tracker.value(sample_frame(blurred)) {
             a.state = AnimatorState::Playing;
             let secs = (frame / tracker.frame_rate()).max(0.0);
             a.tweenable_mut().set_elapsed(Duration::from_secs_f32(secs));
@@ -579,8 +845,8 @@ fn animate_system(
         }
     }
 
-    for (mut a, tracker) in draw_mode_animation.iter_mut() {
-        if let Some(frame) = tracker.value(current_frame) {
+    for (mut a, tracker, blurred) in draw_mode_animation.iter_mut() {
+        if let Some(frame) = tracker.value(sample_frame(blurred)) {
             a.state = AnimatorState::Playing;
             let secs = frame / tracker.frame_rate();
             a.tweenable_mut().set_elapsed(Duration::from_secs_f32(secs));
@@ -589,6 +855,25 @@ fn animate_system(
         }
     }
 
+    for (animator, handle, tracker, blurred) in gradient_animation.iter() {
+        let Some(frame) = tracker.value(sample_frame(blurred)) else {
+            continue;
+        };
+        let Some(material) = materials.get_mut(handle) else {
+            continue;
+        };
+        if let Some(gradient) = animator.fill.as_ref() {
+            material.gradient = GradientDataUniform::new(gradient, frame);
+            let ramp = material.gradient_ramp.as_ref();
+            update_gradient_ramp(&mut images, ramp, gradient, frame);
+        }
+        if let Some(gradient) = animator.stroke.as_ref() {
+            material.stroke_gradient = GradientDataUniform::new(gradient, frame);
+            let ramp = material.stroke_gradient_ramp.as_ref();
+            update_gradient_ramp(&mut images, ramp, gradient, frame);
+        }
+    }
+
     for (_, mut visibility, computed_visibility, audio_sink, tracker) in visibility_query.iter_mut()
     {
         let visible = tracker.value(current_frame).is_some();
@@ -607,7 +892,26 @@ fn animate_system(
         };
     }
 
-    info.current_time += delta;
+    if capturing {
+        info.advance(delta);
+    } else {
+        info.current_time += delta;
+    }
+}
+
+/// Rebake the lookup texture of `gradient` if its colors are animated
+fn update_gradient_ramp(
+    images: &mut Assets<Image>,
+    ramp: Option<&Handle<Image>>,
+    gradient: &lottie_core::prelude::Gradient,
+    frame: f32,
+) {
+    if !gradient.colors.colors.is_animated() {
+        return;
+    }
+    if let Some(image) = ramp.and_then(|handle| images.get_mut(handle)) {
+        image.data = gradient_ramp_data(&gradient.colors.colors.value(frame));
+    }
 }
 
 fn save_img(
@@ -615,12 +919,19 @@ fn save_img(
     info: Res<LottieAnimationInfo>,
     mut images: ResMut<Assets<Image>>,
     image_sender: Res<FrameSender>,
+    mut accumulator: ResMut<SampleAccumulator>,
     mut exit: EventWriter<AppExit>,
 ) {
-    // Capture has 3 frames latency
+    // Capture has 3 updates latency, frames with motion blur being rendered
+    // once per sample
     let delta = 1.0 / info.frame_rate;
-    let timestamp = info.current_time - 3.0 * delta;
-    if timestamp <= 0.0 {
+    let samples = info.samples() as i64;
+    let rendered =
+        (info.current_time * info.frame_rate).round() as i64 * samples + info.sample as i64;
+    let captured = rendered - 3;
+    let (frame, sample) = (captured.div_euclid(samples), captured.rem_euclid(samples));
+    let timestamp = frame as f32 * delta;
+    if frame <= 0 {
         return;
     } else if info.finished_once && timestamp * info.frame_rate > info.end_frame {
         if !image_sender.is_closed() {
@@ -656,6 +967,15 @@ fn save_img(
         } else {
             Cow::Borrowed(data)
         };
+        let data = if samples > 1 {
+            accumulator.add(&data);
+            if sample + 1 < samples {
+                continue;
+            }
+            Cow::Owned(accumulator.take_average())
+        } else {
+            data
+        };
         image_sender
             .sender
             .unbounded_send(FrameData {
@@ -1,6 +1,7 @@
 use bevy::asset::{Asset, AssetPath};
 use bevy::math::Vec2;
-use bevy::prelude::{Color, Handle, Image, UVec4, Vec4};
+use bevy::prelude::{Handle, Image, Mat4, UVec4, Vec4};
+use bevy::render::render_asset::RenderAssetUsages;
 // use bevy::reflect::{TypePath, TypeUuid};
 use bevy::reflect::TypePath;
 // use bevy
@@ -10,7 +11,7 @@ use bevy::render::render_resource::{
     VertexBufferLayout,
 };
 use bevy::sprite::{Material2d, Material2dKey};
-use lottie_core::prelude::GradientColor;
+use lottie_core::prelude::{Gradient, GradientColor, Rgba};
 use wgpu::*;
 
 // #[derive(AsBindGroup, TypeUuid, Clone, TypePath)]
@@ -31,6 +32,27 @@ pub struct LottieMaterial {
     pub mask_info: MaskDataUniform,
     #[uniform(4)]
     pub gradient: GradientDataUniform,
+    /// Colors of the gradient, sampled from all of its color and opacity stops
+    #[texture(5)]
+    #[sampler(6)]
+    pub gradient_ramp: Option<Handle<Image>>,
+    #[uniform(7)]
+    pub stroke_gradient: GradientDataUniform,
+    /// Colors of the stroke gradient
+    #[texture(8)]
+    #[sampler(9)]
+    pub stroke_gradient_ramp: Option<Handle<Image>>,
+    /// `BlendMode` of the layer, only used when `backdrop` is set
+    #[uniform(10)]
+    pub blend_mode: u32,
+    /// Everything rendered behind the layer, which is blended with its colors
+    #[texture(11)]
+    #[sampler(12)]
+    pub backdrop: Option<Handle<Image>>,
+    /// Perspective of the composition camera in world space, applied to the
+    /// shapes of 3D layers. Identity for 2D layers
+    #[uniform(13)]
+    pub camera: Mat4,
 }
 
 impl Material2d for LottieMaterial {
@@ -111,11 +133,25 @@ pub struct GradientDataKey {
 pub struct GradientDataUniform {
     pub start: Vec2,
     pub end: Vec2,
-    // #[size(runtime)]
-    // TODO: change this to a Vec (which compiles to a storage buffer) when bevy supports it
-    // tracking: https://github.com/bevyengine/bevy/issues/5499
+    /// 0 without gradient, otherwise the `GradientType` of the fill or stroke
     pub use_gradient: u32,
-    pub stops: [GradientDataStop; 2],
+    /// Focal point of a radial gradient
+    pub focal: Vec2,
+}
+
+impl GradientDataUniform {
+    /// Geometry of `gradient` at `frame`, in the coordinates of the shape
+    pub fn new(gradient: &Gradient, frame: f32) -> Self {
+        let start = gradient.start.value(frame);
+        let end = gradient.end.value(frame);
+        let focal = gradient.focal_point(frame);
+        GradientDataUniform {
+            start: Vec2::new(start.x, start.y),
+            end: Vec2::new(end.x, end.y),
+            use_gradient: gradient.gradient_ty as u32,
+            focal: Vec2::new(focal.x, focal.y),
+        }
+    }
 }
 
 #[derive(Clone, Default, ShaderType)]
@@ -128,30 +164,40 @@ pub struct MaskDataUniform {
     pub mask_total_count: u32,
 }
 
-#[derive(Clone, Default, ShaderType)]
-pub struct GradientDataStop {
-    pub offset: f32,
-    pub color: Color,
+/// Width in texels of a gradient lookup texture
+pub const GRADIENT_RAMP_WIDTH: u32 = 256;
+
+/// Bake gradient `stops` into the texels of a lookup texture row
+pub fn gradient_ramp_data(stops: &[GradientColor]) -> Vec<u8> {
+    (0..GRADIENT_RAMP_WIDTH)
+        .flat_map(|x| {
+            let Rgba { r, g, b, a } =
+                GradientColor::sample(stops, x as f32 / (GRADIENT_RAMP_WIDTH - 1) as f32);
+            [r, g, b, a]
+        })
+        .collect()
 }
 
-impl<'a> From<&'a GradientColor> for GradientDataStop {
-    fn from(stop: &'a GradientColor) -> Self {
-        GradientDataStop {
-            offset: stop.offset,
-            color: Color::Rgba {
-                red: stop.color.r as f32 / 255.0,
-                green: stop.color.g as f32 / 255.0,
-                blue: stop.color.b as f32 / 255.0,
-                alpha: stop.color.a as f32 / 255.0,
-            },
-        }
-    }
+/// Create a lookup texture for gradient `stops`
+pub fn gradient_ramp_image(stops: &[GradientColor]) -> Image {
+    Image::new(
+        Extent3d {
+            width: GRADIENT_RAMP_WIDTH,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        gradient_ramp_data(stops),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    )
 }
 
 impl From<&LottieMaterial> for GradientDataKey {
     fn from(material: &LottieMaterial) -> Self {
         Self {
-            use_gradient: material.gradient.stops.is_empty(),
+            use_gradient: material.gradient.use_gradient != 0
+                || material.stroke_gradient.use_gradient != 0,
         }
     }
 }
@@ -8,6 +8,7 @@ use lyon::geom::euclid::point2;
 use lyon::lyon_tessellation::*;
 use lyon::path::{Event, Path as LyonPath};
 
+use crate::effect::EffectMaterial;
 use crate::material::LottieMaterial;
 use crate::shape::*;
 
@@ -113,6 +114,7 @@ impl Plugin for LottiePlugin {
         app.insert_resource(FillTessRes(fill_tess))
             .insert_resource(StrokeTessRes(stroke_tess))
             .add_plugins(Material2dPlugin::<LottieMaterial>::default())
+            .add_plugins(Material2dPlugin::<EffectMaterial>::default())
             .add_systems(
                 PostUpdate,
                 mesh_shapes_system
@@ -136,11 +138,12 @@ fn mesh_shapes_system(
         if let Some(fill_mode) = tess_mode.fill.as_ref() {
             fill(&mut fill_tess, &path.0, fill_mode, &mut buffers);
         }
+        let fill_vertex_count = buffers.vertices.len();
         if let Some(stroke_mode) = tess_mode.stroke.as_ref() {
             stroke(&mut fill_tess, &path.0, stroke_mode, &mut buffers);
         }
 
-        mesh.0 = meshes.add(build_mesh(&buffers));
+        mesh.0 = meshes.add(build_mesh(&buffers, fill_vertex_count));
     }
 }
 
@@ -180,7 +183,10 @@ fn stroke(
     }
 }
 
-fn build_mesh(buffers: &VertexBuffers) -> Mesh {
+/// Build a mesh from tessellated `buffers`, where vertices past
+/// `fill_vertex_count` belong to the stroke. The first UV component tells the
+/// shader which one a vertex belongs to: 0.0 for fill and 1.0 for stroke
+fn build_mesh(buffers: &VertexBuffers, fill_vertex_count: usize) -> Mesh {
     let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD);
     // mesh.set_indices(Some(Indices::U32(buffers.indices.clone())));
     mesh.insert_indices(Indices::U32(buffers.indices.clone()));
@@ -204,7 +210,18 @@ fn build_mesh(buffers: &VertexBuffers) -> Mesh {
             .map(|v| v.color)
             .collect::<Vec<u32>>(),
     );
-    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; len]);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        (0..len)
+            .map(|index| {
+                if index < fill_vertex_count {
+                    [0.0, 0.0]
+                } else {
+                    [1.0, 0.0]
+                }
+            })
+            .collect::<Vec<[f32; 2]>>(),
+    );
     mesh
 }
 
@@ -8,7 +8,11 @@ use bevy::render::view::RenderLayers;
 use bevy_tweening::{Animator, EaseMethod, Sequence, Tracks, Tween};
 use lottie_core::prelude::{Transform as LottieTransform, *};
 
-use crate::lens::{OpacityLens, PathFactoryLens, PathLens, StrokeWidthLens, TransformLens};
+use crate::camera::Projected;
+use crate::lens::{
+    FillColorLens, OpacityLens, PathFactoryLens, PathLens, StrokeColorLens, StrokeWidthLens,
+    TextStyleLens, TransformLens,
+};
 use crate::material::*;
 use crate::plugin::MaskMarker;
 use crate::shape::ShapeBundle;
@@ -28,6 +32,10 @@ pub struct BevyStagedLayer<'a> {
     pub mask_count: u32,
     pub mask_registry: &'a mut HashMap<Id, u32>,
     pub zindex_window: f32,
+    /// Cameras this layer is visible to
+    pub render_layers: RenderLayers,
+    /// Backdrop texture of this layer if it has a blend mode
+    pub backdrop: Option<Handle<Image>>,
 }
 
 impl<'a> BevyStagedLayer<'a> {
@@ -40,12 +48,26 @@ impl<'a> BevyStagedLayer<'a> {
             .unwrap_or("Layer")
             .to_string();
         let mut c = commands.spawn(Name::new(name));
-        let mut initial_transform = Transform::from_matrix(self.layer.transform.value(0.0));
-        initial_transform.translation.z = self.layer.zindex as f32 * -1.0;
+        // The z position of 3D layers is kept until `camera_system` sorts them
+        // by depth
+        let mut initial_transform =
+            Transform::from_matrix(utils::flip_z(self.layer.transform.value(0.0)));
+        initial_transform.translation.z -= self.layer.zindex;
         if self.layer.is_mask {
-            initial_transform.translation.x += (*self.mask_index as f32) * self.model_size.x
-                / self.layer.transform_hierarchy.scale_x(0.0);
-            self.mask_registry.insert(self.layer.id, *self.mask_index);
+            // Items of the same matte source layer share their mask
+            let group = self.layer.mask_group.unwrap_or(self.layer.id);
+            let slot = match self.mask_registry.get(&group) {
+                Some(slot) => *slot,
+                None => {
+                    let slot = *self.mask_index;
+                    *self.mask_index += 1;
+                    self.mask_registry.insert(group, slot);
+                    slot
+                }
+            };
+            self.mask_registry.insert(self.layer.id, slot);
+            initial_transform.translation.x +=
+                (slot as f32) * self.model_size.x / self.layer.transform_hierarchy.scale_x(0.0);
         }
 
         log::trace!(
@@ -94,6 +116,7 @@ impl<'a> BevyStagedLayer<'a> {
                     };
                     bundle.sprite.flip_x = true;
                     c.insert(bundle);
+                    c.insert(self.render_layers);
                 } else if mime.mime_type().starts_with("audio") {
                     let source = AudioSource {
                         bytes: media.content.as_slice().into(),
@@ -119,17 +142,16 @@ impl<'a> BevyStagedLayer<'a> {
             global: Default::default(),
         });
         if let Some(animator) =
-            self.transform_animator(&self.layer.transform, initial_transform.translation.z, None)
+            self.transform_animator(&self.layer.transform, -self.layer.zindex, None)
         {
             c.insert(animator);
         }
 
-        if self.layer.is_mask {
-            *self.mask_index += 1;
-        }
-
         let id = c.id();
         c.insert(FrameTracker(self.layer.frame_transform_hierarchy.clone()));
+        if self.layer.motion_blur {
+            c.insert(MotionBlurred);
+        }
         c.insert(VisibilityBundle::default());
         Ok(id)
     }
@@ -220,6 +242,16 @@ impl<'a> BevyStagedLayer<'a> {
                 None
             },
             gradient: GradientDataUniform::default(),
+            gradient_ramp: None,
+            stroke_gradient: GradientDataUniform::default(),
+            stroke_gradient_ramp: None,
+            blend_mode: if self.backdrop.is_some() {
+                self.layer.blend_mode as u32
+            } else {
+                0
+            },
+            backdrop: self.backdrop.clone(),
+            camera: Mat4::IDENTITY,
         };
 
         if !self.layer.is_mask {
@@ -243,8 +275,11 @@ impl<'a> BevyStagedLayer<'a> {
             .to_string();
         let mut c = commands.spawn(Name::new(name));
 
+        c.insert(self.render_layers);
         if self.layer.is_mask {
-            c.insert(MaskMarker).insert(RenderLayers::from_layers(&[1]));
+            c.insert(MaskMarker);
+        } else if self.layer.is_3d {
+            c.insert(Projected(self.layer.id));
         }
 
         let mut initial_pos = Vector2D::new(0.0, 0.0);
@@ -325,29 +360,35 @@ impl<'a> BevyStagedLayer<'a> {
             }
         }
 
-        // register gradient texture if any
+        // register gradient textures if any
+        let mut gradient_animator = GradientAnimator::default();
         if let AnyFill::Gradient(g) = &shape.fill {
-            let start = g.gradient.start.initial_value();
-            let end = g.gradient.end.initial_value();
             let stops = g.gradient.colors.colors.initial_value();
-            assert!(stops.len() >= 2, "gradient stops must be at least 2");
-            let stops = stops.iter().map(GradientDataStop::from).collect::<Vec<_>>();
-            material.gradient.stops = [stops[0].clone(), stops[1].clone()];
-            material.gradient.start = Vec2::new(start.x, start.y);
-            material.gradient.end = Vec2::new(end.x, end.y);
-            material.gradient.use_gradient = 1;
+            material.gradient_ramp = Some(self.image_assets.add(gradient_ramp_image(&stops)));
+            material.gradient = GradientDataUniform::new(&g.gradient, 0.0);
+            if g.gradient.is_animated() {
+                gradient_animator.fill = Some(g.gradient.clone());
+            }
+        }
+        if let Some(AnyStroke::Gradient(g)) = &shape.stroke {
+            let stops = g.gradient.colors.colors.initial_value();
+            material.stroke_gradient_ramp =
+                Some(self.image_assets.add(gradient_ramp_image(&stops)));
+            material.stroke_gradient = GradientDataUniform::new(&g.gradient, 0.0);
+            if g.gradient.is_animated() {
+                gradient_animator.stroke = Some(g.gradient.clone());
+            }
+        }
+        if gradient_animator.fill.is_some() || gradient_animator.stroke.is_some() {
+            c.insert(gradient_animator);
         }
-        // let stroke_index = if let AnyFill::Gradient(g) = &shape.fill {
-        //     self.gradient
-        //         .register(&g.gradient, self.meshes, self.gradient_assets, commands)
-        //         as f32
-        // } else {
-        //     -1.0
-        // };
 
         let handle = self.material_assets.add(material);
         c.insert(handle);
         c.insert(FrameTracker(self.layer.frame_transform_hierarchy.clone()));
+        if self.layer.motion_blur {
+            c.insert(MotionBlurred);
+        }
         Some(c.id())
     }
 
@@ -360,7 +401,8 @@ impl<'a> BevyStagedLayer<'a> {
         let mut tweens = vec![];
         let frame_rate = self.layer.frame_rate;
         let mask_offset = if self.layer.is_mask {
-            Vec2::new(*self.mask_index as f32 * self.model_size.x, 0.0)
+            let slot = self.mask_registry.get(&self.layer.id).copied().unwrap_or(0);
+            Vec2::new(slot as f32 * self.model_size.x, 0.0)
         } else {
             Vec2::ZERO
         };
@@ -406,6 +448,25 @@ impl<'a> BevyStagedLayer<'a> {
             }
         }
 
+        if let AnyFill::Solid(fill) = &shape.fill {
+            if fill.color.is_animated() {
+                tweens.push(fill.color.keyframes.tween(
+                    self.layer.end_frame,
+                    frame_rate,
+                    |start, end, _, _| FillColorLens { start, end },
+                ));
+            }
+        }
+        if let Some(AnyStroke::Solid(stroke)) = shape.stroke.as_ref() {
+            if stroke.color.is_animated() {
+                tweens.push(stroke.color.keyframes.tween(
+                    self.layer.end_frame,
+                    frame_rate,
+                    |start, end, _, _| StrokeColorLens { start, end },
+                ));
+            }
+        }
+
         let opacity = OpacityHierarchy::from(&self.layer.transform_hierarchy);
         if opacity.is_animated() {
             let opacity_lens = OpacityLens {
@@ -424,6 +485,44 @@ impl<'a> BevyStagedLayer<'a> {
             tweens.push(Sequence::from_single(tween));
         }
 
+        // Text animators come last to override the colors and opacities
+        if let Shape::Path {
+            text_range: Some(info),
+            ..
+        } = &shape.shape.shape
+        {
+            if info.ranges.iter().any(|range| range.style.is_some()) {
+                // Text documents only have solid colors, animated colors are
+                // not blended into gradients
+                if matches!(shape.fill, AnyFill::Gradient(_))
+                    || matches!(shape.stroke, Some(AnyStroke::Gradient(_)))
+                {
+                    log::warn!(
+                        "glyph {:?} of layer {:?} has a gradient, its animated colors are ignored",
+                        shape.shape.name,
+                        self.layer.name
+                    );
+                }
+                let text_lens = TextStyleLens {
+                    info: info.clone(),
+                    frames: self.layer.end_frame,
+                    opacity: OpacityHierarchy::from(&self.layer.transform_hierarchy),
+                    fill: match &shape.fill {
+                        AnyFill::Solid(fill) => Some(fill.clone()),
+                        _ => None,
+                    },
+                    stroke: match shape.stroke.as_ref() {
+                        Some(AnyStroke::Solid(stroke)) => Some(stroke.clone()),
+                        _ => None,
+                    },
+                };
+                let secs = (text_lens.frames / self.layer.frame_rate).max(f32::EPSILON);
+                let tween =
+                    Tween::new(EaseMethod::Linear, Duration::from_secs_f32(secs), text_lens);
+                tweens.push(Sequence::from_single(tween));
+            }
+        }
+
         if !tweens.is_empty() {
             let tracks = Tracks::new(tweens);
             Some(Animator::new(tracks).with_state(AnimatorState::Paused))
@@ -454,7 +553,20 @@ impl<'a> BevyStagedLayer<'a> {
 }
 
 #[derive(Component, Deref)]
-pub struct FrameTracker(FrameTransformHierarchy);
+pub struct FrameTracker(pub FrameTransformHierarchy);
+
+/// Layers and shapes with motion blur, animated at the samples of the shutter
+/// instead of the frame
+#[derive(Component)]
+pub struct MotionBlurred;
 
 #[derive(Component)]
 pub struct LottieAudio;
+
+/// Gradients of a shape whose geometry changes over time, they are written
+/// into the shape's material every frame
+#[derive(Component, Default)]
+pub struct GradientAnimator {
+    pub fill: Option<Gradient>,
+    pub stroke: Option<Gradient>,
+}
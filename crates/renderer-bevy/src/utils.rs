@@ -1,4 +1,4 @@
-use bevy::prelude::Color;
+use bevy::prelude::{Color, Mat4, Vec3};
 use lottie_core::prelude::{
     AnyFill, AnyStroke, FillRule as LottieFillRule, LineCap as LottieLineCap,
     LineJoin as LottieLineJoin, Rgb, StyledShape,
@@ -8,6 +8,13 @@ use lyon::tessellation::{FillOptions, LineCap, LineJoin, StrokeOptions};
 
 use crate::shape::{DrawMode, Fill, SolidOrGradient, Stroke};
 
+/// Convert a transform of lottie, whose z axis points away from the viewer,
+/// to bevy's coordinates where it points toward the viewer
+pub fn flip_z(matrix: Mat4) -> Mat4 {
+    let flip = Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0));
+    flip * matrix * flip
+}
+
 /// Get an initial draw mode for a shape. If fill/stroke uses gradient fill, a
 /// default white color is used in this method
 pub fn shape_draw_mode(shape: &StyledShape) -> DrawMode {
@@ -17,7 +24,10 @@ pub fn shape_draw_mode(shape: &StyledShape) -> DrawMode {
             let fill = fill.color.initial_value();
             (fill, fill_opacity)
         }
-        AnyFill::Gradient(gradient) => (Rgb::new_u8(255, 255, 255), 255),
+        AnyFill::Gradient(gradient) => {
+            let fill_opacity = (gradient.opacity.initial_value() / 100.0 * 255.0) as u8;
+            (Rgb::new_u8(255, 255, 255), fill_opacity)
+        }
     };
     let fill_rule = match &shape.fill {
         AnyFill::Solid(fill) => &fill.fill_rule,
@@ -46,7 +56,10 @@ pub fn shape_draw_mode(shape: &StyledShape) -> DrawMode {
                     let stroke = stroke.color.initial_value();
                     (stroke, stroke_opacity)
                 }
-                AnyStroke::Gradient(gradient) => (Rgb::new_u8(255, 255, 255), 255),
+                AnyStroke::Gradient(gradient) => {
+                    let stroke_opacity = (gradient.opacity.initial_value() / 100.0 * 255.0) as u8;
+                    (Rgb::new_u8(255, 255, 255), stroke_opacity)
+                }
             };
 
             let mut result = Stroke {
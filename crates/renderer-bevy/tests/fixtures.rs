@@ -19,6 +19,7 @@ fn check_fixture(
             target: lottie_core::Target::Default,
             filename: String::from("test.webp"),
             frame: None,
+            ..Default::default()
         }),
     );
     renderer.render();